@@ -41,23 +41,23 @@ pub mod type_id {
         pub fn of<T: TypeIdTy + ?Sized>() -> Self {
             T::get_type_id()
         }
+        ///Derives the id from an FNV-1a hash of the type's name.
+        /// Ids are persisted, so neither the hash nor its input may change between builds or toolchains.
+        pub fn from_name(name: &str) -> Self {
+            const OFFSET: u64 = 0xcbf29ce484222325;
+            const PRIME: u64 = 0x100000001b3;
+            let id = name.bytes().fold(OFFSET, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(PRIME)
+            });
+            TypeId { id }
+        }
     }
     impl<T: 'static + ?Sized> TypeIdTy for T {
         default fn get_type_id() -> TypeId {
-            let ti = std::any::TypeId::of::<T>();
-            //reintrepret as u64
-            unsafe {
-                let id: u64 = std::mem::transmute(ti);
-                TypeId { id }
-            }
+            TypeId::from_name(std::any::type_name::<T>())
         }
         default fn get_type_id_ref(&self) -> TypeId {
-            let ti = std::any::TypeId::of::<T>();
-            //reintrepret as u64
-            unsafe {
-                let id: u64 = std::mem::transmute(ti);
-                TypeId { id }
-            }
+            TypeId::from_name(std::any::type_name::<T>())
         }
         default fn get_name() -> &'static str {
             std::any::type_name::<T>()
@@ -252,4 +252,12 @@ mod test_super {
     }
     #[test]
     fn test_get_type_name() {}
+    #[test]
+    fn test_type_id_is_stable() {
+        use type_id::{TypeId, TypeIdTy};
+        assert_eq!(TypeId::from_name("").id, 0xcbf29ce484222325);
+        assert_eq!(TypeId::from_name("a").id, 0xaf63dc4c8601ec8c);
+        assert_eq!(u32::get_type_id(), TypeId::from_name("u32"));
+        assert_ne!(u32::get_type_id(), u64::get_type_id());
+    }
 }
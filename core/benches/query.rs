@@ -0,0 +1,67 @@
+//! Compares table-based queries against the linear entity scan `Entman::query` used before entities
+//! were partitioned into archetype tables.
+//! Run with `cargo bench -p nvcore`.
+#![feature(test)]
+extern crate test;
+
+use common::type_id::TypeIdTy;
+use nvcore::ecs::component::components::*;
+use nvcore::ecs::query::Query;
+use nvcore::ecs::{Entman, Signature};
+use test::Bencher;
+
+const ENTITY_COUNT: usize = 20_000;
+//one in every MATCH_EVERY entities matches the query
+const MATCH_EVERY: usize = 100;
+
+fn populate() -> Entman {
+    let mut em = Entman::new();
+    for i in 0..ENTITY_COUNT {
+        let e = em.add_entity();
+        em.add_component_default::<StringFieldComponent>(e).unwrap();
+        if i % MATCH_EVERY == 0 {
+            em.add_component_default::<NameComponent>(e).unwrap();
+            em.add_component_default::<LocationComponent>(e).unwrap();
+        } else if i % 3 == 0 {
+            em.add_component_default::<NumericalFieldComponent>(e)
+                .unwrap();
+        }
+    }
+    em
+}
+
+#[bench]
+fn bench_query_tables(b: &mut Bencher) {
+    let em = populate();
+    let q = Query::<(StringFieldComponent, NameComponent, LocationComponent)>::new();
+    b.iter(|| {
+        let res = em.query(&q);
+        assert_eq!(res.iter().count(), ENTITY_COUNT / MATCH_EVERY);
+    });
+}
+
+///The previous implementation: visit every entity, and compare its signature to the query's
+#[bench]
+fn bench_query_linear_scan(b: &mut Bencher) {
+    let em = populate();
+    let sig = Signature::from(vec![
+        StringFieldComponent::get_type_id(),
+        NameComponent::get_type_id(),
+        LocationComponent::get_type_id(),
+    ]);
+    b.iter(|| {
+        let mut matches = Vec::new();
+        for id in em.get_all_living_entities() {
+            let entity = em.get_entity_clone(id).unwrap();
+            if entity.get_signature().get_canonical_signature() == sig.get_canonical_signature() {
+                matches.push((
+                    id,
+                    em.get_component_ref::<StringFieldComponent>(id).unwrap(),
+                    em.get_component_ref::<NameComponent>(id).unwrap(),
+                    em.get_component_ref::<LocationComponent>(id).unwrap(),
+                ));
+            }
+        }
+        assert_eq!(matches.len(), ENTITY_COUNT / MATCH_EVERY);
+    });
+}
@@ -1,7 +1,9 @@
 /*! The [actions] module contains a number of preconfigured [ActionTy]'s for convenience purposes*/
 use super::*;
 use crate::{
-    ecs::{Component, ComponentTy, ComponentTyReqs, DynamicComponent, EntityOwned, Id},
+    ecs::{
+        Component, ComponentId, ComponentTy, ComponentTyReqs, DynamicComponent, EntityOwned, Id,
+    },
     mir::Mir,
 };
 use ::common::exports::*;
@@ -22,34 +24,29 @@ pub fn ae_add_entity(mir: &mut Mir, p: ()) -> Result<Box<(AddEntityResrc, Id)>>
 }
 ///Undo add entity function.
 pub fn au_add_entity(mir: &mut Mir, r: Resrc<&AddEntityResrc>) -> Result<()> {
-    //remove entity
-    mir.em.remove_entity(r.entity);
-    Ok(())
+    //the entity was created by this action, so it can be deleted for good
+    mir.em.purge_entity(r.entity)
 }
 ///Remove an entity with the given [Id]
-pub fn ae_remove_entity(mir: &mut Mir, id: Id) -> Result<Box<(EntityOwned, ())>> {
-    let entity_owned = mir.em.get_entity_owned(id)?;
-    mir.em.remove_entity(id);
-    Ok(Box::new((entity_owned, ())))
+pub fn ae_remove_entity(mir: &mut Mir, id: Id) -> Result<Box<(Id, ())>> {
+    mir.em.remove_entity(id)?;
+    Ok(Box::new((id, ())))
 }
-///Undo remove entity function.
-pub fn au_remove_entity(mir: &mut Mir, entity_owned: Resrc<&EntityOwned>) -> Result<()> {
-    mir.em.entity_from_owned(entity_owned.into_type().clone())?;
-    Ok(())
+///Undo remove entity function. The entity is restored with the same generation and component ids.
+pub fn au_remove_entity(mir: &mut Mir, id: Resrc<&Id>) -> Result<()> {
+    mir.em.restore_entity(**id)
 }
 ///Add component to an entity with the given [Id]
 pub fn ae_add_component<C: Clone + ComponentTyReqs + serde::Serialize + Clone>(
     mir: &mut Mir,
     p: (Id, C),
-) -> Result<Box<((Id, common::type_id::TypeId), ())>> {
-    let tid = p.1.get_component_type_id();
-    mir.em.add_component(p.0, p.1)?;
-    let dynamic_component = mir.em.get_component_mut::<C>(p.0)?.into_dynamic();
-    Ok(Box::new(((p.0, tid), ())))
+) -> Result<Box<(ComponentId, ())>> {
+    let id = mir.em.add_component(p.0, p.1)?;
+    Ok(Box::new((id, ())))
 }
 ///Undo add component to an entity with the given [Id]
-pub fn au_add_component(mir: &mut Mir, r: Resrc<&(Id, common::type_id::TypeId)>) -> Result<()> {
-    mir.em.remove_component_by_type_id(r.1, r.0 .0)
+pub fn au_add_component(mir: &mut Mir, r: Resrc<&ComponentId>) -> Result<()> {
+    mir.em.remove_component_by_id(**r)
 }
 
 type Executor<P, Rsrc: ResrcTy, Rv: RvTy> = fn(&mut Mir, P) -> Result<Box<(Rsrc, Rv)>>;
@@ -88,7 +85,7 @@ impl ActionConstructorTy for AddEntityConstructor {
 pub struct RemoveEntityConstructor {}
 impl ActionConstructorTy for RemoveEntityConstructor {
     type Ac = StaticAction<Self::Rsrc, Self::P, Self::Rv, Self::E, Self::U>;
-    type Rsrc = EntityOwned;
+    type Rsrc = Id;
     type P = Id;
     fn construct(&self) -> Self::Ac {
         Self::Ac::new_static(
@@ -105,7 +102,7 @@ pub struct AddComponentConstructor<T: ComponentTyReqs + Clone + serde::Serialize
 }
 impl<T: ComponentTy + Clone + serde::Serialize> ActionConstructorTy for AddComponentConstructor<T> {
     type Ac = StaticAction<Self::Rsrc, Self::P, Self::Rv, Self::E, Self::U>;
-    type Rsrc = (Id, common::type_id::TypeId);
+    type Rsrc = ComponentId;
     type P = (Id, T);
 
     fn construct(&self) -> Self::Ac {
@@ -33,8 +33,7 @@ pub fn test_fn(mir: &mut Mir, p: TestParam) -> Result<Box<(TestRsrc, ())>> {
     Ok(Box::new((rsrc, ())))
 }
 pub fn undo(mir: &mut Mir, rsrc: Resrc<&TestRsrc>) -> Result<()> {
-    mir.em.remove_entity(rsrc.ent_id);
-    Ok(())
+    mir.em.remove_entity(rsrc.ent_id)
 }
 
 #[test]
@@ -0,0 +1,245 @@
+//! Compiles a [Manuscript] into a single document, ready to be handed to an editor.
+//!
+//! The progressions are written in the order they are told. A progression of the kind
+//! [EProgressionKind::Chapter] opens a new chapter, and one of the kind [EProgressionKind::Scene] continues
+//! the current chapter after a scene separator. Progressions can be left out by their tags or status, so that
+//! only the finished chapters, or those marked for a reader, are compiled.
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EOutputFormat {
+    Markdown,
+    PlainText,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileOptions {
+    pub format: EOutputFormat,
+    ///Writes the name of every progression as its heading
+    pub headings: bool,
+    ///Writes the metadata of the project before the text, as YAML front matter in Markdown
+    pub front_matter: bool,
+    ///Written between chapters that have no heading
+    pub chapter_separator: String,
+    ///Written between the scenes of a chapter
+    pub scene_separator: String,
+    ///If not empty, only progressions with at least one of these tags are compiled
+    pub include_tags: Vec<String>,
+    ///Progressions with any of these tags are left out
+    pub exclude_tags: Vec<String>,
+    ///If not empty, only progressions with one of these statuses are compiled
+    pub include_statuses: Vec<EProgressionStatus>,
+    ///Progressions with any of these statuses are left out
+    pub exclude_statuses: Vec<EProgressionStatus>,
+}
+impl CompileOptions {
+    pub fn new(format: EOutputFormat) -> Self {
+        CompileOptions {
+            format,
+            headings: true,
+            front_matter: true,
+            chapter_separator: String::from("---"),
+            scene_separator: String::from("* * *"),
+            include_tags: Vec::new(),
+            exclude_tags: Vec::new(),
+            include_statuses: Vec::new(),
+            exclude_statuses: Vec::new(),
+        }
+    }
+    ///Returns whether the progression passes the tag and status filters
+    pub fn includes(&self, progression: &Progression) -> bool {
+        let status = progression.get_status();
+        (self.include_tags.is_empty() || self.include_tags.iter().any(|t| progression.has_tag(t)))
+            && !self.exclude_tags.iter().any(|t| progression.has_tag(t))
+            && (self.include_statuses.is_empty() || self.include_statuses.contains(&status))
+            && !self.exclude_statuses.contains(&status)
+    }
+}
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions::new(EOutputFormat::Markdown)
+    }
+}
+
+fn write_heading(name: &str, kind: EProgressionKind, format: EOutputFormat) -> String {
+    match (format, kind) {
+        (EOutputFormat::Markdown, EProgressionKind::Chapter) => format!("# {}", name),
+        (EOutputFormat::Markdown, EProgressionKind::Scene) => format!("## {}", name),
+        //plain text headings are underlined, like the setext headings of Markdown
+        (EOutputFormat::PlainText, kind) => {
+            let underline = match kind {
+                EProgressionKind::Chapter => "=",
+                EProgressionKind::Scene => "-",
+            };
+            format!("{}\n{}", name, underline.repeat(name.chars().count()))
+        }
+    }
+}
+fn write_front_matter(meta: &ProjectMetaData, format: EOutputFormat) -> Option<String> {
+    let fields = [
+        ("title", meta.get_name()),
+        ("author", meta.get_author()),
+        ("version", meta.get_version()),
+        ("description", meta.get_description()),
+    ];
+    let fields = fields
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .collect::<Vec<_>>();
+    if fields.is_empty() {
+        return None;
+    }
+    Some(match format {
+        //JSON strings are valid YAML, and escape whatever the values contain
+        EOutputFormat::Markdown => {
+            let lines = fields
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, serde_json::Value::from(*value)))
+                .collect::<Vec<_>>();
+            format!("---\n{}\n---", lines.join("\n"))
+        }
+        EOutputFormat::PlainText => {
+            let lines = fields
+                .iter()
+                .map(|(key, value)| match *key {
+                    "title" => value.to_string(),
+                    "author" => format!("by {}", value),
+                    "version" => format!("Version {}", value),
+                    _ => format!("\n{}", value),
+                })
+                .collect::<Vec<_>>();
+            lines.join("\n")
+        }
+    })
+}
+
+///Assembles the progressions of the manuscript that pass the filters of the options into a single document
+pub fn compile(
+    manuscript: &Manuscript,
+    meta: Option<&ProjectMetaData>,
+    options: &CompileOptions,
+) -> String {
+    let mut blocks = Vec::new();
+    if options.front_matter {
+        blocks.extend(meta.and_then(|m| write_front_matter(m, options.format)));
+    }
+    let mut first = true;
+    for progression in manuscript
+        .get_ordered_progressions()
+        .into_iter()
+        .filter(|p| options.includes(p))
+    {
+        let kind = progression.get_kind();
+        let separator = match kind {
+            EProgressionKind::Chapter if !options.headings => &options.chapter_separator,
+            EProgressionKind::Chapter => "",
+            EProgressionKind::Scene => &options.scene_separator,
+        };
+        if !first && !separator.is_empty() {
+            blocks.push(separator.to_string());
+        }
+        first = false;
+        if options.headings {
+            blocks.push(write_heading(progression.get_name(), kind, options.format));
+        }
+        let text = progression.get_text().trim();
+        if !text.is_empty() {
+            blocks.push(text.to_string());
+        }
+    }
+    let mut document = blocks.join("\n\n");
+    document.push('\n');
+    document
+}
+
+#[cfg(test)]
+mod test_compile {
+    use super::*;
+
+    fn get_project() -> (Project, Id) {
+        let mut project = Project::new_empty();
+        let mut meta = ProjectMetaData::new();
+        meta.set_name("The \"Long\" Road");
+        meta.set_author("A. Writer");
+        project.set_metadata(meta);
+        let mut manuscript = Manuscript::new(String::from("Draft"), String::new());
+        let chapters = [
+            (
+                "Departure",
+                EProgressionKind::Chapter,
+                EProgressionStatus::Final,
+                "They left at dawn.\n",
+            ),
+            (
+                "Night",
+                EProgressionKind::Scene,
+                EProgressionStatus::Final,
+                "The fire burned low.",
+            ),
+            (
+                "Notes",
+                EProgressionKind::Chapter,
+                EProgressionStatus::Outline,
+                "Something happens here.",
+            ),
+            (
+                "Arrival",
+                EProgressionKind::Chapter,
+                EProgressionStatus::Revised,
+                "The gates opened.",
+            ),
+        ];
+        for (name, kind, status, text) in chapters {
+            let mut p =
+                Progression::new_draft(String::from(name), String::new(), String::from(text));
+            p.set_kind(kind);
+            p.set_status(status);
+            if name != "Arrival" {
+                p.add_tag("editor");
+            }
+            manuscript.add_progression(p).unwrap();
+        }
+        let id = manuscript.id;
+        project.add_manuscript(manuscript);
+        (project, id)
+    }
+
+    #[test]
+    fn test_compile_markdown() {
+        let (project, id) = get_project();
+        let mut options = CompileOptions::new(EOutputFormat::Markdown);
+        options.exclude_statuses.push(EProgressionStatus::Outline);
+        let document = project.compile_manuscript(id, &options).unwrap();
+        assert_eq!(
+            document,
+            "---\ntitle: \"The \\\"Long\\\" Road\"\nauthor: \"A. Writer\"\n---\n\n\
+             # Departure\n\nThey left at dawn.\n\n* * *\n\n## Night\n\nThe fire burned low.\n\n\
+             # Arrival\n\nThe gates opened.\n"
+        );
+        assert!(project
+            .compile_manuscript(uuid::gen_128(), &options)
+            .is_err());
+    }
+    #[test]
+    fn test_compile_plain_text() {
+        let (project, id) = get_project();
+        let mut options = CompileOptions::new(EOutputFormat::PlainText);
+        options.headings = false;
+        options.front_matter = false;
+        options.include_tags.push(String::from("editor"));
+        let document = project.compile_manuscript(id, &options).unwrap();
+        assert_eq!(
+            document,
+            "They left at dawn.\n\n* * *\n\nThe fire burned low.\n\n---\n\nSomething happens here.\n"
+        );
+        options.headings = true;
+        options.front_matter = true;
+        options.include_tags.clear();
+        options.include_statuses = vec![EProgressionStatus::Revised];
+        let document = project.compile_manuscript(id, &options).unwrap();
+        assert_eq!(
+            document,
+            "The \"Long\" Road\nby A. Writer\n\nArrival\n=======\n\nThe gates opened.\n"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Change detection for the ECS. Every change to the entities or components of an [Entman](super::Entman)
+//! advances its change [Tick] and is recorded as an [EEntityEvent], so that frontends can refresh
+//! incrementally by asking for everything that happened since the last tick they saw.
+//!
+//! Components are marked as changed when they are borrowed mutably, not when they are written to. Every mutable
+//! path marks them up front: [Entman::get_component_mut](super::Entman::get_component_mut),
+//! [Entman::get_component_by_id_mut](super::Entman::get_component_by_id_mut),
+//! [Entman::query_mut](super::Entman::query_mut) and the mutable queries of systems. A borrow that writes
+//! nothing is still reported as a change.
+use super::*;
+
+///A monotonically increasing counter, advanced by every change made to an [Entman](super::Entman)
+pub type Tick = u64;
+
+///The ticks at which a component was added and last changed
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    bincode::Encode,
+    bincode::Decode,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(crate = "common::exports::serde")]
+#[bincode(crate = "common::exports::bincode")]
+pub struct ComponentTicks {
+    pub added: Tick,
+    pub changed: Tick,
+}
+impl ComponentTicks {
+    pub fn new(tick: Tick) -> Self {
+        ComponentTicks {
+            added: tick,
+            changed: tick,
+        }
+    }
+    pub fn is_added_since(&self, tick: Tick) -> bool {
+        self.added > tick
+    }
+    ///Returns true if the component was added or changed after the given tick
+    pub fn is_changed_since(&self, tick: Tick) -> bool {
+        self.changed > tick
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EEntityEvent {
+    EntityCreated(Id),
+    ComponentAdded {
+        entity: Id,
+        component: ComponentId,
+        type_id: TypeId,
+    },
+    ///Emitted whenever a component is borrowed mutably, whether or not it is then written to
+    ComponentChanged {
+        entity: Id,
+        component: ComponentId,
+        type_id: TypeId,
+    },
+    ComponentRemoved {
+        entity: Id,
+        component: ComponentId,
+        type_id: TypeId,
+    },
+    ///Emitted once an entity and all of its components have been removed
+    EntityRemoved(Id),
+    ///Emitted once a removed entity and all of its components have been restored
+    EntityRestored(Id),
+}
+
+///The log of every [EEntityEvent] since the last time it was drained. Frontends are expected to drain it
+/// regularly, since nothing else does.
+///
+/// Alongside the events, the log remembers the last tick at which each entity's components of each type were
+/// added, changed or removed. Unlike the events this is never drained, and only grows with the number of
+/// entities, so caches derived from the components can catch up from their own tick regardless of when the
+/// events are drained.
+#[derive(Default)]
+pub struct ChangeLog {
+    tick: Tick,
+    events: Vec<(Tick, EEntityEvent)>,
+    touched: HashMap<TypeId, HashMap<Id, Tick>>,
+}
+impl ChangeLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn get_tick(&self) -> Tick {
+        self.tick
+    }
+    ///Records the event, returning the tick it happened at
+    pub fn record(&mut self, event: EEntityEvent) -> Tick {
+        self.tick += 1;
+        match &event {
+            EEntityEvent::ComponentAdded {
+                entity, type_id, ..
+            }
+            | EEntityEvent::ComponentChanged {
+                entity, type_id, ..
+            }
+            | EEntityEvent::ComponentRemoved {
+                entity, type_id, ..
+            } => {
+                self.touched
+                    .entry(*type_id)
+                    .or_default()
+                    .insert(*entity, self.tick);
+            }
+            //the components of a removed entity leave storage without events of their own
+            EEntityEvent::EntityRemoved(entity) | EEntityEvent::EntityRestored(entity) => {
+                for touched in self.touched.values_mut() {
+                    if let Some(tick) = touched.get_mut(entity) {
+                        *tick = self.tick;
+                    }
+                }
+            }
+            EEntityEvent::EntityCreated(_) => {}
+        }
+        self.events.push((self.tick, event));
+        self.tick
+    }
+    ///Returns every event that happened after the given tick, oldest first
+    pub fn get_events_since(&self, tick: Tick) -> impl Iterator<Item = &(Tick, EEntityEvent)> {
+        //events are ordered by tick
+        let start = self.events.partition_point(|(t, _)| *t <= tick);
+        self.events[start..].iter()
+    }
+    ///Returns every entity whose components of the given type were added, changed or removed after the given
+    /// tick, or that was removed or restored since
+    pub fn get_touched_since(&self, type_id: TypeId, tick: Tick) -> impl Iterator<Item = Id> + '_ {
+        self.touched
+            .get(&type_id)
+            .into_iter()
+            .flatten()
+            .filter(move |(_, t)| **t > tick)
+            .map(|(entity, _)| *entity)
+    }
+    pub fn drain(&mut self) -> Vec<(Tick, EEntityEvent)> {
+        std::mem::take(&mut self.events)
+    }
+}
+//Only the tick is persisted, so that ticks keep increasing across saves. The events and touched ticks are transient.
+impl bincode::Encode for ChangeLog {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        self.tick.encode(encoder)
+    }
+}
+impl bincode::Decode for ChangeLog {
+    fn decode<D: bincode::de::Decoder>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(ChangeLog {
+            tick: Tick::decode(decoder)?,
+            events: Vec::new(),
+            touched: HashMap::new(),
+        })
+    }
+}
@@ -60,6 +60,18 @@ impl ArchetypeDescriptor {
         self.signature.insert(TypeId::of::<T>());
         self
     }
+    ///Adds a component of a runtime defined schema to the archetype
+    pub fn with_custom_component(
+        mut self,
+        schema: &super::schema::ComponentSchema,
+        values: std::collections::BTreeMap<String, super::schema::FieldValue>,
+    ) -> anyhow::Result<Self> {
+        let component = schema.instantiate(values)?;
+        self.signature.push(component.get_type_id_ref());
+        self.components
+            .push(EComponentGraphTypes::CustomComponent(component));
+        Ok(self)
+    }
     pub fn take_components(self) -> Vec<EComponentGraphTypes> {
         self.components
     }
@@ -0,0 +1,378 @@
+//! Exports the relationship graph of an [Entman] for rendering, as Graphviz DOT, GraphML, or a json list of
+//! nodes and edges. Nodes are labelled with the name of their entity, and edges are styled by the kind of
+//! their relationship.
+use super::super::*;
+use super::components::{CharacterNameComponent, NameComponent};
+use super::relationship::*;
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EGraphFormat {
+    Dot,
+    GraphML,
+    Json,
+}
+
+///Selects the part of the relationship graph to export
+#[derive(Debug, Default, Clone)]
+pub struct GraphExportOptions {
+    ///Only relationships of these kinds are exported, or all of them if empty
+    pub relations: Vec<ERelationship>,
+    ///Only entities within [max_distance] relationships of [root] are exported
+    pub root: Option<Id>,
+    pub max_distance: Option<usize>,
+}
+impl GraphExportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_relation(mut self, relation: ERelationship) -> Self {
+        self.relations.push(relation);
+        self
+    }
+    pub fn with_root(mut self, root: Id, max_distance: Option<usize>) -> Self {
+        self.root = Some(root);
+        self.max_distance = max_distance;
+        self
+    }
+}
+
+///How an edge is drawn
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(crate = "common::exports::serde")]
+pub struct EdgeStyle {
+    pub color: &'static str,
+    ///One of "solid", "dashed", "dotted" or "bold", as understood by Graphviz
+    pub line: &'static str,
+    pub directed: bool,
+}
+impl EdgeStyle {
+    pub fn of(relation: &ERelationship) -> Self {
+        let (color, line, directed) = match relation {
+            ERelationship::MajorMinor(Major::Parent, Minor::Child) => ("black", "solid", true),
+            ERelationship::MajorMinor(_, _) => ("gray40", "solid", true),
+            ERelationship::Symmetric(Symmetric::Friend) => ("darkgreen", "dashed", false),
+            ERelationship::Symmetric(Symmetric::Enemy) => ("red", "dashed", false),
+            ERelationship::Symmetric(Symmetric::Sibling) => ("blue", "dotted", false),
+            ERelationship::Symmetric(Symmetric::Spouse) => ("purple", "bold", false),
+            ERelationship::Symmetric(Symmetric::Custom(_)) => ("gray40", "dashed", false),
+        };
+        EdgeStyle {
+            color,
+            line,
+            directed,
+        }
+    }
+}
+///Returns a short, human readable name for the kind of relationship
+pub fn get_relation_label(relation: &ERelationship) -> String {
+    match relation {
+        ERelationship::MajorMinor(Major::Parent, Minor::Child) => "parent".to_string(),
+        ERelationship::MajorMinor(Major::Custom(c), _) => c.name.clone(),
+        ERelationship::MajorMinor(_, Minor::Custom(c)) => c.name.clone(),
+        ERelationship::Symmetric(Symmetric::Friend) => "friend".to_string(),
+        ERelationship::Symmetric(Symmetric::Enemy) => "enemy".to_string(),
+        ERelationship::Symmetric(Symmetric::Sibling) => "sibling".to_string(),
+        ERelationship::Symmetric(Symmetric::Spouse) => "spouse".to_string(),
+        ERelationship::Symmetric(Symmetric::Custom(c)) => c.name.clone(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(crate = "common::exports::serde")]
+pub struct ExportedNode {
+    ///The id of the entity, as a string so that it survives json parsers without 128 bit integers
+    pub id: String,
+    pub label: String,
+}
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(crate = "common::exports::serde")]
+pub struct ExportedEdge {
+    pub source: String,
+    pub target: String,
+    pub label: String,
+    pub style: EdgeStyle,
+}
+///The selected part of the relationship graph, ready to be written in any [EGraphFormat]
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize)]
+#[serde(crate = "common::exports::serde")]
+pub struct ExportedGraph {
+    pub nodes: Vec<ExportedNode>,
+    pub edges: Vec<ExportedEdge>,
+}
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+impl ExportedGraph {
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph relationships {\n");
+        for n in self.nodes.iter() {
+            writeln!(
+                out,
+                "    \"{}\" [label=\"{}\"];",
+                n.id,
+                escape_dot(&n.label)
+            )
+            .unwrap();
+        }
+        for e in self.edges.iter() {
+            writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"{}\", color=\"{}\", style=\"{}\", dir=\"{}\"];",
+                e.source,
+                e.target,
+                escape_dot(&e.label),
+                e.style.color,
+                e.style.line,
+                if e.style.directed { "forward" } else { "none" }
+            )
+            .unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"label\" for=\"all\" attr.name=\"label\" attr.type=\"string\"/>\n",
+            "  <key id=\"color\" for=\"edge\" attr.name=\"color\" attr.type=\"string\"/>\n",
+            "  <key id=\"style\" for=\"edge\" attr.name=\"style\" attr.type=\"string\"/>\n",
+            "  <graph id=\"relationships\" edgedefault=\"directed\">\n"
+        ));
+        for n in self.nodes.iter() {
+            writeln!(
+                out,
+                "    <node id=\"{}\"><data key=\"label\">{}</data></node>",
+                n.id,
+                escape_xml(&n.label)
+            )
+            .unwrap();
+        }
+        for (i, e) in self.edges.iter().enumerate() {
+            writeln!(
+                out,
+                "    <edge id=\"e{}\" source=\"{}\" target=\"{}\" directed=\"{}\"><data key=\"label\">{}</data><data key=\"color\">{}</data><data key=\"style\">{}</data></edge>",
+                i,
+                e.source,
+                e.target,
+                e.style.directed,
+                escape_xml(&e.label),
+                e.style.color,
+                e.style.line
+            )
+            .unwrap();
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+    pub fn to_format(&self, format: EGraphFormat) -> Result<String> {
+        match format {
+            EGraphFormat::Dot => Ok(self.to_dot()),
+            EGraphFormat::GraphML => Ok(self.to_graphml()),
+            EGraphFormat::Json => self.to_json(),
+        }
+    }
+}
+
+impl Entman {
+    ///Returns the name of the entity, from its [CharacterNameComponent] or [NameComponent], or its id if it has neither
+    pub fn get_display_name(&self, entity: Id) -> String {
+        if let Ok(c) = self.get_component_ref::<CharacterNameComponent>(entity) {
+            let name = &c.component.name;
+            let parts = std::iter::once(&name.given_name)
+                .chain(name.other_names.iter())
+                .chain(std::iter::once(&name.family_name))
+                .filter(|p| !p.is_empty())
+                .cloned()
+                .collect::<Vec<_>>();
+            if !parts.is_empty() {
+                return parts.join(" ");
+            }
+        }
+        match self.get_component_ref::<NameComponent>(entity) {
+            Ok(c) if !c.component.name.is_empty() => c.component.name.clone(),
+            _ => entity.to_string(),
+        }
+    }
+    ///Returns the part of the relationship graph selected by the options. Relationships to entities that are
+    /// not alive are left out.
+    pub fn get_exported_graph(&self, options: &GraphExportOptions) -> Result<ExportedGraph> {
+        let graph = self.get_relationship_graph();
+        let relationships = graph
+            .get_owned_relationships()
+            .map(|(_, r)| r)
+            .filter(|r| {
+                self.entities.contains_key(&r.get_major_pair())
+                    && self.entities.contains_key(&r.get_minor_pair())
+                    && (options.relations.is_empty()
+                        || options.relations.contains(r.get_relation()))
+            })
+            .collect::<Vec<_>>();
+        let mut nodes = BTreeSet::new();
+        match options.root {
+            Some(root) => {
+                self.check_alive(root)?;
+                //breadth first search over the selected relationships, in both directions
+                let mut queue = VecDeque::from([(root, 0)]);
+                nodes.insert(root);
+                while let Some((id, distance)) = queue.pop_front() {
+                    if options.max_distance.map_or(false, |max| distance >= max) {
+                        continue;
+                    }
+                    for r in relationships.iter() {
+                        let other = match (r.get_major_pair(), r.get_minor_pair()) {
+                            (a, b) if a == id => b,
+                            (a, b) if b == id => a,
+                            _ => continue,
+                        };
+                        if nodes.insert(other) {
+                            queue.push_back((other, distance + 1));
+                        }
+                    }
+                }
+            }
+            None => {
+                for r in relationships.iter() {
+                    nodes.insert(r.get_major_pair());
+                    nodes.insert(r.get_minor_pair());
+                }
+            }
+        }
+        let mut edges = relationships
+            .iter()
+            .filter(|r| nodes.contains(&r.get_major_pair()) && nodes.contains(&r.get_minor_pair()))
+            .map(|r| ExportedEdge {
+                source: r.get_major_pair().to_string(),
+                target: r.get_minor_pair().to_string(),
+                label: get_relation_label(r.get_relation()),
+                style: EdgeStyle::of(r.get_relation()),
+            })
+            .collect::<Vec<_>>();
+        edges.sort_by(|a, b| {
+            (&a.source, &a.target, &a.label).cmp(&(&b.source, &b.target, &b.label))
+        });
+        Ok(ExportedGraph {
+            nodes: nodes
+                .into_iter()
+                .map(|id| ExportedNode {
+                    id: id.to_string(),
+                    label: self.get_display_name(id),
+                })
+                .collect(),
+            edges,
+        })
+    }
+    ///Exports the part of the relationship graph selected by the options in the given format
+    pub fn export_relationship_graph(
+        &self,
+        format: EGraphFormat,
+        options: &GraphExportOptions,
+    ) -> Result<String> {
+        self.get_exported_graph(options)?.to_format(format)
+    }
+}
+
+#[cfg(test)]
+mod test_graph_export {
+    use super::super::components::CharacterNameFormat;
+    use super::*;
+
+    #[test]
+    fn test_export_relationship_graph() {
+        let mut em = Entman::new();
+        let (a, b, c, d) = (
+            em.add_entity(),
+            em.add_entity(),
+            em.add_entity(),
+            em.add_entity(),
+        );
+        em.add_component(
+            a,
+            CharacterNameComponent {
+                name: CharacterNameFormat {
+                    given_name: "Ada".to_string(),
+                    other_names: vec![],
+                    family_name: "Stone".to_string(),
+                },
+                aliases: vec![],
+            },
+        )
+        .unwrap();
+        em.add_component(
+            b,
+            NameComponent {
+                name: "Bo \"the Bold\" & co".to_string(),
+                aliases: vec![],
+            },
+        )
+        .unwrap();
+        em.add_component(
+            a,
+            RelationshipComponent {
+                relationships: vec![
+                    Relationship::parent_child(a, b),
+                    Relationship::symmetric(Symmetric::Friend, b, c),
+                    Relationship::symmetric(Symmetric::Enemy, c, d),
+                ],
+            },
+        )
+        .unwrap();
+
+        let all = em.get_exported_graph(&GraphExportOptions::new()).unwrap();
+        assert_eq!(all.nodes.len(), 4);
+        assert_eq!(all.edges.len(), 3);
+        let label = |id: Id| {
+            all.nodes
+                .iter()
+                .find(|n| n.id == id.to_string())
+                .unwrap()
+                .label
+                .clone()
+        };
+        assert_eq!(label(a), "Ada Stone");
+        assert_eq!(label(d), d.to_string());
+
+        //filters by relationship kind and distance from a root
+        let options = GraphExportOptions::new().with_root(a, Some(2));
+        let near = em.get_exported_graph(&options).unwrap();
+        assert_eq!(near.nodes.len(), 3);
+        assert_eq!(near.edges.len(), 2);
+        let options = GraphExportOptions::new()
+            .with_relation(ERelationship::parent_child())
+            .with_root(a, None);
+        assert_eq!(em.get_exported_graph(&options).unwrap().edges.len(), 1);
+
+        let dot = em
+            .export_relationship_graph(EGraphFormat::Dot, &GraphExportOptions::new())
+            .unwrap();
+        assert!(dot.starts_with("digraph relationships {"));
+        assert!(dot.contains("label=\"Bo \\\"the Bold\\\" & co\""));
+        assert!(dot.contains(&format!(
+            "\"{}\" -> \"{}\" [label=\"parent\", color=\"black\", style=\"solid\", dir=\"forward\"];",
+            a, b
+        )));
+        let graphml = em
+            .export_relationship_graph(EGraphFormat::GraphML, &GraphExportOptions::new())
+            .unwrap();
+        assert_eq!(graphml.matches("<node ").count(), 4);
+        assert!(graphml.contains("Bo &quot;the Bold&quot; &amp; co"));
+        let json = em
+            .export_relationship_graph(EGraphFormat::Json, &GraphExportOptions::new())
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["edges"].as_array().unwrap().len(), 3);
+        assert_eq!(value["nodes"].as_array().unwrap().len(), 4);
+    }
+}
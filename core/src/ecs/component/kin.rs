@@ -0,0 +1,502 @@
+//! Kinship inference. A [KinGraph] is built from the parent/child, sibling and spouse relationships of the
+//! [RelationshipGraph], and answers how two entities are related by finding the shortest chain of family
+//! ties between them (up to a parent, across to a sibling, down to a child, and at most one marriage on
+//! either end) and naming it with an [EKinTerm].
+use super::super::*;
+use super::components::{ESex, Sex};
+use super::relationship::*;
+use std::collections::VecDeque;
+
+///A single family tie in the path between two relatives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EKinStep {
+    ///From a child to one of its parents
+    Parent,
+    ///From a parent to one of its children
+    Child,
+    Sibling,
+    Spouse,
+}
+
+///What one entity is to another. Generations are counted from 1, so [EKinTerm::Ancestor(1)] is a parent,
+/// [EKinTerm::Ancestor(2)] a grandparent, and [EKinTerm::AuntUncle(2)] a great-aunt or great-uncle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EKinTerm {
+    Itself,
+    Spouse,
+    Ancestor(u32),
+    Descendant(u32),
+    Sibling,
+    HalfSibling,
+    AuntUncle(u32),
+    NieceNephew(u32),
+    Cousin { degree: u32, removed: u32 },
+    InLaw(Box<EKinTerm>),
+    Step(Box<EKinTerm>),
+}
+impl EKinTerm {
+    //The term for someone [up] generations below, and [down] generations above, a common ancestor
+    fn from_generations(up: u32, down: u32) -> Self {
+        match (up, down) {
+            (0, 0) => EKinTerm::Itself,
+            (0, d) => EKinTerm::Ancestor(d),
+            (u, 0) => EKinTerm::Descendant(u),
+            (1, 1) => EKinTerm::Sibling,
+            (1, d) => EKinTerm::AuntUncle(d - 1),
+            (u, 1) => EKinTerm::NieceNephew(u - 1),
+            (u, d) => EKinTerm::Cousin {
+                degree: u.min(d) - 1,
+                removed: u.abs_diff(d),
+            },
+        }
+    }
+    ///Returns the name of the term, gendered if the sex of the relative is known, e.g. "great-grandmother"
+    /// or "second cousin once removed"
+    pub fn get_name(&self, sex: Option<&ESex>) -> String {
+        let gendered = |neutral: &str, male: &str, female: &str| match sex {
+            Some(ESex::Male) => male.to_string(),
+            Some(ESex::Female) => female.to_string(),
+            None => neutral.to_string(),
+        };
+        let greats = |n: u32| match n {
+            0 => String::new(),
+            1 => "great-".to_string(),
+            2 => "great-great-".to_string(),
+            n => format!("{}x great-", n),
+        };
+        match self {
+            EKinTerm::Itself => "self".to_string(),
+            EKinTerm::Spouse => gendered("spouse", "husband", "wife"),
+            EKinTerm::Ancestor(1) => gendered("parent", "father", "mother"),
+            EKinTerm::Ancestor(n) => {
+                greats(n - 2) + &gendered("grandparent", "grandfather", "grandmother")
+            }
+            EKinTerm::Descendant(1) => gendered("child", "son", "daughter"),
+            EKinTerm::Descendant(n) => {
+                greats(n - 2) + &gendered("grandchild", "grandson", "granddaughter")
+            }
+            EKinTerm::Sibling => gendered("sibling", "brother", "sister"),
+            EKinTerm::HalfSibling => {
+                "half-".to_string() + &gendered("sibling", "brother", "sister")
+            }
+            EKinTerm::AuntUncle(n) => greats(n - 1) + &gendered("aunt or uncle", "uncle", "aunt"),
+            EKinTerm::NieceNephew(n) => {
+                greats(n - 1) + &gendered("niece or nephew", "nephew", "niece")
+            }
+            EKinTerm::Cousin { degree, removed } => {
+                let degree = match degree {
+                    1 => "first".to_string(),
+                    2 => "second".to_string(),
+                    3 => "third".to_string(),
+                    n => format!("{}th", n),
+                };
+                let removed = match removed {
+                    0 => String::new(),
+                    1 => " once removed".to_string(),
+                    2 => " twice removed".to_string(),
+                    n => format!(" {} times removed", n),
+                };
+                format!("{} cousin{}", degree, removed)
+            }
+            EKinTerm::InLaw(term) => format!("{}-in-law", term.get_name(sex)),
+            EKinTerm::Step(term) => format!("step{}", term.get_name(sex)),
+        }
+    }
+}
+
+///How one entity is related to another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Kinship {
+    ///What the first entity is to the second
+    pub term: EKinTerm,
+    ///Every entity from the first to the second, both included
+    pub path: Vec<Id>,
+    ///The tie between each consecutive pair of entities in [path]
+    pub steps: Vec<EKinStep>,
+}
+
+///A family tree that can never hold, e.g. someone being their own ancestor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EKinContradiction {
+    OwnAncestor(Id),
+    ///A parent/child, sibling or spouse relationship between an entity and itself
+    SelfRelationship(Id),
+    ///Two entities that are siblings, although one is an ancestor of the other
+    SiblingIsAncestor {
+        sibling: Id,
+        descendant: Id,
+    },
+}
+
+//The shapes a path between two relatives may take, in the order they can be walked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum EPathPhase {
+    Start,
+    LeadingSpouse,
+    Up,
+    Across,
+    Down,
+    TrailingSpouse,
+}
+impl EPathPhase {
+    fn next(self, step: EKinStep) -> Option<EPathPhase> {
+        use EKinStep::*;
+        use EPathPhase::*;
+        match (self, step) {
+            (Start, Spouse) => Some(LeadingSpouse),
+            (Start | LeadingSpouse | Up, Parent) => Some(Up),
+            (Start | LeadingSpouse | Up, Sibling) => Some(Across),
+            (Start | LeadingSpouse | Up | Across | Down, Child) => Some(Down),
+            (Up | Across | Down, Spouse) => Some(TrailingSpouse),
+            _ => None,
+        }
+    }
+}
+
+///The family ties between entities
+#[derive(Debug, Default, Clone)]
+pub struct KinGraph {
+    parents: HashMap<Id, Vec<Id>>,
+    children: HashMap<Id, Vec<Id>>,
+    siblings: HashMap<Id, Vec<Id>>,
+    spouses: HashMap<Id, Vec<Id>>,
+    //Relationships between an entity and itself, which are never added to the tree
+    self_relationships: Vec<Id>,
+}
+impl KinGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    ///Builds the family tree from every parent/child, sibling and spouse relationship in the graph
+    pub fn from_relationships<'a>(relationships: impl Iterator<Item = &'a Relationship>) -> Self {
+        let mut kin = Self::new();
+        for r in relationships {
+            kin.add_relationship(r);
+        }
+        kin
+    }
+    fn link(map: &mut HashMap<Id, Vec<Id>>, from: Id, to: Id) {
+        let list = map.entry(from).or_default();
+        if !list.contains(&to) {
+            list.push(to);
+        }
+    }
+    pub fn add_relationship(&mut self, relationship: &Relationship) {
+        let (major, minor) = (relationship.get_major_pair(), relationship.get_minor_pair());
+        let kind = match relationship.get_relation() {
+            ERelationship::MajorMinor(Major::Parent, Minor::Child) => EKinStep::Child,
+            ERelationship::Symmetric(Symmetric::Sibling) => EKinStep::Sibling,
+            ERelationship::Symmetric(Symmetric::Spouse) => EKinStep::Spouse,
+            _ => return,
+        };
+        if major == minor {
+            if !self.self_relationships.contains(&major) {
+                self.self_relationships.push(major);
+            }
+            return;
+        }
+        match kind {
+            EKinStep::Child => {
+                Self::link(&mut self.children, major, minor);
+                Self::link(&mut self.parents, minor, major);
+            }
+            EKinStep::Sibling => {
+                Self::link(&mut self.siblings, major, minor);
+                Self::link(&mut self.siblings, minor, major);
+            }
+            _ => {
+                Self::link(&mut self.spouses, major, minor);
+                Self::link(&mut self.spouses, minor, major);
+            }
+        }
+    }
+    fn get(map: &HashMap<Id, Vec<Id>>, id: Id) -> &[Id] {
+        map.get(&id).map(|l| l.as_slice()).unwrap_or(&[])
+    }
+    pub fn get_parents(&self, id: Id) -> &[Id] {
+        Self::get(&self.parents, id)
+    }
+    pub fn get_children(&self, id: Id) -> &[Id] {
+        Self::get(&self.children, id)
+    }
+    pub fn get_spouses(&self, id: Id) -> &[Id] {
+        Self::get(&self.spouses, id)
+    }
+    ///Returns the explicit siblings of the entity, as well as those that share a parent with it
+    pub fn get_siblings(&self, id: Id) -> Vec<Id> {
+        let mut siblings = Self::get(&self.siblings, id).to_vec();
+        for p in self.get_parents(id) {
+            for c in self.get_children(*p) {
+                if *c != id && !siblings.contains(c) {
+                    siblings.push(*c);
+                }
+            }
+        }
+        siblings
+    }
+    ///Returns every ancestor of the entity, nearest generations first
+    pub fn get_ancestors(&self, id: Id) -> Vec<Id> {
+        let mut ancestors: Vec<Id> = Vec::new();
+        let mut queue = VecDeque::from(self.get_parents(id).to_vec());
+        while let Some(a) = queue.pop_front() {
+            if ancestors.contains(&a) {
+                continue;
+            }
+            ancestors.push(a);
+            queue.extend(self.get_parents(a));
+        }
+        ancestors
+    }
+    fn get_neighbours(&self, id: Id, step: EKinStep) -> &[Id] {
+        match step {
+            EKinStep::Parent => self.get_parents(id),
+            EKinStep::Child => self.get_children(id),
+            EKinStep::Sibling => Self::get(&self.siblings, id),
+            EKinStep::Spouse => self.get_spouses(id),
+        }
+    }
+    ///Returns how [a] is related to [b], or [None] if they are not family
+    pub fn get_kinship(&self, a: Id, b: Id) -> Option<Kinship> {
+        let steps = self.find_path(a, b)?;
+        let mut path = vec![a];
+        let mut kinds = Vec::new();
+        for (id, step) in steps {
+            path.push(id);
+            kinds.push(step);
+        }
+        let term = self.name_path(&path, &kinds);
+        Some(Kinship {
+            term,
+            path,
+            steps: kinds,
+        })
+    }
+    //Breadth first search for the shortest path from [a] to [b] that has the shape of a family tie
+    fn find_path(&self, a: Id, b: Id) -> Option<Vec<(Id, EKinStep)>> {
+        let mut visited = HashSet::new();
+        let mut came_from: HashMap<(Id, EPathPhase), ((Id, EPathPhase), EKinStep)> = HashMap::new();
+        let mut queue = VecDeque::from([(a, EPathPhase::Start)]);
+        visited.insert((a, EPathPhase::Start));
+        let mut end = None;
+        while let Some(state) = queue.pop_front() {
+            if state.0 == b {
+                end = Some(state);
+                break;
+            }
+            for step in [
+                EKinStep::Parent,
+                EKinStep::Sibling,
+                EKinStep::Child,
+                EKinStep::Spouse,
+            ] {
+                let phase = match state.1.next(step) {
+                    Some(p) => p,
+                    None => continue,
+                };
+                for n in self.get_neighbours(state.0, step) {
+                    let next = (*n, phase);
+                    if visited.insert(next) {
+                        came_from.insert(next, (state, step));
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        let mut state = end?;
+        let mut path = Vec::new();
+        while let Some((prev, step)) = came_from.get(&state) {
+            path.push((state.0, *step));
+            state = *prev;
+        }
+        path.reverse();
+        Some(path)
+    }
+    fn name_path(&self, path: &[Id], steps: &[EKinStep]) -> EKinTerm {
+        let leading = steps.first() == Some(&EKinStep::Spouse);
+        let trailing = steps.len() > 1 && steps.last() == Some(&EKinStep::Spouse);
+        if steps == [EKinStep::Spouse] {
+            return EKinTerm::Spouse;
+        }
+        let count = |s: EKinStep| steps.iter().filter(|k| **k == s).count() as u32;
+        let across = count(EKinStep::Sibling);
+        let up = count(EKinStep::Parent) + across;
+        let down = count(EKinStep::Child) + across;
+        let mut term = EKinTerm::from_generations(up, down);
+        //siblings through a common parent are only full siblings if they share all of their parents
+        if term == EKinTerm::Sibling && across == 0 {
+            let first = path[leading as usize];
+            let last = path[path.len() - 1 - trailing as usize];
+            let mut p1 = self.get_parents(first).to_vec();
+            let mut p2 = self.get_parents(last).to_vec();
+            p1.sort();
+            p2.sort();
+            if p1 != p2 {
+                term = EKinTerm::HalfSibling;
+            }
+        }
+        match (leading, trailing, &term) {
+            (true, false, EKinTerm::Ancestor(1)) => EKinTerm::Step(Box::new(term)),
+            (false, true, EKinTerm::Descendant(1)) => EKinTerm::Step(Box::new(term)),
+            (false, false, _) => term,
+            _ => EKinTerm::InLaw(Box::new(term)),
+        }
+    }
+    ///Returns every contradiction in the family tree
+    pub fn get_contradictions(&self) -> Vec<EKinContradiction> {
+        let mut contradictions = self
+            .self_relationships
+            .iter()
+            .map(|id| EKinContradiction::SelfRelationship(*id))
+            .collect::<Vec<_>>();
+        let mut ids = self.parents.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            let ancestors = self.get_ancestors(id);
+            if ancestors.contains(&id) {
+                contradictions.push(EKinContradiction::OwnAncestor(id));
+            }
+            for s in Self::get(&self.siblings, id) {
+                if ancestors.contains(s) {
+                    contradictions.push(EKinContradiction::SiblingIsAncestor {
+                        sibling: *s,
+                        descendant: id,
+                    });
+                }
+            }
+        }
+        contradictions
+    }
+}
+
+impl Entman {
+    ///Returns the family tree formed by the relationships between living entities
+    pub fn get_kin_graph(&self) -> KinGraph {
+        let graph = self.get_relationship_graph();
+        KinGraph::from_relationships(graph.get_owned_relationships().map(|(_, r)| r).filter(|r| {
+            self.entities.contains_key(&r.get_major_pair())
+                && self.entities.contains_key(&r.get_minor_pair())
+        }))
+    }
+    ///Returns how [a] is related to [b], e.g. that [a] is the grandmother of [b], or [None] if they are not family
+    pub fn get_kinship(&self, a: Id, b: Id) -> Result<Option<Kinship>> {
+        self.check_alive(a)?;
+        self.check_alive(b)?;
+        Ok(self.get_kin_graph().get_kinship(a, b))
+    }
+    ///Returns the name of the kin term of [a] to [b], gendered by the [Sex] component of [a] if it has one
+    pub fn get_kin_name(&self, a: Id, b: Id) -> Result<Option<String>> {
+        let sex = self
+            .get_component_ref::<Sex>(a)
+            .ok()
+            .map(|s| &s.component.sex);
+        Ok(self.get_kinship(a, b)?.map(|k| k.term.get_name(sex)))
+    }
+    pub fn get_kin_contradictions(&self) -> Vec<EKinContradiction> {
+        self.get_kin_graph().get_contradictions()
+    }
+}
+
+#[cfg(test)]
+mod test_kin {
+    use super::*;
+
+    #[test]
+    fn test_kinship() {
+        let mut em = Entman::new();
+        let mut people = Vec::new();
+        for _ in 0..10 {
+            people.push(em.add_entity());
+        }
+        let [grandma, dad, aunt, mum, me, sister, cousin, son, wife, half] = people[..] else {
+            unreachable!()
+        };
+        let rels = vec![
+            Relationship::parent_child(grandma, dad),
+            Relationship::parent_child(grandma, aunt),
+            Relationship::parent_child(dad, me),
+            Relationship::parent_child(mum, me),
+            Relationship::parent_child(dad, sister),
+            Relationship::parent_child(mum, sister),
+            Relationship::parent_child(dad, half),
+            Relationship::parent_child(aunt, cousin),
+            Relationship::parent_child(me, son),
+            Relationship::symmetric(Symmetric::Spouse, me, wife),
+            Relationship::symmetric(Symmetric::Spouse, dad, mum),
+        ];
+        em.add_component(
+            grandma,
+            RelationshipComponent {
+                relationships: rels,
+            },
+        )
+        .unwrap();
+        em.add_component(grandma, Sex { sex: ESex::Female })
+            .unwrap();
+        let term = |em: &Entman, a, b| em.get_kinship(a, b).unwrap().unwrap().term;
+
+        assert_eq!(term(&em, me, me), EKinTerm::Itself);
+        assert_eq!(term(&em, dad, me), EKinTerm::Ancestor(1));
+        assert_eq!(term(&em, son, grandma), EKinTerm::Descendant(3));
+        assert_eq!(term(&em, sister, me), EKinTerm::Sibling);
+        assert_eq!(term(&em, half, me), EKinTerm::HalfSibling);
+        assert_eq!(term(&em, aunt, me), EKinTerm::AuntUncle(1));
+        assert_eq!(term(&em, aunt, son), EKinTerm::AuntUncle(2));
+        assert_eq!(
+            term(&em, cousin, son),
+            EKinTerm::Cousin {
+                degree: 1,
+                removed: 1
+            }
+        );
+        assert_eq!(term(&em, wife, me), EKinTerm::Spouse);
+        assert_eq!(
+            term(&em, wife, dad),
+            EKinTerm::InLaw(Box::new(EKinTerm::Descendant(1)))
+        );
+        assert_eq!(
+            term(&em, sister, wife),
+            EKinTerm::InLaw(Box::new(EKinTerm::Sibling))
+        );
+        assert_eq!(
+            term(&em, wife, son),
+            EKinTerm::Step(Box::new(EKinTerm::Ancestor(1)))
+        );
+
+        let kinship = em.get_kinship(cousin, me).unwrap().unwrap();
+        assert_eq!(kinship.path, vec![cousin, aunt, grandma, dad, me]);
+        assert_eq!(
+            kinship.steps,
+            vec![
+                EKinStep::Parent,
+                EKinStep::Parent,
+                EKinStep::Child,
+                EKinStep::Child
+            ]
+        );
+        assert_eq!(
+            em.get_kin_name(grandma, son).unwrap().unwrap(),
+            "great-grandmother"
+        );
+        assert_eq!(
+            EKinTerm::Cousin {
+                degree: 2,
+                removed: 1
+            }
+            .get_name(None),
+            "second cousin once removed"
+        );
+        let stranger = em.add_entity();
+        assert_eq!(em.get_kinship(me, stranger).unwrap(), None);
+        assert!(em.get_kin_contradictions().is_empty());
+
+        //someone can not be their own ancestor
+        em.get_component_mut::<RelationshipComponent>(grandma)
+            .unwrap()
+            .component
+            .relationships
+            .push(Relationship::parent_child(son, grandma));
+        let contradictions = em.get_kin_contradictions();
+        assert!(contradictions.contains(&EKinContradiction::OwnAncestor(grandma)));
+        assert!(contradictions.contains(&EKinContradiction::OwnAncestor(son)));
+    }
+}
@@ -1,5 +1,8 @@
 pub mod archetypes;
+pub mod graph_export;
+pub mod kin;
 pub mod relationship;
+pub mod schema;
 
 use common::components::*;
 use common::exports::serde::*;
@@ -85,9 +88,14 @@ pub mod components {
         }
     }
 
+    ///When an entity was born and died, or was made and destroyed, from which its age at any moment
+    /// is derived. See [Entman::age_at](crate::ecs::Entman::age_at)
     #[component]
-    pub struct AgeComponent {
-        age: f32,
+    pub struct LifespanComponent {
+        pub birth: Option<crate::khronos::DateTime>,
+        pub death: Option<crate::khronos::DateTime>,
+        ///The id of the calendar the dates are measured in
+        pub calendar: Option<crate::ecs::Id>,
     }
     #[component]
     pub struct Sex {
@@ -143,6 +151,54 @@ pub mod components {
         elements: Vec<BinaryComponentElement>,
     }
 
+    ///A component whose fields are described by a [ComponentSchema](super::schema::ComponentSchema)
+    /// registered at runtime, rather than by a rust type.
+    #[component]
+    pub struct CustomComponent {
+        ///The name of the schema of this component
+        pub schema: String,
+        pub fields: std::collections::BTreeMap<String, schema::FieldValue>,
+    }
+    impl CustomComponent {
+        pub fn get_field(&self, name: &str) -> Option<&schema::FieldValue> {
+            self.fields.get(name)
+        }
+    }
+
+    ///Places an entity in the containment hierarchy of the project, such as a city within a continent,
+    /// or an item carried by a character. It is maintained by the hierarchy methods of [Entman](crate::ecs::Entman).
+    #[component]
+    pub struct HierarchyComponent {
+        pub(crate) parent: Option<crate::ecs::Id>,
+        pub(crate) children: Vec<crate::ecs::Id>,
+    }
+    impl HierarchyComponent {
+        pub fn get_parent(&self) -> Option<crate::ecs::Id> {
+            self.parent
+        }
+        pub fn get_children(&self) -> &[crate::ecs::Id] {
+            &self.children
+        }
+    }
+
+    ///Links an entity to the prefab it was instantiated from. See [Entman::instantiate_prefab](crate::ecs::Entman::instantiate_prefab)
+    #[component]
+    pub struct PrefabInstanceComponent {
+        pub(crate) prefab: crate::ecs::Id,
+        ///The component types that have been changed locally, and are no longer inherited from the prefab
+        pub(crate) overrides: Vec<TypeId>,
+        ///The tick at which the instance was last synced with its prefab
+        pub(crate) synced: u64,
+    }
+    impl PrefabInstanceComponent {
+        pub fn get_prefab(&self) -> crate::ecs::Id {
+            self.prefab
+        }
+        pub fn get_overrides(&self) -> &[TypeId] {
+            &self.overrides
+        }
+    }
+
     #[component]
     pub struct RelationshipComponent {
         pub relationships: Vec<relationship::Relationship>,
@@ -154,6 +210,75 @@ pub mod components {
     pub struct ArcComponent {
         pub arc_name: String,
         pub arc_description: String,
-        pub arc_events: Vec<crate::Event>,
+        ///The entities of the events, each with an [EventComponent]
+        pub arc_events: Vec<crate::ecs::Id>,
+    }
+
+    ///The part an entity plays in an event
+    #[nvproc::bincode_derive]
+    #[nvproc::serde_derive]
+    #[derive(Debug, PartialEq)]
+    pub enum EEventRole {
+        Protagonist,
+        Antagonist,
+        Witness,
+        Victim,
+        Participant,
+        Other(String),
+    }
+    #[nvproc::bincode_derive]
+    #[nvproc::serde_derive]
+    #[derive(Debug, PartialEq)]
+    pub struct EventParticipant {
+        pub entity: crate::ecs::Id,
+        pub role: EEventRole,
+    }
+
+    ///Makes an entity an event of the world. Its participants are maintained by the event methods of
+    /// [Entman](crate::ecs::Entman), which index them in an [EventParticipationComponent] on each participant.
+    #[component]
+    pub struct EventComponent {
+        pub name: String,
+        pub description: String,
+        pub time: Option<crate::timeline::EEventTime>,
+        ///The entity of the place the event happened at
+        pub location: Option<crate::ecs::Id>,
+        pub(crate) participants: Vec<EventParticipant>,
+    }
+    impl EventComponent {
+        pub fn new(name: &str, description: &str) -> Self {
+            EventComponent {
+                name: String::from(name),
+                description: String::from(description),
+                ..Default::default()
+            }
+        }
+        pub fn with_time(mut self, time: crate::timeline::EEventTime) -> Self {
+            self.time = Some(time);
+            self
+        }
+        pub fn with_location(mut self, location: crate::ecs::Id) -> Self {
+            self.location = Some(location);
+            self
+        }
+        pub fn get_participants(&self) -> &[EventParticipant] {
+            &self.participants
+        }
+        pub fn get_role(&self, entity: crate::ecs::Id) -> Option<&EEventRole> {
+            self.participants
+                .iter()
+                .find(|p| p.entity == entity)
+                .map(|p| &p.role)
+        }
+    }
+    ///The events an entity takes part in, the back-references of [EventComponent::participants]
+    #[component]
+    pub struct EventParticipationComponent {
+        pub(crate) events: Vec<crate::ecs::Id>,
+    }
+    impl EventParticipationComponent {
+        pub fn get_events(&self) -> &[crate::ecs::Id] {
+            &self.events
+        }
     }
 }
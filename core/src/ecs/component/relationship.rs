@@ -1,9 +1,14 @@
 use super::super::*;
 use nvproc::Component;
 use petgraph::graph::*;
+use petgraph::stable_graph::StableDiGraph;
 use petgraph::visit::*;
+use crate::khronos::{DateTime, System};
+use std::cmp::Ordering;
+use std::sync::{RwLock, RwLockReadGuard};
 #[nvproc::bincode_derive]
 #[nvproc::serde_derive]
+#[derive(Debug, PartialEq)]
 pub enum ERelationship {
     MajorMinor(Major, Minor),
     Symmetric(Symmetric),
@@ -20,6 +25,7 @@ impl Default for ERelationship {
 }
 #[nvproc::bincode_derive]
 #[nvproc::serde_derive]
+#[derive(Debug, PartialEq)]
 pub enum Major {
     Parent,
     Custom(Custom),
@@ -27,12 +33,14 @@ pub enum Major {
 #[nvproc::bincode_derive]
 #[nvproc::serde_derive]
 
+#[derive(Debug, PartialEq)]
 pub struct Custom {
     pub name: String,
     pub description: String,
 }
 #[nvproc::bincode_derive]
 #[nvproc::serde_derive]
+#[derive(Debug, PartialEq)]
 pub enum Minor {
     Child,
     Custom(Custom),
@@ -40,6 +48,7 @@ pub enum Minor {
 #[nvproc::bincode_derive]
 #[nvproc::serde_derive]
 
+#[derive(Debug, PartialEq)]
 pub enum Symmetric {
     Friend,
     Enemy,
@@ -57,11 +66,19 @@ impl Default for Symmetric {
 #[nvproc::serde_derive]
 #[derive(Default)]
 
+#[derive(Debug, PartialEq)]
 pub struct Relationship {
     pub relationship_name: String,
     pub relation: ERelationship,
     //The entities the relationship is between.
     pairs: (Id, Id),
+    ///When the relationship began. A relationship without a start has always held.
+    pub start: Option<DateTime>,
+    ///When the relationship ended. A relationship without an end still holds.
+    pub end: Option<DateTime>,
+    ///The events that began and ended the relationship
+    pub started_by: Option<Id>,
+    pub ended_by: Option<Id>,
 }
 impl Relationship {
     pub fn new(name: String, relation: ERelationship, pairs: (Id, Id)) -> Self {
@@ -69,8 +86,37 @@ impl Relationship {
             relationship_name: name,
             relation,
             pairs,
+            ..Default::default()
         }
     }
+    ///Sets when the relationship began, and optionally the event that began it
+    pub fn with_start(mut self, start: DateTime, event: Option<Id>) -> Self {
+        self.start = Some(start);
+        self.started_by = event;
+        self
+    }
+    ///Sets when the relationship ended, and optionally the event that ended it
+    pub fn with_end(mut self, end: DateTime, event: Option<Id>) -> Self {
+        self.end = Some(end);
+        self.ended_by = event;
+        self
+    }
+    ///Returns true if the relationship holds at the given moment, that is from its start up to, but not
+    /// including, its end. The bounds and the moment are compared in [system], whichever cycles and epochs they
+    /// are measured in.
+    pub fn is_active_at(&self, system: &System, at: &DateTime) -> Result<bool> {
+        if let Some(start) = &self.start {
+            if system.compare(at, start)? == Ordering::Less {
+                return Ok(false);
+            }
+        }
+        if let Some(end) = &self.end {
+            if system.compare(at, end)? != Ordering::Less {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
     pub fn get_name(&self) -> &str {
         &self.relationship_name
     }
@@ -101,22 +147,262 @@ impl Relationship {
         )
     }
 }
+///A graph of every relationship between entities. Each edge points from the major to the minor entity of
+/// its [Relationship], and remembers the entity whose [RelationshipComponent] it came from.
 pub struct RelationshipGraph {
-    pub graph: DiGraph<Id, Relationship>,
+    pub graph: StableDiGraph<Id, Relationship>,
+    nodes: HashMap<Id, NodeIndex>,
+    //The edges created from the relationship components of each entity
+    owned_edges: HashMap<Id, Vec<EdgeIndex>>,
+    //The tick the graph was last synced at, if it has been synced at all
+    synced: Option<Tick>,
+}
+impl Default for RelationshipGraph {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl RelationshipGraph {
     pub fn new() -> Self {
         RelationshipGraph {
-            graph: DiGraph::new(),
+            graph: StableDiGraph::new(),
+            nodes: HashMap::new(),
+            owned_edges: HashMap::new(),
+            synced: None,
         }
     }
     pub fn find_node_index(&self, id: Id) -> Option<NodeIndex<u32>> {
-        for (i, n) in self.graph.node_weights().enumerate() {
-            if *n == id {
-                return Some(NodeIndex::new(i.into()));
+        self.nodes.get(&id).copied()
+    }
+    fn get_or_add_node(&mut self, id: Id) -> NodeIndex<u32> {
+        match self.nodes.get(&id) {
+            Some(n) => *n,
+            None => {
+                let n = self.graph.add_node(id);
+                self.nodes.insert(id, n);
+                n
             }
         }
-        None
+    }
+    ///Replaces every edge that came from the relationship components of [owner]
+    pub fn set_relationships(&mut self, owner: Id, relationships: Vec<Relationship>) {
+        self.remove_relationships(owner);
+        let mut edges = Vec::new();
+        for r in relationships {
+            let major = self.get_or_add_node(r.get_major_pair());
+            let minor = self.get_or_add_node(r.get_minor_pair());
+            edges.push(self.graph.add_edge(major, minor, r));
+        }
+        if !edges.is_empty() {
+            self.owned_edges.insert(owner, edges);
+        }
+    }
+    ///Removes every edge that came from the relationship components of [owner]
+    pub fn remove_relationships(&mut self, owner: Id) {
+        for e in self.owned_edges.remove(&owner).unwrap_or_default() {
+            self.graph.remove_edge(e);
+        }
+    }
+    pub fn clear(&mut self) {
+        self.graph.clear();
+        self.nodes.clear();
+        self.owned_edges.clear();
+        self.synced = None;
+    }
+    ///Returns every relationship the entity is part of, as either the major or the minor entity
+    pub fn get_relationships(&self, id: Id) -> Vec<&Relationship> {
+        let node = match self.find_node_index(id) {
+            Some(n) => n,
+            None => return Vec::new(),
+        };
+        self.graph
+            .edges_directed(node, petgraph::Direction::Outgoing)
+            .chain(
+                self.graph
+                    .edges_directed(node, petgraph::Direction::Incoming),
+            )
+            .map(|e| e.weight())
+            .collect()
+    }
+    ///Returns every relationship, along with the entity whose component it came from
+    pub fn get_owned_relationships(&self) -> impl Iterator<Item = (Id, &Relationship)> {
+        self.owned_edges
+            .iter()
+            .flat_map(move |(owner, edges)| edges.iter().map(move |e| (*owner, &self.graph[*e])))
+    }
+}
+
+///Keeps the [RelationshipGraph] of an [Entman] in sync with its relationship components. The graph is
+/// updated lazily whenever it is read, from the entities the change log reports as touched since the graph
+/// was last synced, so edits made through [Entman::get_component_mut] are picked up as well. Draining the
+/// events of the change log does not affect it.
+#[derive(Default)]
+pub struct RelationshipGraphCache {
+    graph: RwLock<RelationshipGraph>,
+}
+//The graph is transient, and is simply rebuilt after decoding
+impl bincode::Encode for RelationshipGraphCache {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        _encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        Ok(())
+    }
+}
+impl bincode::Decode for RelationshipGraphCache {
+    fn decode<D: bincode::de::Decoder>(
+        _decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(RelationshipGraphCache::default())
+    }
+}
+
+//Returns the other entity of every relationship of the given kind
+fn get_related(entity: Id, relation: &ERelationship, relationships: Vec<Relationship>) -> Vec<Id> {
+    let mut related = Vec::new();
+    for r in relationships
+        .iter()
+        .filter(|r| r.get_relation() == relation)
+    {
+        let other = match r.get_major_pair() == entity {
+            true => r.get_minor_pair(),
+            false => r.get_major_pair(),
+        };
+        if !related.contains(&other) {
+            related.push(other);
+        }
+    }
+    related
+}
+
+impl Entman {
+    //Returns every relationship stored in the components of the entity
+    fn get_entity_relationships(&self, entity: Id) -> Vec<Relationship> {
+        self.storage
+            .get_components_ref::<RelationshipComponent>(entity)
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|c| c.component.relationships.iter().cloned())
+            .collect()
+    }
+    ///Returns the relationship graph, brought up to date with every change made to the relationship components
+    pub fn get_relationship_graph(&self) -> RwLockReadGuard<'_, RelationshipGraph> {
+        let tick = self.get_tick();
+        {
+            let graph = self.relationships.graph.read().unwrap();
+            if graph.synced == Some(tick) {
+                return graph;
+            }
+        }
+        let mut graph = self.relationships.graph.write().unwrap();
+        match graph.synced {
+            Some(synced) => {
+                let changed = self
+                    .storage
+                    .get_change_log()
+                    .get_touched_since(RelationshipComponent::get_type_id(), synced);
+                for entity in changed {
+                    match self.entities.contains_key(&entity) {
+                        true => {
+                            graph.set_relationships(entity, self.get_entity_relationships(entity))
+                        }
+                        false => graph.remove_relationships(entity),
+                    }
+                }
+            }
+            None => {
+                graph.clear();
+                for entity in self.entities.keys() {
+                    graph.set_relationships(*entity, self.get_entity_relationships(*entity));
+                }
+            }
+        }
+        graph.synced = Some(tick);
+        drop(graph);
+        self.relationships.graph.read().unwrap()
+    }
+    ///Returns every relationship the entity is part of, whose other entity is alive as well
+    pub fn relations_of(&self, entity: Id) -> Result<Vec<Relationship>> {
+        self.check_alive(entity)?;
+        let graph = self.get_relationship_graph();
+        Ok(graph
+            .get_relationships(entity)
+            .into_iter()
+            .filter(|r| {
+                self.entities.contains_key(&r.get_major_pair())
+                    && self.entities.contains_key(&r.get_minor_pair())
+            })
+            .cloned()
+            .collect())
+    }
+    ///Returns every relationship the entity is part of that holds at the given moment of [system], whose other
+    /// entity is alive as well
+    pub fn relations_of_at(
+        &self,
+        entity: Id,
+        system: &System,
+        at: &DateTime,
+    ) -> Result<Vec<Relationship>> {
+        let mut relations = Vec::new();
+        for r in self.relations_of(entity)? {
+            if r.is_active_at(system, at)? {
+                relations.push(r);
+            }
+        }
+        Ok(relations)
+    }
+    ///Returns the living entities related to [entity] by [relation]. For a major/minor relation this is the
+    /// other side of the relation, e.g. the children of a parent, or the parents of a child.
+    pub fn related_by(&self, entity: Id, relation: ERelationship) -> Result<Vec<Id>> {
+        Ok(get_related(entity, &relation, self.relations_of(entity)?))
+    }
+    ///Returns the living entities related to [entity] by [relation] at the given moment of [system]
+    pub fn related_by_at(
+        &self,
+        entity: Id,
+        relation: ERelationship,
+        system: &System,
+        at: &DateTime,
+    ) -> Result<Vec<Id>> {
+        Ok(get_related(
+            entity,
+            &relation,
+            self.relations_of_at(entity, system, at)?,
+        ))
+    }
+    ///Returns every relationship that refers to an entity that is not alive, along with the entity that owns it
+    pub fn get_invalid_relationships(&self) -> Vec<(Id, Relationship)> {
+        let graph = self.get_relationship_graph();
+        graph
+            .get_owned_relationships()
+            .filter(|(_, r)| {
+                !self.entities.contains_key(&r.get_major_pair())
+                    || !self.entities.contains_key(&r.get_minor_pair())
+            })
+            .map(|(owner, r)| (owner, r.clone()))
+            .collect()
+    }
+    ///Returns an error if any relationship refers to an entity that is not alive
+    pub fn validate_relationships(&self) -> Result<()> {
+        let invalid = self.get_invalid_relationships();
+        match invalid.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!(
+                "{} relationships refer to entities that do not exist: {}",
+                invalid.len(),
+                invalid
+                    .iter()
+                    .map(|(owner, r)| format!(
+                        "{} ({} -> {}) owned by {}",
+                        r.get_name(),
+                        r.get_major_pair(),
+                        r.get_minor_pair(),
+                        owner
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
     }
 }
 #[cfg(test)]
@@ -127,9 +413,7 @@ mod test_relationship {
 
     #[test]
     fn test_basic() {
-        let mut graph = RelationshipGraph {
-            graph: DiGraph::new(),
-        };
+        let mut graph = RelationshipGraph::new();
         let ent1 = uuid::gen_128();
         let ent2 = uuid::gen_128();
         let ent3 = uuid::gen_128();
@@ -137,6 +421,7 @@ mod test_relationship {
             relationship_name: "FatherSon".to_string(),
             relation: ERelationship::parent_child(),
             pairs: (ent1, ent2),
+            ..Default::default()
         };
         let m = Major::Parent;
         let mother = Relationship {
@@ -144,6 +429,7 @@ mod test_relationship {
             relation: ERelationship::parent_child(),
 
             pairs: (ent1, ent3),
+            ..Default::default()
         };
 
         //create nodes
@@ -154,4 +440,192 @@ mod test_relationship {
         graph.graph.add_edge(n1, n2, father);
         graph.graph.add_edge(n3, n2, mother);
     }
+    #[test]
+    fn test_relationship_graph_sync() {
+        let mut em = Entman::new();
+        let (a, b, c) = (em.add_entity(), em.add_entity(), em.add_entity());
+        em.add_component(
+            a,
+            RelationshipComponent {
+                relationships: vec![
+                    Relationship::parent_child(a, b),
+                    Relationship::symmetric(Symmetric::Friend, a, c),
+                ],
+            },
+        )
+        .unwrap();
+        assert_eq!(em.relations_of(a).unwrap().len(), 2);
+        assert_eq!(
+            em.related_by(b, ERelationship::parent_child()).unwrap(),
+            vec![a]
+        );
+        assert_eq!(
+            em.related_by(a, ERelationship::Symmetric(Symmetric::Friend))
+                .unwrap(),
+            vec![c]
+        );
+        assert!(em.get_relationship_graph().find_node_index(b).is_some());
+
+        //edits made through a mutable borrow are picked up
+        em.get_component_mut::<RelationshipComponent>(a)
+            .unwrap()
+            .component
+            .relationships
+            .push(Relationship::symmetric(Symmetric::Sibling, b, c));
+        //draining the events before the graph is read loses nothing
+        em.drain_events();
+        assert_eq!(
+            em.related_by(c, ERelationship::Symmetric(Symmetric::Sibling))
+                .unwrap(),
+            vec![b]
+        );
+        assert_eq!(em.relations_of(c).unwrap().len(), 2);
+
+        //relationships to removed entities are skipped, and reported as invalid
+        em.remove_entity(c).unwrap();
+        assert_eq!(em.relations_of(a).unwrap().len(), 1);
+        assert_eq!(em.get_invalid_relationships().len(), 2);
+        assert!(em.validate_relationships().is_err());
+        em.restore_entity(c).unwrap();
+        assert!(em.validate_relationships().is_ok());
+
+        em.remove_component::<RelationshipComponent>(a).unwrap();
+        assert!(em.relations_of(a).unwrap().is_empty());
+        assert_eq!(em.get_relationship_graph().graph.edge_count(), 0);
+    }
+    #[test]
+    fn test_relationship_edits_through_queries() {
+        use crate::ecs::system::Schedule;
+        use crate::mir::Mir;
+        let mut mir = Mir::new();
+        let em = &mut mir.em;
+        let (a, b, c) = (em.add_entity(), em.add_entity(), em.add_entity());
+        em.add_component(
+            a,
+            RelationshipComponent {
+                relationships: vec![Relationship::parent_child(a, b)],
+            },
+        )
+        .unwrap();
+        assert_eq!(em.relations_of(a).unwrap().len(), 1);
+
+        //components fetched by a mutable query are marked as changed
+        let mut res = em
+            .query_mut(&Query::<RelationshipComponent>::new())
+            .unwrap();
+        for (_, r) in res.iter_typed_mut() {
+            r.relationships
+                .push(Relationship::symmetric(Symmetric::Friend, a, c));
+        }
+        assert_eq!(
+            em.related_by(c, ERelationship::Symmetric(Symmetric::Friend))
+                .unwrap(),
+            vec![a]
+        );
+
+        //and so are those fetched by the mutable queries of a system
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system("forget", |mut q: QueryResultMut<RelationshipComponent>| {
+                for (_, r) in q.iter_typed_mut() {
+                    r.relationships
+                        .retain(|r| r.get_relation() == &ERelationship::parent_child());
+                }
+            })
+            .unwrap();
+        schedule.run(&mut mir).unwrap();
+        assert!(mir.em.relations_of(c).unwrap().is_empty());
+        assert_eq!(
+            mir.em.related_by(b, ERelationship::parent_child()).unwrap(),
+            vec![a]
+        );
+    }
+    #[test]
+    fn test_time_bounded_relationships() {
+        use crate::khronos::{Coef, Cycle, Epoch, UnitCycle};
+        let month = Cycle::new(1, "Month", "A month", Coef::Inverted(12), None, 0).unwrap();
+        let day = Cycle::new(2, "Day", "A day", Coef::Inverted(30), None, 1).unwrap();
+        let unit = UnitCycle {
+            name: String::from("Year"),
+            description: String::new(),
+        };
+        let mut system = System::from_cycles(unit, vec![month, day]).unwrap();
+        system
+            .add_epoch(Epoch::new(1, "Before Founding", "BF", 0.0).counting_backwards())
+            .unwrap();
+        let date = |d: &str| DateTime::from_cycle_string(d, &system).unwrap();
+        let mut em = Entman::new();
+        let (a, b, c) = (em.add_entity(), em.add_entity(), em.add_entity());
+        let wedding = uuid::gen_128();
+        let death = uuid::gen_128();
+        em.add_component(
+            a,
+            RelationshipComponent {
+                relationships: vec![
+                    Relationship::symmetric(Symmetric::Spouse, a, b)
+                        .with_start(date("12"), Some(wedding))
+                        .with_end(date("40"), Some(death)),
+                    Relationship::symmetric(Symmetric::Friend, a, c).with_start(date("30"), None),
+                ],
+            },
+        )
+        .unwrap();
+        let spouse = ERelationship::Symmetric(Symmetric::Spouse);
+        assert!(em
+            .relations_of_at(a, &system, &date("5"))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            em.related_by_at(a, spouse.clone(), &system, &date("12"))
+                .unwrap(),
+            vec![b]
+        );
+        assert_eq!(
+            em.relations_of_at(a, &system, &date("35")).unwrap().len(),
+            2
+        );
+        let at_40 = em.relations_of_at(a, &system, &date("40")).unwrap();
+        assert_eq!(at_40.len(), 1);
+        assert_eq!(
+            at_40[0].get_relation(),
+            &ERelationship::Symmetric(Symmetric::Friend)
+        );
+        //the time bounds do not affect the queries that ignore time
+        assert_eq!(em.related_by(a, spouse.clone()).unwrap(), vec![b]);
+        let marriage = &em.relations_of(b).unwrap()[0];
+        assert_eq!(marriage.started_by, Some(wedding));
+        assert_eq!(marriage.ended_by, Some(death));
+
+        //dates of any precision are compared
+        assert_eq!(
+            em.related_by_at(a, spouse.clone(), &system, &date("12:5:3"))
+                .unwrap(),
+            vec![b]
+        );
+        assert!(em
+            .related_by_at(a, spouse.clone(), &system, &date("39:12:30.5"))
+            .unwrap()
+            .contains(&b));
+        assert!(em
+            .related_by_at(a, spouse.clone(), &system, &date("11:12:30"))
+            .unwrap()
+            .is_empty());
+
+        //and so are dates of other epochs, including those that count backwards
+        em.add_component(
+            c,
+            RelationshipComponent {
+                relationships: vec![Relationship::symmetric(Symmetric::Sibling, c, b)
+                    .with_start(date("100").with_epoch(1), None)
+                    .with_end(date("20"), None)],
+            },
+        )
+        .unwrap();
+        let sibling = ERelationship::Symmetric(Symmetric::Sibling);
+        let related = |at: DateTime| em.related_by_at(b, sibling.clone(), &system, &at).unwrap();
+        assert_eq!(related(date("99").with_epoch(1)), vec![c]);
+        assert!(related(date("101").with_epoch(1)).is_empty());
+        assert_eq!(related(date("19:12:30")), vec![c]);
+        assert!(related(date("20")).is_empty());
+    }
 }
@@ -0,0 +1,397 @@
+//! Component types defined at runtime. A [ComponentSchema] describes the fields of a component
+//! (their names, types and defaults) and is usually parsed from json. Instances of a schema are
+//! stored as [CustomComponent]s, which live in [Storage](crate::ecs::Storage) like any other component.
+use super::super::*;
+use super::components::CustomComponent;
+use std::collections::BTreeMap;
+
+///The value of a single field of a [CustomComponent]
+#[nvproc::bincode_derive]
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(crate = "common::exports::serde", untagged)]
+pub enum FieldValue {
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+    List(Vec<FieldValue>),
+}
+impl FieldValue {
+    ///Returns true if the value can be stored in a field of the given type
+    pub fn is_of_type(&self, field_type: &EFieldType) -> bool {
+        match (self, field_type) {
+            (FieldValue::Boolean(_), EFieldType::Boolean)
+            | (FieldValue::Integer(_), EFieldType::Integer)
+            | (FieldValue::Number(_), EFieldType::Number)
+            | (FieldValue::String(_), EFieldType::String) => true,
+            (FieldValue::List(l), EFieldType::List(t)) => l.iter().all(|v| v.is_of_type(t)),
+            _ => false,
+        }
+    }
+    //Converts the value to the given type, if it can be done without loss.
+    //Json does not distinguish integers from numbers, so an integer literal may be given for a number field
+    fn coerce(self, field_type: &EFieldType) -> Result<FieldValue> {
+        match (self, field_type) {
+            (FieldValue::Integer(i), EFieldType::Number) => Ok(FieldValue::Number(i as f64)),
+            (FieldValue::List(l), EFieldType::List(t)) => Ok(FieldValue::List(
+                l.into_iter()
+                    .map(|v| v.coerce(t))
+                    .collect::<Result<Vec<_>>>()?,
+            )),
+            (v, t) if v.is_of_type(t) => Ok(v),
+            (v, t) => Err(anyhow!("Value {:?} is not of type {:?}", v, t)),
+        }
+    }
+}
+
+#[nvproc::bincode_derive]
+#[derive(Debug, PartialEq)]
+#[nvproc::serde_derive]
+pub enum EFieldType {
+    Boolean,
+    Integer,
+    Number,
+    String,
+    List(Box<EFieldType>),
+}
+impl EFieldType {
+    pub fn get_default_value(&self) -> FieldValue {
+        match self {
+            EFieldType::Boolean => FieldValue::Boolean(false),
+            EFieldType::Integer => FieldValue::Integer(0),
+            EFieldType::Number => FieldValue::Number(0.0),
+            EFieldType::String => FieldValue::String(String::new()),
+            EFieldType::List(_) => FieldValue::List(Vec::new()),
+        }
+    }
+}
+
+#[nvproc::bincode_derive]
+#[derive(Debug, PartialEq)]
+#[nvproc::serde_derive]
+pub struct FieldSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: EFieldType,
+    ///The value of the field if none is given. If this is not set, the default of the field type is used
+    #[serde(default)]
+    pub default: Option<FieldValue>,
+}
+
+///Describes a component type that is defined at runtime, such as
+/// ```json
+/// {
+///     "name": "Spaceship Specs",
+///     "fields": [
+///         { "name": "Crew", "type": "Integer", "default": 12 },
+///         { "name": "Engines", "type": { "List": "String" } }
+///     ]
+/// }
+/// ```
+#[nvproc::bincode_derive]
+#[derive(Debug, PartialEq)]
+#[nvproc::serde_derive]
+pub struct ComponentSchema {
+    pub name: String,
+    pub fields: Vec<FieldSchema>,
+}
+impl ComponentSchema {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut schema: ComponentSchema = serde_json::from_str(json)?;
+        for i in 0..schema.fields.len() {
+            let field = &schema.fields[i];
+            if schema.fields[..i].iter().any(|f| f.name == field.name) {
+                return Err(anyhow!(
+                    "Field {} appears more than once in schema {}",
+                    field.name,
+                    schema.name
+                ));
+            }
+            if let Some(default) = schema.fields[i].default.take() {
+                let coerced = default.coerce(&schema.fields[i].field_type)?;
+                schema.fields[i].default = Some(coerced);
+            }
+        }
+        Ok(schema)
+    }
+    pub fn get_field(&self, name: &str) -> Option<&FieldSchema> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+    ///Creates a component of this schema. Fields that are not given are set to their default.
+    pub fn instantiate(&self, mut values: BTreeMap<String, FieldValue>) -> Result<CustomComponent> {
+        let mut fields = BTreeMap::new();
+        for field in &self.fields {
+            let value = match values.remove(&field.name) {
+                Some(v) => v.coerce(&field.field_type)?,
+                None => field
+                    .default
+                    .clone()
+                    .unwrap_or_else(|| field.field_type.get_default_value()),
+            };
+            fields.insert(field.name.clone(), value);
+        }
+        if let Some(name) = values.keys().next() {
+            return Err(anyhow!("Schema {} has no field {}", self.name, name));
+        }
+        Ok(CustomComponent {
+            schema: self.name.clone(),
+            fields,
+        })
+    }
+    ///Checks that the component has exactly the fields of this schema, with the right types
+    pub fn validate(&self, component: &CustomComponent) -> Result<()> {
+        if component.schema != self.name {
+            return Err(anyhow!(
+                "Component of schema {} is not of schema {}",
+                component.schema,
+                self.name
+            ));
+        }
+        for field in &self.fields {
+            match component.fields.get(&field.name) {
+                Some(v) if v.is_of_type(&field.field_type) => {}
+                Some(v) => {
+                    return Err(anyhow!(
+                        "Value {:?} of field {} is not of type {:?}",
+                        v,
+                        field.name,
+                        field.field_type
+                    ))
+                }
+                None => return Err(anyhow!("Component is missing field {}", field.name)),
+            }
+        }
+        if component.fields.len() != self.fields.len() {
+            return Err(anyhow!(
+                "Component has fields that are not part of schema {}",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+}
+
+///Holds every [ComponentSchema] known to an [Entman], hashed by name
+#[nvproc::bincode_derive]
+#[derive(Default)]
+pub struct SchemaRegistry {
+    schemas: HashMap<String, ComponentSchema>,
+}
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn register(&mut self, schema: ComponentSchema) -> Result<()> {
+        if self.schemas.contains_key(&schema.name) {
+            return Err(anyhow!("Schema {} is already registered", schema.name));
+        }
+        self.schemas.insert(schema.name.clone(), schema);
+        Ok(())
+    }
+    pub fn get(&self, name: &str) -> Result<&ComponentSchema> {
+        self.schemas
+            .get(name)
+            .ok_or(anyhow!("No schema named {} has been registered", name))
+    }
+    pub fn get_names(&self) -> impl Iterator<Item = &String> {
+        self.schemas.keys()
+    }
+}
+
+impl Entman {
+    pub fn register_schema(&mut self, schema: ComponentSchema) -> Result<()> {
+        self.schemas.register(schema)
+    }
+    ///Parses the schema from json and registers it
+    pub fn register_schema_json(&mut self, json: &str) -> Result<()> {
+        self.register_schema(ComponentSchema::from_json(json)?)
+    }
+    pub fn get_schema(&self, name: &str) -> Result<&ComponentSchema> {
+        self.schemas.get(name)
+    }
+    ///Adds a component of the named schema to the entity. Fields that are not given are set to their default.
+    pub fn add_custom_component(
+        &mut self,
+        entity: Id,
+        schema: &str,
+        values: BTreeMap<String, FieldValue>,
+    ) -> Result<ComponentId> {
+        let component = self.schemas.get(schema)?.instantiate(values)?;
+        self.add_component(entity, component)
+    }
+    ///Sets a field of a custom component, checking it against the schema of the component
+    pub fn set_custom_field(
+        &mut self,
+        id: ComponentId,
+        field: &str,
+        value: FieldValue,
+    ) -> Result<()> {
+        let schema = self
+            .get_component_by_id_ref::<CustomComponent>(id)?
+            .schema
+            .clone();
+        let field_type = &self
+            .schemas
+            .get(&schema)?
+            .get_field(field)
+            .ok_or(anyhow!("Schema {} has no field {}", schema, field))?
+            .field_type;
+        let value = value.coerce(field_type)?;
+        self.get_component_by_id_mut::<CustomComponent>(id)?
+            .component
+            .fields
+            .insert(field.to_string(), value);
+        Ok(())
+    }
+    ///Returns every component of the named schema the entity owns
+    pub fn get_custom_components(
+        &self,
+        entity: Id,
+        schema: &str,
+    ) -> Result<Vec<&Component<CustomComponent>>> {
+        Ok(self
+            .get_components_of::<CustomComponent>(entity)?
+            .into_iter()
+            .filter(|c| c.schema == schema)
+            .collect())
+    }
+    ///Returns every entity owning a component of the named schema, along with those components
+    pub fn query_custom(&self, schema: &str) -> BTreeMap<Id, Vec<&Component<CustomComponent>>> {
+        let mut matches = BTreeMap::new();
+        for table in self.storage.get_matching_tables(&QueryFilter::from_sig(
+            CustomComponent::get_type_id().into(),
+        )) {
+            for id in self.storage.get_table(table).unwrap().get_entities() {
+                let comps = self
+                    .storage
+                    .get_components_ref::<CustomComponent>(*id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|c| c.schema == schema)
+                    .collect::<Vec<_>>();
+                if !comps.is_empty() {
+                    matches.insert(*id, comps);
+                }
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod test_schema {
+    use super::super::archetypes::*;
+    use super::*;
+
+    const SPACESHIP: &str = r#"{
+        "name": "Spaceship Specs",
+        "fields": [
+            { "name": "Crew", "type": "Integer", "default": 12 },
+            { "name": "Speed", "type": "Number", "default": 3 },
+            { "name": "Engines", "type": { "List": "String" } },
+            { "name": "Armed", "type": "Boolean" }
+        ]
+    }"#;
+    const MAGIC: &str = r#"{
+        "name": "Magic System",
+        "fields": [{ "name": "Source", "type": "String", "default": "Mana" }]
+    }"#;
+
+    #[test]
+    fn test_custom_components() {
+        let mut em = Entman::new();
+        em.register_schema_json(SPACESHIP).unwrap();
+        em.register_schema_json(MAGIC).unwrap();
+        assert!(em.register_schema_json(MAGIC).is_err());
+
+        let ship = em.add_entity();
+        let specs = em
+            .add_custom_component(
+                ship,
+                "Spaceship Specs",
+                BTreeMap::from([(
+                    "Engines".to_string(),
+                    FieldValue::List(vec![FieldValue::String("Warp".to_string())]),
+                )]),
+            )
+            .unwrap();
+        let comp = em
+            .get_component_by_id_ref::<CustomComponent>(specs)
+            .unwrap();
+        assert_eq!(comp.get_field("Crew"), Some(&FieldValue::Integer(12)));
+        //integer defaults of number fields are stored as numbers
+        assert_eq!(comp.get_field("Speed"), Some(&FieldValue::Number(3.0)));
+        assert_eq!(comp.get_field("Armed"), Some(&FieldValue::Boolean(false)));
+        em.get_schema("Spaceship Specs")
+            .unwrap()
+            .validate(comp)
+            .unwrap();
+
+        em.set_custom_field(specs, "Speed", FieldValue::Integer(9))
+            .unwrap();
+        assert!(em
+            .set_custom_field(specs, "Speed", FieldValue::String("fast".to_string()))
+            .is_err());
+        assert!(em
+            .set_custom_field(specs, "Shields", FieldValue::Boolean(true))
+            .is_err());
+        let bad = BTreeMap::from([("Crew".to_string(), FieldValue::Boolean(true))]);
+        assert!(em
+            .add_custom_component(ship, "Spaceship Specs", bad)
+            .is_err());
+        assert!(em
+            .add_custom_component(ship, "Unknown", BTreeMap::new())
+            .is_err());
+
+        let wizard = em.add_entity();
+        em.add_custom_component(wizard, "Magic System", BTreeMap::new())
+            .unwrap();
+        em.add_custom_component(ship, "Magic System", BTreeMap::new())
+            .unwrap();
+        let ships = em.query_custom("Spaceship Specs");
+        assert_eq!(ships.keys().copied().collect::<Vec<_>>(), vec![ship]);
+        assert_eq!(
+            ships[&ship][0].get_field("Speed"),
+            Some(&FieldValue::Number(9.0))
+        );
+        assert_eq!(em.query_custom("Magic System").len(), 2);
+        assert_eq!(
+            em.get_custom_components(ship, "Magic System")
+                .unwrap()
+                .len(),
+            1
+        );
+
+        //schemas and custom components survive a round trip through bincode
+        let bytes = bincode::encode_to_vec(&em, bincode::config::standard()).unwrap();
+        let em2: Entman = bincode::decode_from_slice(&bytes, bincode::config::standard())
+            .unwrap()
+            .0;
+        assert!(em2.get_schema("Magic System").is_ok());
+        assert_eq!(em2.query_custom("Spaceship Specs").len(), 1);
+    }
+    #[test]
+    fn test_custom_archetype() {
+        let schema = ComponentSchema::from_json(MAGIC).unwrap();
+        let desc = ArchetypeDescriptor::new("Wizard".to_string(), vec![])
+            .with_custom_component(
+                &schema,
+                BTreeMap::from([(
+                    "Source".to_string(),
+                    FieldValue::String("Blood".to_string()),
+                )]),
+            )
+            .unwrap();
+        let json = serde_json::to_string(&desc).unwrap();
+        let archetype = JsonArchetype::new("Wizard".to_string(), json);
+
+        let mut em = Entman::new();
+        em.register_schema(schema).unwrap();
+        let wizard = em.entity_from_archetype(archetype);
+        let magic = em.query_custom("Magic System");
+        assert_eq!(
+            magic[&wizard][0].get_field("Source"),
+            Some(&FieldValue::String("Blood".to_string()))
+        );
+    }
+}
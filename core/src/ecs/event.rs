@@ -0,0 +1,150 @@
+//! Events of the world as entities, with the entities that take part in them.
+//!
+//! An event is any entity with an [EventComponent], which lists its participants and the roles they
+//! played. Each participant keeps the events it takes part in in an [EventParticipationComponent], so
+//! finding every event involving an entity does not require looking at every event.
+//! Participants should only be changed with the methods below, which keep both sides in step.
+use super::*;
+
+impl Entman {
+    ///Returns the event component of the entity, or an error if it is not an event
+    pub fn get_event(&self, event: Id) -> Result<&EventComponent> {
+        self.check_alive(event)?;
+        Ok(&self
+            .storage
+            .get_component_ref::<EventComponent>(event)
+            .map_err(|_| anyhow!("Entity with id {} is not an event", event))?
+            .component)
+    }
+    //Returns the participation index of an entity, adding it to living entities that have none yet
+    fn get_participation_mut(&mut self, entity: Id) -> Result<&mut EventParticipationComponent> {
+        if !self.entities.contains_key(&entity) {
+            return Ok(&mut self
+                .tombstone_storage
+                .get_component_mut::<EventParticipationComponent>(entity)?
+                .component);
+        }
+        if self
+            .storage
+            .get_component_ref::<EventParticipationComponent>(entity)
+            .is_err()
+        {
+            self.add_component_default::<EventParticipationComponent>(entity)?;
+        }
+        Ok(&mut self
+            .get_component_mut::<EventParticipationComponent>(entity)?
+            .component)
+    }
+    ///Creates a new event entity, recording it on each of its participants
+    pub fn add_event(&mut self, mut event: EventComponent) -> Result<Id> {
+        let participants = std::mem::take(&mut event.participants);
+        for p in participants.iter() {
+            self.check_alive(p.entity)?;
+        }
+        let id = self.add_entity();
+        self.add_component(id, event)?;
+        for p in participants {
+            self.add_participant(id, p.entity, p.role)?;
+        }
+        Ok(id)
+    }
+    ///Makes the entity take part in the event in the given role, replacing any role it had in it
+    pub fn add_participant(&mut self, event: Id, entity: Id, role: EEventRole) -> Result<()> {
+        self.get_event(event)?;
+        self.check_alive(entity)?;
+        let participants = &mut self
+            .get_component_mut::<EventComponent>(event)?
+            .component
+            .participants;
+        match participants.iter_mut().find(|p| p.entity == entity) {
+            Some(p) => p.role = role,
+            None => participants.push(EventParticipant { entity, role }),
+        }
+        let events = &mut self.get_participation_mut(entity)?.events;
+        if !events.contains(&event) {
+            events.push(event);
+        }
+        Ok(())
+    }
+    pub fn remove_participant(&mut self, event: Id, entity: Id) -> Result<()> {
+        self.get_event(event)?;
+        self.get_component_mut::<EventComponent>(event)?
+            .component
+            .participants
+            .retain(|p| p.entity != entity);
+        if let Ok(p) = self.get_participation_mut(entity) {
+            p.events.retain(|e| *e != event);
+        }
+        Ok(())
+    }
+    ///Returns the living participants of the event
+    pub fn get_participants(&self, event: Id) -> Result<Vec<EventParticipant>> {
+        Ok(self
+            .get_event(event)?
+            .get_participants()
+            .iter()
+            .filter(|p| self.entities.contains_key(&p.entity))
+            .cloned()
+            .collect())
+    }
+    ///Returns every living event the entity takes part in, in the order it joined them
+    pub fn get_events_involving(&self, entity: Id) -> Result<Vec<Id>> {
+        self.check_alive(entity)?;
+        Ok(self
+            .storage
+            .get_component_ref::<EventParticipationComponent>(entity)
+            .map(|c| c.component.events.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|e| matches!(self.get_event(*e), Ok(c) if c.get_role(entity).is_some()))
+            .collect())
+    }
+    ///Returns every living event the entity takes part in with the given role
+    pub fn get_events_with_role(&self, entity: Id, role: &EEventRole) -> Result<Vec<Id>> {
+        Ok(self
+            .get_events_involving(entity)?
+            .into_iter()
+            .filter(|e| matches!(self.get_event(*e), Ok(c) if c.get_role(entity) == Some(role)))
+            .collect())
+    }
+    ///Returns every living event that happened at the location
+    pub fn get_events_at_location(&self, location: Id) -> Vec<Id> {
+        self.query(&Query::<EventComponent>::new())
+            .iter_typed()
+            .filter(|(_, c)| c.location == Some(location))
+            .map(|(id, _)| id)
+            .collect()
+    }
+    //Removes every participation to and from the entity, before it is purged
+    pub(crate) fn unlink_event_participants(&mut self, entity: Id) -> Result<()> {
+        let storage = match self.entities.contains_key(&entity) {
+            true => &self.storage,
+            false => &self.tombstone_storage,
+        };
+        let participants = storage
+            .get_component_ref::<EventComponent>(entity)
+            .map(|c| c.component.participants.clone())
+            .unwrap_or_default();
+        let events = storage
+            .get_component_ref::<EventParticipationComponent>(entity)
+            .map(|c| c.component.events.clone())
+            .unwrap_or_default();
+        for p in participants {
+            if let Ok(c) = self.get_participation_mut(p.entity) {
+                c.events.retain(|e| *e != entity);
+            }
+        }
+        for event in events {
+            let component = match self.entities.contains_key(&event) {
+                true => self.get_component_mut::<EventComponent>(event),
+                false => self
+                    .tombstone_storage
+                    .get_component_mut::<EventComponent>(event),
+            };
+            if let Ok(c) = component {
+                c.component.participants.retain(|p| p.entity != entity);
+            }
+        }
+        Ok(())
+    }
+}
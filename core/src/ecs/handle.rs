@@ -0,0 +1,182 @@
+//! Generational entity handles and soft deletion.
+//!
+//! Removing an entity does not destroy it right away. It becomes a tombstone, keeping its components
+//! aside so that it can be restored (for example by undo). A tombstone can later be purged for good.
+//! The generation of an entity is only advanced when a purged id is brought back to life, so an
+//! [EntityHandle] taken before then is reported as stale rather than silently pointing at the new entity.
+use super::*;
+
+///A handle to a specific generation of an entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[nvproc::serde_derive]
+#[derive(bincode::Encode, bincode::Decode)]
+#[bincode(crate = "common::exports::bincode")]
+pub struct EntityHandle {
+    id: Id,
+    generation: u32,
+}
+impl EntityHandle {
+    pub fn new(id: Id, generation: u32) -> Self {
+        Self { id, generation }
+    }
+    pub fn get_id(&self) -> Id {
+        self.id
+    }
+    pub fn get_generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+///The state of the entity an [EntityHandle] refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EHandleStatus {
+    Alive,
+    ///The entity has been removed, but can still be restored
+    Removed,
+    ///The handle refers to a generation of the entity that no longer exists
+    Stale,
+    NeverExisted,
+}
+
+///The errors returned when an entity cannot be accessed. These are wrapped in an [anyhow::Error],
+/// and can be recovered with `downcast_ref::<EEntityError>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EEntityError {
+    ///No entity with this id has ever existed
+    NeverExisted(Id),
+    ///The entity has been removed, but can still be restored
+    Removed(Id),
+    ///The entity was removed and purged, or the handle refers to an earlier generation of it
+    Stale(EntityHandle),
+}
+impl Display for EEntityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EEntityError::NeverExisted(id) => write!(f, "Entity with id {} not found", id),
+            EEntityError::Removed(id) => write!(f, "Entity with id {} has been removed", id),
+            EEntityError::Stale(h) => write!(
+                f,
+                "Handle to generation {} of entity {} is stale",
+                h.generation, h.id
+            ),
+        }
+    }
+}
+impl std::error::Error for EEntityError {}
+
+impl Entman {
+    ///Returns a handle to the current generation of a living entity
+    pub fn get_handle(&self, entity: Id) -> Result<EntityHandle> {
+        Ok(self.get_entity(entity)?.get_handle())
+    }
+    pub fn get_handle_status(&self, handle: EntityHandle) -> EHandleStatus {
+        if let Some(e) = self.entities.get(&handle.id) {
+            match e.generation == handle.generation {
+                true => EHandleStatus::Alive,
+                false => EHandleStatus::Stale,
+            }
+        } else if let Some(e) = self.tombstones.get(&handle.id) {
+            match e.generation == handle.generation {
+                true => EHandleStatus::Removed,
+                false => EHandleStatus::Stale,
+            }
+        } else if self.retired.contains_key(&handle.id) {
+            EHandleStatus::Stale
+        } else {
+            EHandleStatus::NeverExisted
+        }
+    }
+    ///Returns the id of the entity if the handle refers to a living entity
+    pub fn check_handle(&self, handle: EntityHandle) -> Result<Id> {
+        match self.get_handle_status(handle) {
+            EHandleStatus::Alive => Ok(handle.id),
+            EHandleStatus::Removed => Err(EEntityError::Removed(handle.id).into()),
+            EHandleStatus::Stale => Err(EEntityError::Stale(handle).into()),
+            EHandleStatus::NeverExisted => Err(EEntityError::NeverExisted(handle.id).into()),
+        }
+    }
+    ///Returns the error describing why the entity is not alive
+    pub(crate) fn get_entity_error(&self, entity: Id) -> anyhow::Error {
+        if let Some(e) = self.tombstones.get(&entity) {
+            EEntityError::Removed(e.id).into()
+        } else if let Some(generation) = self.retired.get(&entity) {
+            EEntityError::Stale(EntityHandle::new(entity, *generation)).into()
+        } else {
+            EEntityError::NeverExisted(entity).into()
+        }
+    }
+    ///Returns an error if the entity is not alive
+    pub(crate) fn check_alive(&self, entity: Id) -> Result<()> {
+        match self.entities.contains_key(&entity) {
+            true => Ok(()),
+            false => Err(self.get_entity_error(entity)),
+        }
+    }
+    pub fn is_removed(&self, entity: Id) -> bool {
+        self.tombstones.contains_key(&entity)
+    }
+    ///Returns the ids of every removed entity that can still be restored
+    pub fn get_removed_entities(&self) -> Vec<Id> {
+        self.tombstones.keys().copied().collect()
+    }
+    ///Restores a removed entity, along with all of its components
+    pub fn restore_entity(&mut self, entity: Id) -> Result<()> {
+        let mut e = self
+            .tombstones
+            .remove(&entity)
+            .ok_or_else(|| self.get_entity_error(entity))?;
+        e.is_alive = true;
+        self.entities.insert(entity, e);
+        self.storage.insert_entity(entity);
+        for c in self.tombstone_storage.take_entity_components(entity) {
+            self.storage.insert_dynamic(c)?;
+        }
+        self.record_event(EEntityEvent::EntityRestored(entity));
+        Ok(())
+    }
+    ///Permanently deletes a removed entity. Its id is remembered, so handles to it are reported as stale.
+    pub fn purge_entity(&mut self, entity: Id) -> Result<()> {
+        if self.entities.contains_key(&entity) {
+            self.remove_entity(entity)?;
+        }
+        if !self.tombstones.contains_key(&entity) {
+            return Err(self.get_entity_error(entity));
+        }
+        self.unlink_entity(entity)?;
+        self.unlink_event_participants(entity)?;
+        let e = self.tombstones.remove(&entity).unwrap();
+        self.tombstone_storage.remove_entity_components(entity);
+        self.retired.insert(entity, e.generation);
+        Ok(())
+    }
+    ///Permanently deletes every removed entity
+    pub fn purge_removed_entities(&mut self) -> Result<()> {
+        for id in self.get_removed_entities() {
+            self.purge_entity(id)?;
+        }
+        Ok(())
+    }
+    //Purges the removed entity with the given id, if any, so that the id can be taken by a new entity
+    pub(crate) fn revive_entity_id(&mut self, entity: Id) -> Result<()> {
+        if self.tombstones.contains_key(&entity) {
+            self.purge_entity(entity)?;
+        }
+        Ok(())
+    }
+    //Returns the generation a new entity with the given id must have
+    fn get_next_generation(&mut self, entity: Id) -> u32 {
+        match self.retired.remove(&entity) {
+            Some(generation) => generation + 1,
+            None => 0,
+        }
+    }
+    //Inserts a new entity record, reviving a purged id with a new generation.
+    //A removed entity with the same id must be purged first, see [Entman::revive_entity_id].
+    pub(crate) fn insert_entity_record(&mut self, mut entity: Entity) {
+        entity.generation = self.get_next_generation(entity.id);
+        let id = entity.id;
+        self.entities.insert(id, entity);
+        self.storage.insert_entity(id);
+        self.record_event(EEntityEvent::EntityCreated(id));
+    }
+}
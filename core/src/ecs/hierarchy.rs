@@ -0,0 +1,213 @@
+//! The containment hierarchy of entities (planet > continent > city > tavern, or a character and the items they carry).
+//!
+//! The links between a parent and its children are stored in a [HierarchyComponent] on both entities,
+//! so they travel with [EntityOwned] and survive removal and restoration. Removed entities keep their
+//! links, but are skipped by every query, and purging an entity unlinks it from its parent and children.
+use super::*;
+
+///An entity and its whole subtree, each entity owning all of its components
+#[derive(Clone, serde::Serialize)]
+#[serde(crate = "common::exports::serde")]
+pub struct EntityTree {
+    pub entity: EntityOwned,
+    pub children: Vec<EntityTree>,
+}
+impl EntityTree {
+    ///Returns the number of entities in the tree
+    pub fn len(&self) -> usize {
+        1 + self.children.iter().map(|c| c.len()).sum::<usize>()
+    }
+}
+
+impl Entman {
+    //Returns the hierarchy links of an entity, whether it is alive or removed
+    fn get_links(&self, entity: Id) -> Option<&HierarchyComponent> {
+        let storage = match self.entities.contains_key(&entity) {
+            true => &self.storage,
+            false => &self.tombstone_storage,
+        };
+        storage
+            .get_component_ref::<HierarchyComponent>(entity)
+            .ok()
+            .map(|c| &c.component)
+    }
+    //Returns the hierarchy links of an entity, adding them to living entities that have none yet
+    fn get_links_mut(&mut self, entity: Id) -> Result<&mut HierarchyComponent> {
+        if !self.entities.contains_key(&entity) {
+            return Ok(&mut self
+                .tombstone_storage
+                .get_component_mut::<HierarchyComponent>(entity)?
+                .component);
+        }
+        if self.get_links(entity).is_none() {
+            self.add_component_default::<HierarchyComponent>(entity)?;
+        }
+        Ok(&mut self
+            .get_component_mut::<HierarchyComponent>(entity)?
+            .component)
+    }
+    ///Returns the parent of the entity, if it has a living one
+    pub fn get_parent(&self, entity: Id) -> Result<Option<Id>> {
+        self.check_alive(entity)?;
+        Ok(self
+            .get_links(entity)
+            .and_then(|l| l.parent)
+            .filter(|p| self.entities.contains_key(p)))
+    }
+    ///Returns the living children of the entity, in the order they were added
+    pub fn get_children(&self, entity: Id) -> Result<Vec<Id>> {
+        self.check_alive(entity)?;
+        Ok(self
+            .get_links(entity)
+            .map(|l| l.children.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|c| self.entities.contains_key(c))
+            .collect())
+    }
+    ///Makes [child] a child of [parent], detaching it from its previous parent.
+    /// Fails if [parent] is [child] or one of its descendants, as that would create a cycle.
+    pub fn set_parent(&mut self, child: Id, parent: Id) -> Result<()> {
+        self.check_alive(child)?;
+        self.check_alive(parent)?;
+        //the links of removed entities are followed as well, so restoring them can never create a cycle
+        let mut current = Some(parent);
+        while let Some(c) = current {
+            if c == child {
+                return Err(anyhow!(
+                    "Cannot make {} a child of {}, as it would create a cycle",
+                    child,
+                    parent
+                ));
+            }
+            current = self.get_links(c).and_then(|l| l.parent);
+        }
+        self.remove_parent(child)?;
+        self.get_links_mut(child)?.parent = Some(parent);
+        self.get_links_mut(parent)?.children.push(child);
+        Ok(())
+    }
+    ///Detaches the entity from its parent, making it a root
+    pub fn remove_parent(&mut self, child: Id) -> Result<()> {
+        self.check_alive(child)?;
+        let parent = match self.get_links(child).and_then(|l| l.parent) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        self.get_links_mut(child)?.parent = None;
+        //the parent may have been purged already
+        if let Ok(links) = self.get_links_mut(parent) {
+            links.children.retain(|c| *c != child);
+        }
+        Ok(())
+    }
+    ///Returns the living ancestors of the entity, nearest first
+    pub fn get_ancestors(&self, entity: Id) -> Result<Vec<Id>> {
+        let mut ancestors = Vec::new();
+        let mut current = self.get_parent(entity)?;
+        while let Some(p) = current {
+            ancestors.push(p);
+            current = self.get_parent(p)?;
+        }
+        Ok(ancestors)
+    }
+    ///Returns the topmost living ancestor of the entity, or the entity itself if it has no parent
+    pub fn get_root(&self, entity: Id) -> Result<Id> {
+        Ok(self.get_ancestors(entity)?.pop().unwrap_or(entity))
+    }
+    ///Returns every living descendant of the entity, depth first, with parents before their children
+    pub fn get_descendants(&self, entity: Id) -> Result<Vec<Id>> {
+        let mut descendants = Vec::new();
+        let mut stack = self.get_children(entity)?;
+        stack.reverse();
+        while let Some(e) = stack.pop() {
+            descendants.push(e);
+            let mut children = self.get_children(e)?;
+            children.reverse();
+            stack.extend(children);
+        }
+        Ok(descendants)
+    }
+    ///Returns every living descendant of the entity that would match a query for [Q],
+    /// e.g. `get_descendants_with::<LocationComponent>(planet)`
+    pub fn get_descendants_with<Q: QueryTy>(&self, entity: Id) -> Result<Vec<Id>> {
+        let filter = Q::generate_filter();
+        Ok(self
+            .get_descendants(entity)?
+            .into_iter()
+            .filter(|e| {
+                self.storage
+                    .get_entity_table(*e)
+                    .map_or(false, |t| filter.matches(t.get_signature_ref()))
+            })
+            .collect())
+    }
+    ///Removes the entity and all of its descendants, returning the ids of every removed entity, parents first.
+    /// They keep their links, so restoring them all rebuilds the subtree.
+    pub fn remove_entity_recursive(&mut self, entity: Id) -> Result<Vec<Id>> {
+        let mut removed = vec![entity];
+        removed.extend(self.get_descendants(entity)?);
+        for e in removed.iter() {
+            self.remove_entity(*e)?;
+        }
+        Ok(removed)
+    }
+    ///Returns owned copies of the entity and its whole living subtree
+    pub fn get_subtree_owned(&self, entity: Id) -> Result<EntityTree> {
+        Ok(EntityTree {
+            entity: self.get_entity_owned(entity)?,
+            children: self
+                .get_children(entity)?
+                .into_iter()
+                .map(|c| self.get_subtree_owned(c))
+                .collect::<Result<Vec<_>>>()?,
+        })
+    }
+    ///Inserts every entity of the tree, returning the id of its root. The root is attached back to its
+    /// parent if that still exists, and becomes a root otherwise.
+    pub fn entity_tree_from_owned(&mut self, tree: EntityTree) -> Result<Id> {
+        let root = self.insert_tree(tree)?;
+        if let Some(parent) = self.get_links(root).and_then(|l| l.parent) {
+            match self.entities.contains_key(&parent) || self.tombstones.contains_key(&parent) {
+                true => {
+                    let links = self.get_links_mut(parent)?;
+                    if !links.children.contains(&root) {
+                        links.children.push(root);
+                    }
+                }
+                false => self.get_links_mut(root)?.parent = None,
+            }
+        }
+        Ok(root)
+    }
+    fn insert_tree(&mut self, tree: EntityTree) -> Result<Id> {
+        let id = self.entity_from_owned(tree.entity)?;
+        for c in tree.children {
+            //purging an earlier copy of the child may have unlinked it from this entity
+            let child = self.insert_tree(c)?;
+            let links = self.get_links_mut(id)?;
+            if !links.children.contains(&child) {
+                links.children.push(child);
+            }
+        }
+        Ok(id)
+    }
+    //Removes every link to and from the entity, before it is purged
+    pub(crate) fn unlink_entity(&mut self, entity: Id) -> Result<()> {
+        let links = match self.get_links(entity) {
+            Some(l) => l.clone(),
+            None => return Ok(()),
+        };
+        if let Some(parent) = links.parent {
+            if let Ok(l) = self.get_links_mut(parent) {
+                l.children.retain(|c| *c != entity);
+            }
+        }
+        for child in links.children {
+            if let Ok(l) = self.get_links_mut(child) {
+                l.parent = None;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+//! The lifespans of entities, and their ages at any moment of the story.
+//!
+//! Births and deaths are stored as dates in a [LifespanComponent], so ages never go stale. The dates are
+//! measured in the calendar they were set with, whose id the lifespan keeps, and every query must be given
+//! that same calendar.
+use super::*;
+use crate::khronos::{CycleId, DateTime, System};
+use std::cmp::Ordering;
+
+impl Entman {
+    fn get_lifespan(&self, entity: Id) -> Result<Option<&LifespanComponent>> {
+        self.check_alive(entity)?;
+        Ok(self
+            .storage
+            .get_component_ref::<LifespanComponent>(entity)
+            .ok()
+            .map(|c| &c.component))
+    }
+    //Returns the lifespan of the entity, or an error if its dates are measured in another calendar
+    fn get_lifespan_in(&self, entity: Id, system: &System) -> Result<Option<&LifespanComponent>> {
+        let lifespan = self.get_lifespan(entity)?;
+        match lifespan {
+            Some(l) if l.birth.is_some() || l.death.is_some() => match l.calendar {
+                Some(calendar) if calendar != system.get_id() => Err(anyhow!(
+                    "The lifespan of entity {} is measured in calendar {}, not {}",
+                    entity,
+                    calendar,
+                    system.get_id()
+                )),
+                _ => Ok(lifespan),
+            },
+            _ => Ok(lifespan),
+        }
+    }
+    fn get_lifespan_mut(&mut self, entity: Id) -> Result<&mut LifespanComponent> {
+        if self.get_lifespan(entity)?.is_none() {
+            self.add_component_default::<LifespanComponent>(entity)?;
+        }
+        Ok(&mut self
+            .get_component_mut::<LifespanComponent>(entity)?
+            .component)
+    }
+    //Replaces the dates of the entity once they are checked to be valid in the calendar, and in order
+    fn set_lifespan(
+        &mut self,
+        entity: Id,
+        birth: Option<DateTime>,
+        death: Option<DateTime>,
+        calendar: &System,
+    ) -> Result<()> {
+        for date in birth.iter().chain(death.iter()) {
+            calendar.verify_date(date)?;
+        }
+        if let (Some(birth), Some(death)) = (&birth, &death) {
+            if calendar.compare(death, birth)? == Ordering::Less {
+                return Err(anyhow!(
+                    "Entity {} cannot die at {} before it is born at {}",
+                    entity,
+                    death,
+                    birth
+                ));
+            }
+        }
+        *self.get_lifespan_mut(entity)? = LifespanComponent {
+            birth,
+            death,
+            calendar: Some(calendar.get_id()),
+        };
+        Ok(())
+    }
+    pub fn get_birth(&self, entity: Id) -> Result<Option<DateTime>> {
+        Ok(self.get_lifespan(entity)?.and_then(|l| l.birth.clone()))
+    }
+    pub fn get_death(&self, entity: Id) -> Result<Option<DateTime>> {
+        Ok(self.get_lifespan(entity)?.and_then(|l| l.death.clone()))
+    }
+    ///Returns the id of the calendar the birth and death of the entity are measured in
+    pub fn get_lifespan_calendar(&self, entity: Id) -> Result<Option<Id>> {
+        Ok(self.get_lifespan(entity)?.and_then(|l| l.calendar))
+    }
+    ///Sets the birth of the entity, measured in the calendar. Fails if the entity has a death measured in
+    /// another calendar, or one that comes before the birth.
+    pub fn set_birth(
+        &mut self,
+        entity: Id,
+        birth: Option<DateTime>,
+        calendar: &System,
+    ) -> Result<()> {
+        let death = self
+            .get_lifespan_in(entity, calendar)?
+            .and_then(|l| l.death.clone());
+        self.set_lifespan(entity, birth, death, calendar)
+    }
+    ///Sets the death of the entity, measured in the calendar. Fails if the entity has a birth measured in
+    /// another calendar, or one that comes after the death.
+    pub fn set_death(
+        &mut self,
+        entity: Id,
+        death: Option<DateTime>,
+        calendar: &System,
+    ) -> Result<()> {
+        let birth = self
+            .get_lifespan_in(entity, calendar)?
+            .and_then(|l| l.birth.clone());
+        self.set_lifespan(entity, birth, death, calendar)
+    }
+    ///Returns whether the entity was born at or before the date, and had not yet died.
+    /// Entities whose birth is not known are never alive.
+    pub fn is_alive_at(&self, entity: Id, system: &System, date: &DateTime) -> Result<bool> {
+        let lifespan = match self.get_lifespan_in(entity, system)? {
+            Some(l) => l,
+            None => return Ok(false),
+        };
+        let born = match &lifespan.birth {
+            Some(birth) => system.compare(birth, date)? != Ordering::Greater,
+            None => false,
+        };
+        let dead = match &lifespan.death {
+            Some(death) => system.compare(death, date)? != Ordering::Greater,
+            None => false,
+        };
+        Ok(born && !dead)
+    }
+    ///Returns every living entity that was alive at the date
+    pub fn get_alive_at(&self, system: &System, date: &DateTime) -> Result<Vec<Id>> {
+        let mut alive = Vec::new();
+        for entity in self.get_all_living_entities() {
+            if self.is_alive_at(entity, system, date)? {
+                alive.push(entity);
+            }
+        }
+        Ok(alive)
+    }
+    ///Returns the number of whole cycles the entity had lived at the date, counted from its birth the way
+    /// a calendar counts birthdays. Entities stop ageing when they die.
+    pub fn age_at(
+        &self,
+        entity: Id,
+        system: &System,
+        date: &DateTime,
+        cycle_id: CycleId,
+    ) -> Result<i64> {
+        let lifespan = self.get_lifespan_in(entity, system)?;
+        let birth = lifespan
+            .and_then(|l| l.birth.clone())
+            .ok_or_else(|| anyhow!("The birth of entity {} is not known", entity))?;
+        let death = lifespan.and_then(|l| l.death.clone());
+        let date = match &death {
+            Some(death) if system.compare(death, date)? == Ordering::Less => death,
+            _ => date,
+        };
+        if system.compare(date, &birth)? == Ordering::Less {
+            return Err(anyhow!("Entity {} was not yet born at {}", entity, date));
+        }
+        //the difference ignores leap days, so is corrected by counting the birthdays around it
+        let is_after = |age: i64| -> Result<bool> {
+            let birthday = system.add(&birth, age as f64, cycle_id)?;
+            Ok(system.compare(&birthday, date)? == Ordering::Greater)
+        };
+        let mut age = system.difference(&birth, date, cycle_id)?.floor() as i64;
+        while age > 0 && is_after(age)? {
+            age -= 1;
+        }
+        while !is_after(age + 1)? {
+            age += 1;
+        }
+        Ok(age)
+    }
+}
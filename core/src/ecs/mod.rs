@@ -1,11 +1,23 @@
+pub mod change;
 pub mod component;
+pub mod event;
+pub mod handle;
+pub mod hierarchy;
+pub mod lifespan;
+pub mod prefab;
 pub mod prelude;
 pub mod query;
+pub mod system;
+pub mod table;
 mod tests;
 use self::component::archetypes::{Archetype, ArchetypeTy};
+use crate::ecs::change::*;
+use crate::ecs::handle::*;
 use crate::ecs::query::*;
+use crate::ecs::table::*;
 
 use super::*;
+use crate::ecs::component::schema::*;
 use crate::ecs::component::*;
 use common::{
     exports::{
@@ -21,7 +33,7 @@ pub use serde::{Deserialize, Serialize};
 use anyhow::{anyhow, Result};
 use std::{
     any::Any,
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt::Display,
 };
 pub trait ComponentTypeIdTy: TypeIdTy {}
@@ -129,11 +141,12 @@ impl<T: ComponentTy> IntoComponentRef<T> for &dyn ComponentTy {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Default, PartialEq, Eq, Hash)]
 #[nvproc::bincode_derive]
 #[nvproc::serde_derive]
-///The signature of an Entity. An entity can only have one component of each type.
-/// A [Signature] is essentially an ordered set of TypeIds
+///The signature of an Entity. An entity may own several components of the same type, in which case
+/// the type id appears once per component instance.
+/// A [Signature] is essentially an ordered list of TypeIds
 
 pub struct Signature(Vec<TypeId>);
 impl Signature {
@@ -146,6 +159,11 @@ impl Signature {
             self.0.push(type_id);
         }
     }
+    ///Pushes the type id onto the signature, even if it is already present. Used to track
+    /// one entry per component instance.
+    pub fn push(&mut self, type_id: TypeId) {
+        self.0.push(type_id);
+    }
     ///Inserts the type id into the signature, but returns an error if the type id is already in the signature.
     pub fn insert_checked(&mut self, type_id: TypeId) -> Result<()> {
         if self.0.contains(&type_id) {
@@ -171,9 +189,17 @@ impl Signature {
         self.0.remove(index);
         Ok(())
     }
+    ///Removes every occurence of type_id
+    pub fn remove_all(&mut self, type_id: TypeId) {
+        self.0.retain(|&x| x != type_id);
+    }
     pub fn contains(&self, type_id: TypeId) -> bool {
         self.0.contains(&type_id)
     }
+    ///Returns the number of times type_id occurs in the signature
+    pub fn count(&self, type_id: TypeId) -> usize {
+        self.0.iter().filter(|&&x| x == type_id).count()
+    }
     pub fn merge(&mut self, other: &Signature) {
         self.0.extend(other.0.iter().cloned());
     }
@@ -187,6 +213,13 @@ impl Signature {
         }
         sig
     }
+    ///Returns the set of component types in this signature, sorted by type id. Two signatures
+    /// describe the same archetype if their canonical signatures are equal.
+    pub fn get_canonical_signature(&self) -> Signature {
+        let mut sig = self.get_singular_signature();
+        sig.0.sort_by_key(|t| t.get_id());
+        sig
+    }
 }
 impl From<Vec<TypeId>> for Signature {
     fn from(vec: Vec<TypeId>) -> Self {
@@ -228,6 +261,8 @@ impl<'a> IntoIterator for &'a Signature {
 #[nvproc::serde_derive]
 pub struct Entity {
     id: Id,
+    //Advanced every time the id is reused after the entity was purged. See [EntityHandle]
+    generation: u32,
     is_alive: bool,
     sig: Signature,
 }
@@ -235,6 +270,7 @@ impl Entity {
     pub fn new(id: Id) -> Self {
         Self {
             id,
+            generation: 0,
             is_alive: true,
             sig: Signature::new(),
         }
@@ -242,6 +278,7 @@ impl Entity {
     pub fn from_sig(id: Id, sig: Signature) -> Self {
         Self {
             id,
+            generation: 0,
             is_alive: true,
             sig,
         }
@@ -252,17 +289,28 @@ impl Entity {
     pub fn get_id(&self) -> Id {
         self.id
     }
+    pub fn get_generation(&self) -> u32 {
+        self.generation
+    }
+    pub fn get_handle(&self) -> EntityHandle {
+        EntityHandle::new(self.id, self.generation)
+    }
     pub fn add_component<T: ComponentTyReqs>(&mut self) -> Result<()> {
-        let tid = TypeId::of::<T>();
-        self.sig.insert(tid);
-
+        self.add_component_type_id(TypeId::of::<T>());
         Ok(())
     }
+    pub fn add_component_type_id(&mut self, type_id: TypeId) {
+        self.sig.push(type_id);
+    }
+    ///Removes a single instance of T from the signature
     pub fn remove_component<T: ComponentTyReqs>(&mut self) -> Result<()> {
         let tid = TypeId::of::<T>();
         self.sig.remove_component(tid)?;
         Ok(())
     }
+    pub fn remove_component_type_id(&mut self, type_id: TypeId) -> Result<()> {
+        self.sig.remove_component(type_id)
+    }
     pub fn has_component<T: ComponentTyReqs>(&self) -> bool {
         let tid = TypeId::of::<T>();
         self.sig.contains(tid)
@@ -301,6 +349,9 @@ pub struct EntityOwned {
     components: Vec<DynamicComponent>,
 }
 impl EntityOwned {
+    pub fn get_id(&self) -> Id {
+        self.id
+    }
     pub fn get_signature(&self) -> Signature {
         self.signature.clone()
     }
@@ -324,12 +375,30 @@ pub trait CommonComponentStoreTy: Any + Send + Sync {
     fn get_any(&self) -> &dyn CommonComponentStoreTy;
     fn get_any_owned(&self) -> Box<dyn CommonComponentStoreTy>;
     fn get_any_mut(&mut self) -> &mut dyn Any;
-    fn insert_dyn(&mut self, component: DynamicComponent) -> Result<()>;
+    fn insert_dyn(&mut self, component: DynamicComponent) -> Result<ComponentId>;
     fn remove_entity_components(&mut self, entity: Id) -> Result<()>;
-    fn get_dynamic_component(&self, entity: Id) -> Result<DynamicComponent>;
-    ///Returns the component of a given entity as &dyn ComponentTy
+    ///Returns owned copies of every component of this type owned by the entity
+    fn get_dynamic_components(&self, entity: Id) -> Vec<DynamicComponent>;
+    fn get_dynamic_component_by_id(&self, id: ComponentId) -> Result<DynamicComponent>;
+    ///Returns the first component of a given entity as &dyn ComponentTy
     fn get_component_dyn_ref(&self, entity: Id) -> Result<&dyn ComponentTy>;
+    ///Returns every component of this type owned by the entity as &dyn ComponentTy
+    fn get_components_dyn_ref(&self, entity: Id) -> Vec<&dyn ComponentTy>;
+    ///Removes every component of this type owned by the entity
     fn remove_component(&mut self, entity: Id) -> Result<()>;
+    ///Removes a single component instance, returning the id of the entity that owned it (if any)
+    fn remove_component_by_id(&mut self, id: ComponentId) -> Result<Option<Id>>;
+    fn contains_component(&self, id: ComponentId) -> bool;
+    ///Removes every component of this type owned by the entity, returning them
+    fn take_entity_components(&mut self, entity: Id) -> Vec<DynamicComponent>;
+    ///Returns the number of components of this type the entity owns
+    fn get_entity_component_count(&self, entity: Id) -> usize;
+    ///Returns the id of the first component of this type the entity owns
+    fn get_first_component_id(&self, entity: Id) -> Option<ComponentId>;
+    ///Creates a new, empty store of the same type
+    fn new_empty(&self) -> Box<dyn CommonComponentStoreTy>;
+    ///Returns the first component of every entity in this store as &mut dyn ComponentTy
+    fn get_first_components_dyn_mut(&mut self) -> HashMap<Id, &mut dyn ComponentTy>;
 }
 
 impl dyn CommonComponentStoreTy {
@@ -344,7 +413,7 @@ impl dyn CommonComponentStoreTy {
             .ok_or(anyhow!("Could not downcast to CommonComponentStore"))?;
         Ok(any)
     }
-    fn into_store_mut<T: ComponentTyReqs>(&mut self) -> &mut CommonComponentStore<T> {
+    pub(crate) fn into_store_mut<T: ComponentTyReqs>(&mut self) -> &mut CommonComponentStore<T> {
         self.get_any_mut()
             .downcast_mut::<CommonComponentStore<T>>()
             .unwrap()
@@ -375,7 +444,7 @@ impl<T: ComponentTyReqs> CommonComponentStoreTy for CommonComponentStore<T> {
     fn get_any_mut(&mut self) -> &mut dyn Any {
         self
     }
-    fn insert_dyn(&mut self, component: DynamicComponent) -> Result<()> {
+    fn insert_dyn(&mut self, component: DynamicComponent) -> Result<ComponentId> {
         self.insert_dynamic(component)
     }
 
@@ -385,18 +454,58 @@ impl<T: ComponentTyReqs> CommonComponentStoreTy for CommonComponentStore<T> {
     fn remove_entity_components(&mut self, entity: Id) -> Result<()> {
         self.remove_entity_components_internal(entity)
     }
-    fn get_dynamic_component(&self, entity: Id) -> Result<DynamicComponent> {
+    fn get_dynamic_components(&self, entity: Id) -> Vec<DynamicComponent> {
         self.get_owned_entity_components_internal(entity)
     }
+    fn get_dynamic_component_by_id(&self, id: ComponentId) -> Result<DynamicComponent> {
+        Ok(self.get_component_by_id_ref(id)?.into_dynamic())
+    }
     fn get_component_dyn_ref(&self, entity: Id) -> Result<&dyn ComponentTy> {
+        let comp = self.get_first(entity)?;
+        Ok(comp.component.get_any())
+    }
+    fn get_components_dyn_ref(&self, entity: Id) -> Vec<&dyn ComponentTy> {
         self.get_entity_components_as_dyn_ref_internal(entity)
     }
     fn remove_component(&mut self, entity: Id) -> Result<()> {
-        let c = self
-            .components
-            .remove(&entity)
-            .ok_or(anyhow!("Entity does not exist"))?;
-        Ok(())
+        match self.entity_components.contains_key(&entity) {
+            true => self.remove(entity),
+            false => Err(anyhow!("Entity does not exist")),
+        }
+    }
+    fn remove_component_by_id(&mut self, id: ComponentId) -> Result<Option<Id>> {
+        let c = self.remove_by_id(id)?;
+        Ok(c.owning_entity)
+    }
+    fn contains_component(&self, id: ComponentId) -> bool {
+        self.components.contains_key(&id)
+    }
+    fn take_entity_components(&mut self, entity: Id) -> Vec<DynamicComponent> {
+        let ids = self.entity_components.remove(&entity).unwrap_or_default();
+        ids.into_iter()
+            .filter_map(|id| self.components.remove(&id))
+            .map(DynamicComponent::from_component)
+            .collect()
+    }
+    fn get_entity_component_count(&self, entity: Id) -> usize {
+        self.get_entity_component_ids(entity).len()
+    }
+    fn get_first_component_id(&self, entity: Id) -> Option<ComponentId> {
+        self.get_entity_component_ids(entity).first().copied()
+    }
+    fn new_empty(&self) -> Box<dyn CommonComponentStoreTy> {
+        Box::new(CommonComponentStore::<T>::new())
+    }
+    fn get_first_components_dyn_mut(&mut self) -> HashMap<Id, &mut dyn ComponentTy> {
+        let firsts = self
+            .entity_components
+            .iter()
+            .filter_map(|(e, ids)| ids.first().map(|id| (*id, *e)))
+            .collect::<HashMap<ComponentId, Id>>();
+        self.components
+            .iter_mut()
+            .filter_map(|(id, c)| firsts.get(id).map(|e| (*e, c.component.get_any_mut())))
+            .collect()
     }
 }
 
@@ -408,10 +517,18 @@ pub struct DynamicComponent {
     pub owning_entity: Option<Id>,
     component: Box<dyn ComponentTy>,
     type_id: TypeId,
+    //Creates an empty store that can hold this component
+    new_store: fn() -> Box<dyn CommonComponentStoreTy>,
 }
 impl Clone for DynamicComponent {
     fn clone(&self) -> Self {
-        dyn_clone::clone(self)
+        Self {
+            id: self.id,
+            owning_entity: self.owning_entity,
+            component: dyn_clone::clone_box(&*self.component),
+            type_id: self.type_id,
+            new_store: self.new_store,
+        }
     }
 }
 impl DynamicComponent {
@@ -421,11 +538,27 @@ impl DynamicComponent {
             owning_entity: component.owning_entity,
             component: Box::new(component.component),
             type_id: TypeId::of::<T>(),
+            new_store: || Box::new(CommonComponentStore::<T>::new()),
         }
     }
+    pub fn get_id(&self) -> ComponentId {
+        self.id
+    }
+    ///Creates an empty store that can hold this component
+    pub fn new_store(&self) -> Box<dyn CommonComponentStoreTy> {
+        (self.new_store)()
+    }
     pub fn get_type_id(&self) -> TypeId {
         self.type_id
     }
+    ///Returns a copy of the component with a new id, owned by the given entity
+    pub fn clone_for(&self, entity: Id) -> Self {
+        Self {
+            id: uuid::gen_128().into(),
+            owning_entity: Some(entity),
+            ..self.clone()
+        }
+    }
 }
 impl serde::Serialize for DynamicComponent {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -484,12 +617,7 @@ impl<T: ComponentTyReqs> Component<T> {
         }
     }
     pub fn into_dynamic(&self) -> DynamicComponent {
-        DynamicComponent {
-            id: self.id,
-            owning_entity: self.owning_entity,
-            component: Box::new(self.component.clone()),
-            type_id: TypeId::of::<T>(),
-        }
+        DynamicComponent::from_component(self.clone())
     }
     //An orphan component has no owning entity
     pub fn new_orphan(component: T) -> Self {
@@ -576,6 +704,8 @@ impl<T: ComponentTyReqs> Ord for Component<T> {
 pub struct ComponentInfo {
     pub id: Id,
     pub owning_entity: Option<Id>,
+    pub type_id: TypeId,
+    pub ticks: ComponentTicks,
 }
 
 //impl deref
@@ -594,17 +724,16 @@ pub struct CommonComponentStore<T: ComponentTyReqs> {
     //the typs of component this store contains
     type_id: TypeId,
     type_name: String,
-    //the components of this store, hashed by the owning entity id
-    //each entity can only have one component of this type, so one entry in common store per entity
-    components: HashMap<EntityId, Component<T>>,
+    //the components of this store, hashed by their component id
+    components: HashMap<ComponentId, Component<T>>,
+    //the ids of the components each entity owns, in the order they were added.
+    //an entity may own any number of components of this type
+    entity_components: HashMap<EntityId, Vec<ComponentId>>,
 }
 impl<T: ComponentTyReqs + Default> CommonComponentStore<T> {
-    pub fn insert_default(&mut self, owning_entity: Id) -> Result<()> {
+    pub fn insert_default(&mut self, owning_entity: Id) -> Result<ComponentId> {
         let component = Component::new(owning_entity);
-        match self.components.insert(owning_entity, component) {
-            Some(_) => Err(anyhow! {"Entity already has component of type {}", self.type_name}),
-            None => Ok(()),
-        }
+        self.insert_owned(component)
     }
 }
 impl<T: ComponentTyReqs> CommonComponentStore<T> {
@@ -613,49 +742,88 @@ impl<T: ComponentTyReqs> CommonComponentStore<T> {
             type_id: TypeId::of::<T>(),
             type_name: T::get_name().to_string(),
             components: HashMap::new(),
+            entity_components: HashMap::new(),
         }
     }
     pub fn get_type_id(&self) -> TypeId {
         self.type_id
     }
 
-    pub fn insert(&mut self, owning_entity: Id, component: T) -> Result<()> {
-        match self.components.get(&owning_entity) {
-            Some(_) => Err(anyhow! {"Entity already has component of type {}", self.type_name}),
-            None => {
-                self.components
-                    .insert(owning_entity, Component::from(owning_entity, component));
-                Ok(())
-            }
-        }
+    pub fn insert(&mut self, owning_entity: Id, component: T) -> Result<ComponentId> {
+        self.insert_owned(Component::from(owning_entity, component))
     }
-    pub fn insert_dynamic(&mut self, component: DynamicComponent) -> Result<()> {
-        match self.components.get(
-            &component
-                .owning_entity
-                .expect("Component has no owning entity!"),
-        ) {
-            Some(_) => Err(anyhow! {"Entity already has component of type {}", self.type_name}),
-            None => {
-                self.components.insert(
-                    component.owning_entity.unwrap(),
-                    Component::from_dynamic(component),
-                );
-                Ok(())
-            }
+    pub fn insert_dynamic(&mut self, component: DynamicComponent) -> Result<ComponentId> {
+        if component.owning_entity.is_none() {
+            return Err(anyhow!("Component has no owning entity!"));
+        }
+        self.insert_owned(Component::from_dynamic(component))
+    }
+    ///Inserts a fully formed component. The component must have an owning entity, and its id must
+    /// not already be in this store.
+    fn insert_owned(&mut self, component: Component<T>) -> Result<ComponentId> {
+        let owning_entity = component
+            .owning_entity
+            .ok_or(anyhow!("Component has no owning entity!"))?;
+        let id = component.id;
+        if self.components.contains_key(&id) {
+            return Err(anyhow!(
+                "Component with id {} already exists in store of type {}",
+                id,
+                self.type_name
+            ));
         }
+        self.components.insert(id, component);
+        self.entity_components
+            .entry(owning_entity)
+            .or_insert_with(Vec::new)
+            .push(id);
+        Ok(id)
     }
     pub fn get_component_by_id_ref(&self, id: ComponentId) -> Result<&Component<T>> {
-        //loop through components and find the one that matches the id
-        let res = self
-            .components
+        self.components.get(&id).ok_or(anyhow! {
+            "Component with id {} not found", id
+        })
+    }
+    pub fn get_component_by_id_mut(&mut self, id: ComponentId) -> Result<&mut Component<T>> {
+        self.components.get_mut(&id).ok_or(anyhow! {
+            "Component with id {} not found", id
+        })
+    }
+    ///Returns the ids of the components of this type owned by the entity
+    pub fn get_entity_component_ids(&self, owning_entity: Id) -> &[ComponentId] {
+        self.entity_components
+            .get(&owning_entity)
+            .map(|ids| ids.as_slice())
+            .unwrap_or(&[])
+    }
+    ///Returns all the components of this type owned by the entity, in the order they were added
+    pub fn get_entity_components(&self, owning_entity: Id) -> Vec<&Component<T>> {
+        self.get_entity_component_ids(owning_entity)
             .iter()
-            .map(|c| c.1)
-            .find(|c| c.id == id)
-            .ok_or(anyhow! {
-                "Component with id {} not found", id
-            });
-        res
+            .filter_map(|id| self.components.get(id))
+            .collect()
+    }
+    ///Returns the first component of this type the entity owns
+    pub fn get_first(&self, owning_entity: Id) -> Result<&Component<T>> {
+        let id = self
+            .get_entity_component_ids(owning_entity)
+            .first()
+            .ok_or(anyhow!(
+                "Entity does not have component of type {}",
+                self.type_name
+            ))?;
+        self.get_component_by_id_ref(*id)
+    }
+    ///Returns the first component of this type the entity owns
+    pub fn get_first_mut(&mut self, owning_entity: Id) -> Result<&mut Component<T>> {
+        let id = *self
+            .get_entity_component_ids(owning_entity)
+            .first()
+            .ok_or(anyhow!(
+                "Entity does not have component of type {}",
+                self.type_name
+            ))?;
+        self.get_component_by_id_mut(id)
     }
     //Returns the type of this common storage
     pub fn get_common_type(&self) -> Result<EComponentTypes> {
@@ -675,25 +843,48 @@ impl<T: ComponentTyReqs> CommonComponentStore<T> {
         todo!()
     }
 
-    //Delete component of entity from this store
+    //Delete all components of entity from this store
     pub fn remove(&mut self, owning_entity: Id) -> Result<()> {
-        self.components.remove(&owning_entity);
+        if let Some(ids) = self.entity_components.remove(&owning_entity) {
+            for id in ids {
+                self.components.remove(&id);
+            }
+        }
         Ok(())
     }
+    ///Delete a single component from this store, returning it
+    pub fn remove_by_id(&mut self, id: ComponentId) -> Result<Component<T>> {
+        let c = self
+            .components
+            .remove(&id)
+            .ok_or(anyhow!("Component with id {} not found", id))?;
+        if let Some(owner) = c.owning_entity {
+            if let Some(ids) = self.entity_components.get_mut(&owner) {
+                ids.retain(|i| *i != id);
+                if ids.is_empty() {
+                    self.entity_components.remove(&owner);
+                }
+            }
+        }
+        Ok(c)
+    }
     fn remove_entity_components_internal(&mut self, owning_entity: Id) -> Result<()> {
-        self.components.remove(&owning_entity);
-        Ok(())
+        self.remove(owning_entity)
     }
-    fn get_owned_entity_components_internal(&self, owning_entity: Id) -> Result<DynamicComponent> {
-        let comp = self.components.get(&owning_entity).unwrap();
-        Ok(DynamicComponent::from_component(comp.clone()))
+    fn get_owned_entity_components_internal(&self, owning_entity: Id) -> Vec<DynamicComponent> {
+        self.get_entity_components(owning_entity)
+            .into_iter()
+            .map(|c| c.into_dynamic())
+            .collect()
     }
     fn get_entity_components_as_dyn_ref_internal(
         &self,
         owning_entity: Id,
-    ) -> Result<&dyn ComponentTy> {
-        let comp = self.components.get(&owning_entity).unwrap();
-        Ok(comp.component.get_any() as &dyn ComponentTy)
+    ) -> Vec<&dyn ComponentTy> {
+        self.get_entity_components(owning_entity)
+            .into_iter()
+            .map(|c| c.component.get_any())
+            .collect()
     }
 }
 impl<'de> serde::Deserialize<'de> for Box<dyn CommonComponentStoreTy> {
@@ -728,105 +919,348 @@ impl bincode::Decode for Box<dyn CommonComponentStoreTy> {
 
 //the binary storage component stores and manages the access of binary data (like audio/video)
 //the components that need access
-//Stores all the component data in hashmaps indexed by the owning entity id
+//Stores all the component data in archetype tables. Every entity lives in the table matching the
+//set of component types it owns, and each table stores its components in one column per type.
 #[derive(bincode::Encode, bincode::Decode)]
 #[bincode(crate = "common::exports::bincode")]
 pub struct Storage {
-    //The bins of components. Points to a vector of components, hashed by type id
-    bins: HashMap<TypeId, Box<dyn CommonComponentStoreTy>>,
+    //The archetype tables. A table is never removed once created, so a [TableId] stays valid
+    tables: Vec<ArchetypeTable>,
+    //The table of each canonical signature
+    table_index: HashMap<Signature, TableId>,
+    //The table each entity currently lives in
+    entity_tables: HashMap<Id, TableId>,
+    //Information about every component in storage, hashed by component id
     component_infos: HashMap<Id, ComponentInfo>,
+    //The ids of the components each entity owns, so its infos can be found without scanning them all
+    entity_components: HashMap<Id, HashSet<Id>>,
+    query_cache: QueryCache,
+    change_log: ChangeLog,
 }
 impl Storage {
     pub fn new() -> Self {
         Self {
-            bins: HashMap::new(),
+            tables: Vec::new(),
+            table_index: HashMap::new(),
+            entity_tables: HashMap::new(),
             component_infos: HashMap::new(),
+            entity_components: HashMap::new(),
+            query_cache: QueryCache::new(),
+            change_log: ChangeLog::new(),
         }
     }
-    pub fn insert_default<T: ComponentTyReqs + Default + serde::Serialize + Clone>(
+    pub fn get_tables(&self) -> &[ArchetypeTable] {
+        &self.tables
+    }
+    pub fn get_table(&self, table: TableId) -> Option<&ArchetypeTable> {
+        self.tables.get(table)
+    }
+    ///Marks the first component of every type fetched by the filter as changed, for each matching entity
+    /// accepted by [include]
+    pub(crate) fn mark_fetched_changed(
         &mut self,
-        entity: Id,
+        filter: &QueryFilter,
+        include: impl Fn(&Id) -> bool,
     ) -> Result<()> {
-        let id = TypeId::of::<T>();
-        match self.bins.contains_key(&id) {
-            true => {
-                let mut store = self.bins.get_mut(&id).unwrap();
-                //downcast to the correct type
-                let store = store
-                    .as_mut()
-                    .get_any_mut()
-                    .downcast_mut::<CommonComponentStore<T>>()
-                    .unwrap();
-                store.insert_default(entity);
+        let mut types = filter.fetch.clone();
+        types.merge(&filter.optional);
+        let mut fetched = Vec::new();
+        for table in self.get_matching_tables(filter) {
+            let table = &self.tables[table];
+            for tid in types.get_singular_signature().get_type_ids() {
+                if let Some(col) = table.get_column(*tid) {
+                    fetched.extend(
+                        table
+                            .get_entities()
+                            .iter()
+                            .filter(|id| include(id))
+                            .filter_map(|id| col.get_first_component_id(*id)),
+                    );
+                }
             }
-            false => {
-                let mut store = CommonComponentStore::<T>::new();
-                store.insert_default(entity);
-                self.bins.insert(id, Box::new(store));
+        }
+        for id in fetched {
+            self.mark_changed(id)?;
+        }
+        Ok(())
+    }
+    ///Returns mutable references to the components fetched by the filter, for each of the given entities.
+    /// The components of an entity are ordered as in [QueryFilter::fetch], followed by any optional components it owns.
+    /// Every fetched component is marked as changed.
+    pub fn get_matching_components_mut(
+        &mut self,
+        filter: &QueryFilter,
+        entities: &HashSet<Id>,
+    ) -> Result<BTreeMap<Id, Vec<&mut dyn ComponentTy>>> {
+        self.mark_fetched_changed(filter, |id| entities.contains(id))?;
+        let matching = self.get_matching_tables(filter);
+        let mut matches = BTreeMap::new();
+        for table in self
+            .tables
+            .iter_mut()
+            .filter(|t| matching.contains(&t.get_id()))
+        {
+            let table_entities = table.get_entities().to_vec();
+            let mut columns = table
+                .get_columns_mut()
+                .filter(|(tid, _)| filter.fetch.contains(**tid) || filter.optional.contains(**tid))
+                .map(|(tid, col)| (*tid, col.get_first_components_dyn_mut()))
+                .collect::<HashMap<_, _>>();
+            for id in table_entities.iter().filter(|id| entities.contains(id)) {
+                let mut c = Vec::new();
+                for tid in filter.fetch.get_type_ids() {
+                    c.push(columns.get_mut(tid).unwrap().remove(id).unwrap());
+                }
+                for tid in filter.optional.get_type_ids() {
+                    if let Some(comp) = columns.get_mut(tid).and_then(|col| col.remove(id)) {
+                        c.push(comp);
+                    }
+                }
+                matches.insert(*id, c);
             }
         }
+        Ok(matches)
+    }
+    ///Returns the table the entity currently lives in
+    pub fn get_entity_table(&self, entity: Id) -> Option<&ArchetypeTable> {
+        self.entity_tables.get(&entity).map(|t| &self.tables[*t])
+    }
+    ///Returns the ids of all the tables whose signature holds every type fetched by the filter,
+    /// and none of the types it excludes
+    pub fn get_matching_tables(&self, filter: &QueryFilter) -> Vec<TableId> {
+        self.query_cache.get_matching_tables(filter, &self.tables)
+    }
+    ///Returns the table with the given signature, creating it if it does not yet exist
+    fn get_or_create_table(&mut self, sig: &Signature) -> TableId {
+        let sig = sig.get_canonical_signature();
+        match self.table_index.get(&sig) {
+            Some(t) => *t,
+            None => {
+                let id = self.tables.len();
+                self.tables.push(ArchetypeTable::new(id, sig.clone()));
+                self.table_index.insert(sig, id);
+                id
+            }
+        }
+    }
+    ///Adds an entity with no components to storage
+    pub fn insert_entity(&mut self, entity: Id) {
+        if !self.entity_tables.contains_key(&entity) {
+            let table = self.get_or_create_table(&Signature::new());
+            self.tables[table].insert_entity(entity);
+            self.entity_tables.insert(entity, table);
+        }
+    }
+    ///Moves the entity into the table with signature [sig], carrying all of its components with it.
+    /// Returns the id of the table the entity now lives in.
+    fn move_entity(&mut self, entity: Id, sig: &Signature) -> TableId {
+        let dest = self.get_or_create_table(sig);
+        match self.entity_tables.get(&entity).copied() {
+            Some(src) if src == dest => {}
+            Some(src) => {
+                let comps = self.tables[src].take_entity(entity);
+                let table = &mut self.tables[dest];
+                table.insert_entity(entity);
+                for c in comps {
+                    if !table.has_column(c.get_type_id()) {
+                        table.insert_column(c.get_type_id(), c.new_store());
+                    }
+                    table
+                        .get_column_mut(c.get_type_id())
+                        .unwrap()
+                        .insert_dyn(c)
+                        .expect("Component already exists in destination table");
+                }
+            }
+            None => self.tables[dest].insert_entity(entity),
+        }
+        self.entity_tables.insert(entity, dest);
+        dest
+    }
+    ///Returns the table an entity must live in to be able to store a component of [type_id],
+    /// moving the entity if needed
+    fn prepare_insert(&mut self, entity: Id, type_id: TypeId) -> TableId {
+        let mut sig = self
+            .get_entity_table(entity)
+            .map(|t| t.get_signature_ref().clone())
+            .unwrap_or_default();
+        if sig.contains(type_id) {
+            return self.entity_tables[&entity];
+        }
+        sig.insert(type_id);
+        self.move_entity(entity, &sig)
+    }
+    ///Moves the entity to a smaller table if it no longer owns any component of [type_id]
+    fn shrink_entity(&mut self, entity: Id, type_id: TypeId) {
+        if let Some(table) = self.get_entity_table(entity) {
+            let remaining = table
+                .get_column(type_id)
+                .map(|c| c.get_entity_component_count(entity))
+                .unwrap_or(0);
+            if remaining == 0 {
+                let mut sig = table.get_signature_ref().clone();
+                sig.remove_all(type_id);
+                self.move_entity(entity, &sig);
+            }
+        }
+    }
+    fn register_info(&mut self, id: ComponentId, owning_entity: Id, type_id: TypeId) {
+        let tick = self.change_log.record(EEntityEvent::ComponentAdded {
+            entity: owning_entity,
+            component: id,
+            type_id,
+        });
+        self.entity_components
+            .entry(owning_entity)
+            .or_default()
+            .insert(*id);
+        self.component_infos.insert(
+            *id,
+            ComponentInfo {
+                id: *id,
+                owning_entity: Some(owning_entity),
+                type_id,
+                ticks: ComponentTicks::new(tick),
+            },
+        );
+    }
+    //Removes the info of the component from the indices, returning it
+    fn unregister_info(&mut self, id: Id) -> Option<ComponentInfo> {
+        let info = self.component_infos.remove(&id)?;
+        if let Some(entity) = info.owning_entity {
+            if let Some(ids) = self.entity_components.get_mut(&entity) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.entity_components.remove(&entity);
+                }
+            }
+        }
+        Some(info)
+    }
+    //Records the removal of a component
+    fn record_removed(&mut self, info: &ComponentInfo) {
+        if let Some(entity) = info.owning_entity {
+            self.change_log.record(EEntityEvent::ComponentRemoved {
+                entity,
+                component: info.id.into(),
+                type_id: info.type_id,
+            });
+        }
+    }
+    ///Marks the component as changed at the current tick
+    pub fn mark_changed(&mut self, id: ComponentId) -> Result<()> {
+        let info = self
+            .component_infos
+            .get(&*id)
+            .ok_or(anyhow!("Component with id {} not found", id))?;
+        let event = EEntityEvent::ComponentChanged {
+            entity: info.owning_entity.unwrap_or_default(),
+            component: id,
+            type_id: info.type_id,
+        };
+        let tick = self.change_log.record(event);
+        self.component_infos.get_mut(&*id).unwrap().ticks.changed = tick;
         Ok(())
     }
-    pub fn insert_dynamic(&mut self, component: DynamicComponent) -> Result<()> {
+    pub fn get_change_log(&self) -> &ChangeLog {
+        &self.change_log
+    }
+    pub fn get_change_log_mut(&mut self) -> &mut ChangeLog {
+        &mut self.change_log
+    }
+    pub fn insert_default<T: ComponentTyReqs + Default + serde::Serialize + Clone>(
+        &mut self,
+        entity: Id,
+    ) -> Result<ComponentId> {
+        let table = self.prepare_insert(entity, TypeId::of::<T>());
+        let id = self.tables[table]
+            .get_or_create_column::<T>()
+            .insert_default(entity)?;
+        self.register_info(id, entity, TypeId::of::<T>());
+        Ok(id)
+    }
+    pub fn insert_dynamic(&mut self, component: DynamicComponent) -> Result<ComponentId> {
         let tid = component.get_type_id();
-        let mut store = self.bins.get_mut(&tid).unwrap();
-        store.insert_dyn(component)
+        let owner = component
+            .owning_entity
+            .ok_or(anyhow!("Component has no owning entity!"))?;
+        let table = self.prepare_insert(owner, tid);
+        let table = &mut self.tables[table];
+        if !table.has_column(tid) {
+            table.insert_column(tid, component.new_store());
+        }
+        let id = table.get_column_mut(tid).unwrap().insert_dyn(component)?;
+        self.register_info(id, owner, tid);
+        Ok(id)
     }
 
     pub fn insert_component<T: ComponentTyReqs + serde::Serialize + Clone>(
         &mut self,
         entity: Id,
         component: T,
-    ) -> Result<()> {
-        let id = TypeId::of::<T>();
-
-        match self.bins.contains_key(&id) {
-            true => {
-                let mut store = self.bins.get_mut(&id).unwrap();
-                //downcast to the correct type
-                let store = (store.as_mut().get_any_mut())
-                    .downcast_mut::<CommonComponentStore<T>>()
-                    .unwrap();
-                store.insert(entity, component);
-            }
-            false => {
-                let mut store = CommonComponentStore::<T>::new();
-                store.insert(entity, component);
-                self.bins.insert(id, Box::new(store));
-            }
+    ) -> Result<ComponentId> {
+        let table = self.prepare_insert(entity, TypeId::of::<T>());
+        let id = self.tables[table]
+            .get_or_create_column::<T>()
+            .insert(entity, component)?;
+        self.register_info(id, entity, TypeId::of::<T>());
+        Ok(id)
+    }
+    ///Returns the column of type T of the table the entity lives in
+    fn get_entity_store<T: ComponentTyReqs>(&self, entity: Id) -> Result<&CommonComponentStore<T>> {
+        match self
+            .get_entity_table(entity)
+            .and_then(|t| t.get_column(TypeId::of::<T>()))
+        {
+            Some(store) => store.into_store(),
+            None => Err(anyhow!(
+                "Entity does not have component of type {}",
+                std::any::type_name::<T>()
+            )),
+        }
+    }
+    fn get_entity_store_mut<T: ComponentTyReqs>(
+        &mut self,
+        entity: Id,
+    ) -> Result<&mut CommonComponentStore<T>> {
+        let table = *self
+            .entity_tables
+            .get(&entity)
+            .ok_or(anyhow!("Entity with id {} not found", entity))?;
+        match self.tables[table].get_column_mut(TypeId::of::<T>()) {
+            Some(store) => Ok(store.into_store_mut::<T>()),
+            None => Err(anyhow!(
+                "Entity does not have component of type {}",
+                std::any::type_name::<T>()
+            )),
         }
-        Ok(())
     }
-    //Get the component of the given type for the given entity
+    //Get the first component of the given type for the given entity
     pub fn get_component_ref<'c, T: ComponentTyReqs>(
         &'c self,
         entity: EntityId,
     ) -> Result<&'c Component<T>> {
-        match self.bins.contains_key(&TypeId::of::<T>()) {
-            true => {
-                let store = self.bins.get(&TypeId::of::<T>()).unwrap();
-                let store = store.into_store().unwrap();
-                let comp = store.components.get(&entity);
-                match comp {
-                    Some(c) => Ok(c),
-                    None => Err(anyhow! {"Entity dooes not have component"}),
-                }
-            }
-            false => Err(anyhow!(
-                "No component store of type {} has yet been created",
-                std::any::type_name::<T>()
-            )),
+        self.get_entity_store::<T>(entity)?.get_first(entity)
+    }
+    ///Get every component of the given type for the given entity
+    pub fn get_components_ref<T: ComponentTyReqs>(
+        &self,
+        entity: EntityId,
+    ) -> Result<Vec<&Component<T>>> {
+        match self.get_entity_store::<T>(entity) {
+            Ok(store) => Ok(store.get_entity_components(entity)),
+            Err(_) => Ok(Vec::new()),
         }
     }
-    ///Returns a list of all components of type id [type_id] for the given [entity]
+    ///Returns the first component of type id [type_id] for the given [entity]
     fn get_component_dyn_ref(&self, type_id: TypeId, entity: Id) -> Result<&dyn ComponentTy> {
-        match self.bins.contains_key(&type_id) {
-            true => {
-                let store = self.bins.get(&type_id).unwrap();
-                store.get_component_dyn_ref(entity)
-            }
-            false => Err(anyhow!(
-                "No component store of type {} has yet been created",
+        match self
+            .get_entity_table(entity)
+            .and_then(|t| t.get_column(type_id))
+        {
+            Some(store) => store.get_component_dyn_ref(entity),
+            None => Err(anyhow!(
+                "Entity does not have component of type {}",
                 type_id.get_name_ref()
             )),
         }
@@ -836,103 +1270,123 @@ impl Storage {
         &mut self,
         entity: Id,
     ) -> Result<&mut Component<T>> {
-        let id = TypeId::of::<T>();
-        match self.bins.contains_key(&id) {
-            true => {
-                let store = self.bins.get_mut(&id).unwrap();
-                //downcast to the correct type
-                let store = store.into_store_mut::<T>();
-                match store.components.get_mut(&entity) {
-                    Some(component) => Ok(component),
-                    None => Err(anyhow!(
-                        "Entity does not have component of type {}",
-                        std::any::type_name::<T>()
-                    )),
-                }
-            }
-            false => Err(anyhow!(
-                "No component store of type {} has yet been created",
-                std::any::type_name::<T>()
-            )),
-        }
+        self.get_entity_store_mut::<T>(entity)?
+            .get_first_mut(entity)
+    }
+    ///Returns the entity that owns the component with the given id
+    fn get_component_owner(&self, id: ComponentId) -> Result<Id> {
+        self.get_component_info(id)?
+            .owning_entity
+            .ok_or(anyhow!("Component with id {} has no owning entity", id))
     }
     pub fn get_component_by_id_ref<T: ComponentTyReqs>(
         &self,
         id: ComponentId,
     ) -> Result<&Component<T>> {
-        match self.bins.get(&T::get_type_id()) {
-            Some(s) => {
-                let store = s.into_store()?;
-                store.get_component_by_id_ref(id)
-            }
-            None => Err(
-                anyhow! { "No component store of type {} has yet been created",
-                    std::any::type_name::<T>()
-                },
-            ),
-        }
+        let owner = self.get_component_owner(id)?;
+        self.get_entity_store::<T>(owner)?
+            .get_component_by_id_ref(id)
+    }
+    pub fn get_component_by_id_mut<T: ComponentTyReqs>(
+        &mut self,
+        id: ComponentId,
+    ) -> Result<&mut Component<T>> {
+        let owner = self.get_component_owner(id)?;
+        self.get_entity_store_mut::<T>(owner)?
+            .get_component_by_id_mut(id)
+    }
+    pub fn get_component_info(&self, id: ComponentId) -> Result<&ComponentInfo> {
+        self.component_infos
+            .get(&*id)
+            .ok_or(anyhow!("Component with id {} not found", id))
     }
     ///Get owned clones of all the [Component]'s owned by a given entity
     pub fn get_entity_owned_components(&self, entity: Id) -> Result<Vec<DynamicComponent>> {
         let mut comps: Vec<DynamicComponent> = Vec::new();
-        for (_, store) in self.bins.iter() {
-            let mut cs = store.get_dynamic_component(entity)?;
-            comps.push(cs)
+        if let Some(table) = self.get_entity_table(entity) {
+            for store in table.get_columns() {
+                comps.extend(store.get_dynamic_components(entity));
+            }
         }
         Ok(comps)
     }
     pub fn get_components_dyn_ref(&self, entity: Id) -> Result<Vec<&dyn ComponentTy>> {
         let mut res = Vec::new();
-        for (_, b) in self.bins.iter() {
-            res.push(b.get_component_dyn_ref(entity)?)
+        if let Some(table) = self.get_entity_table(entity) {
+            for store in table.get_columns() {
+                res.extend(store.get_components_dyn_ref(entity));
+            }
         }
         Ok(res)
     }
 
-    ///Removes all components associated with the given entity
+    ///Removes the entity and all components associated with it
     pub fn remove_entity_components(&mut self, entity: Id) {
-        for (_, store) in self.bins.iter_mut() {
-            let store = store;
-            store.remove_entity_components(entity);
+        self.take_entity_components(entity);
+    }
+    ///Removes the entity from storage, returning all of its components
+    pub fn take_entity_components(&mut self, entity: Id) -> Vec<DynamicComponent> {
+        let comps = match self.entity_tables.remove(&entity) {
+            Some(table) => self.tables[table].take_entity(entity),
+            None => Vec::new(),
+        };
+        for id in self.entity_components.remove(&entity).unwrap_or_default() {
+            self.component_infos.remove(&id);
         }
+        comps
     }
+    ///Removes every component of type T owned by the entity
     pub fn remove_component<T: ComponentTyReqs>(&mut self, entity: Id) -> Result<()> {
-        let id = TypeId::of::<T>();
-        match self.bins.contains_key(&id) {
-            true => {
-                let mut store = self.bins.get_mut(&id).unwrap();
-                //downcast to the correct type
-                let store = store
-                    .as_mut()
-                    .get_any_mut()
-                    .downcast_mut::<CommonComponentStore<T>>()
-                    .unwrap();
-                store.remove(entity);
-            }
-            false => {
-                return Err(anyhow!(
-                    "No component store of type {} has yet been created",
-                    std::any::type_name::<T>()
-                ))
-            }
-        }
-        Ok(())
+        self.remove_component_by_type_id(entity, TypeId::of::<T>())
     }
+    ///Removes every component of type [type_id] owned by the entity
     pub fn remove_component_by_type_id(&mut self, entity: Id, type_id: TypeId) -> Result<()> {
-        match self.bins.contains_key(&type_id) {
-            true => {
-                let mut store = self.bins.get_mut(&type_id).unwrap();
-                store.remove_component(entity);
+        let table = *self
+            .entity_tables
+            .get(&entity)
+            .ok_or(anyhow!("Entity with id {} not found", entity))?;
+        match self.tables[table].get_column_mut(type_id) {
+            Some(store) => {
+                store.remove_component(entity)?;
+                let removed = self
+                    .entity_components
+                    .get(&entity)
+                    .into_iter()
+                    .flatten()
+                    .filter(|id| self.component_infos[*id].type_id == type_id)
+                    .copied()
+                    .collect::<Vec<_>>();
+                for id in removed {
+                    let info = self.unregister_info(id).unwrap();
+                    self.record_removed(&info);
+                }
+                self.shrink_entity(entity, type_id);
             }
-            false => {
+            None => {
                 return Err(anyhow!(
-                    "No component store of type {} has yet been created",
+                    "Entity does not have component of type {}",
                     type_id.get_name_ref()
                 ))
             }
         }
         Ok(())
     }
+    ///Removes a single component, returning its info
+    pub fn remove_component_by_id(&mut self, id: ComponentId) -> Result<ComponentInfo> {
+        let owner = self.get_component_owner(id)?;
+        let info = self.unregister_info(*id).unwrap();
+        let table = self.entity_tables[&owner];
+        let store = self.tables[table]
+            .get_column_mut(info.type_id)
+            .ok_or(anyhow!(
+                "Entity does not have component of type {}",
+                info.type_id.get_name_ref()
+            ))?;
+        store.remove_component_by_id(id)?;
+        self.shrink_entity(owner, info.type_id);
+        self.record_removed(&info);
+        Ok(info)
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -965,19 +1419,33 @@ impl Display for TypeIdInternal {
 #[derive(bincode::Encode, bincode::Decode)]
 #[bincode(crate = "common::exports::bincode")]
 pub struct Entman {
+    //The living entities
     entities: HashMap<Id, Entity>,
     storage: Storage,
+    //Removed entities that can still be restored, and their components
+    tombstones: HashMap<Id, Entity>,
+    tombstone_storage: Storage,
+    //The last generation of every purged entity
+    retired: HashMap<Id, u32>,
+    //The schemas of the runtime defined components
+    schemas: SchemaRegistry,
+    relationships: component::relationship::RelationshipGraphCache,
 }
 impl Entman {
     pub fn new() -> Self {
         Entman {
             entities: HashMap::new(),
             storage: Storage::new(),
+            tombstones: HashMap::new(),
+            tombstone_storage: Storage::new(),
+            retired: HashMap::new(),
+            relationships: Default::default(),
+            schemas: SchemaRegistry::new(),
         }
     }
     pub fn add_entity(&mut self) -> Id {
         let ent = uuid::gen_128();
-        self.entities.insert(ent, Entity::new(ent));
+        self.insert_entity_record(Entity::new(ent));
         ent
     }
     ///Creates a new entity from an archetype
@@ -985,8 +1453,7 @@ impl Entman {
         let ent = uuid::gen_128();
         let desc = archetype.describe();
         let sig = desc.get_signature();
-        self.entities.insert(ent, Entity::from_sig(ent, sig));
-        let entity_mut = self.get_entity_mut(ent).unwrap();
+        self.insert_entity_record(Entity::from_sig(ent, sig));
 
         for c in desc.take_components().into_iter() {
             c.insert_component_into_storage(&mut self.storage, ent);
@@ -996,8 +1463,11 @@ impl Entman {
     }
     ///Create an entity from an [EntityOwned], returning the id
     pub fn entity_from_owned(&mut self, e: EntityOwned) -> Result<Id> {
-        let entity = Entity::from_sig(e.id, e.get_signature());
-        self.entities.insert(e.id, entity);
+        if self.entities.contains_key(&e.id) {
+            return Err(anyhow!("Entity with id {} already exists", e.id));
+        }
+        self.revive_entity_id(e.id)?;
+        self.insert_entity_record(Entity::from_sig(e.id, e.get_signature()));
         for c in e.components {
             self.storage.insert_dynamic(c)?;
         }
@@ -1008,39 +1478,53 @@ impl Entman {
         let desc = archetype.describe();
         let sig = desc.get_signature();
 
-        let entity_mut = self.get_entity_mut(entity).unwrap();
+        if let Some(e) = self.entities.get_mut(&entity) {
+            e.sig.merge(&sig);
+        }
         for c in desc.take_components().into_iter() {
             c.insert_component_into_storage(&mut self.storage, entity);
         }
 
         entity
     }
-    //Removes the entity from the entity manager
-    pub fn remove_entity(&mut self, entity: Id) {
-        self.entities.remove(&entity);
-        self.storage.remove_entity_components(entity);
+    ///Removes the entity from the entity manager. The entity is kept as a tombstone
+    /// until it is purged, so that it can be restored with [Entman::restore_entity].
+    pub fn remove_entity(&mut self, entity: Id) -> Result<()> {
+        self.check_alive(entity)?;
+        let mut e = self.entities.remove(&entity).unwrap();
+        e.is_alive = false;
+        self.tombstones.insert(entity, e);
+        self.tombstone_storage.insert_entity(entity);
+        for c in self.storage.take_entity_components(entity) {
+            self.tombstone_storage.insert_dynamic(c)?;
+        }
+        self.record_event(EEntityEvent::EntityRemoved(entity));
+        Ok(())
     }
-    //Adds a component to an entity
+    ///Adds a component to an entity, returning the id of the new component. An entity may own
+    /// any number of components of the same type.
     pub fn add_component<T: ComponentTyReqs + serde::Serialize + Clone>(
         &mut self,
         entity: Id,
         component: T,
-    ) -> Result<()> {
-        self.storage.insert_component::<T>(entity, component);
-        //add to entity signature
+    ) -> Result<ComponentId> {
+        self.check_alive(entity)?;
         let ent = self.entities.get_mut(&entity).unwrap();
+        let id = self.storage.insert_component::<T>(entity, component)?;
+        //add to entity signature
         ent.add_component::<T>()?;
-        Ok(())
+        Ok(id)
     }
     ///Adds a component to an entity, calling the default "constructor"
     pub fn add_component_default<T: ComponentTyReqs + Default + serde::Serialize + Clone>(
         &mut self,
         entity: Id,
-    ) -> Result<()> {
+    ) -> Result<ComponentId> {
+        self.check_alive(entity)?;
         let ent = self.entities.get_mut(&entity).unwrap();
+        let id = self.storage.insert_default::<T>(entity)?;
         ent.add_component::<T>()?;
-        self.storage.insert_default::<T>(entity)?;
-        Ok(())
+        Ok(id)
     }
     pub fn get_entity_ref(&self, entity: Id) -> Option<EntityRef> {
         Some(EntityRef {
@@ -1061,13 +1545,13 @@ impl Entman {
     fn get_entity(&self, entity: Id) -> Result<&Entity> {
         self.entities
             .get(&entity)
-            .ok_or(anyhow!("Entity with id {} not found", entity))
+            .ok_or_else(|| self.get_entity_error(entity))
     }
     pub fn get_entity_clone(&self, entity: Id) -> Result<Entity> {
         let e = self
             .entities
             .get(&entity)
-            .ok_or(anyhow!("Entity with id {} not found", entity))?;
+            .ok_or_else(|| self.get_entity_error(entity))?;
         Ok(e.clone())
     }
     pub fn get_all_living_entities(&self) -> Vec<Id> {
@@ -1093,6 +1577,7 @@ impl Entman {
         &'c self,
         entity: Id,
     ) -> Result<&'c Component<T>> {
+        self.check_alive(entity)?;
         self.storage.get_component_ref::<T>(entity)
     }
     pub fn get_component_by_id_ref<T: ComponentTyReqs>(
@@ -1101,58 +1586,160 @@ impl Entman {
     ) -> Result<&Component<T>> {
         self.storage.get_component_by_id_ref(id)
     }
-    //Returns a reference to a component of an entity, if it exists
+    ///Returns every component of type T the entity owns, in the order they were added
+    pub fn get_components_of<T: ComponentTyReqs>(&self, entity: Id) -> Result<Vec<&Component<T>>> {
+        self.get_entity(entity)?;
+        self.storage.get_components_ref::<T>(entity)
+    }
+    //Returns a reference to the first component of type T of an entity, if it exists.
+    //The component is marked as changed.
     pub fn get_component_mut<T: ComponentTyReqs>(
         &mut self,
         entity: Id,
     ) -> Result<&mut Component<T>> {
-        self.storage.get_component_mut::<T>(entity)
+        self.check_alive(entity)?;
+        let id = self.storage.get_component_ref::<T>(entity)?.get_id();
+        self.get_component_by_id_mut(id)
+    }
+    ///Returns a mutable reference to the component with the given id, marking it as changed
+    pub fn get_component_by_id_mut<T: ComponentTyReqs>(
+        &mut self,
+        id: ComponentId,
+    ) -> Result<&mut Component<T>> {
+        self.storage.get_component_by_id_ref::<T>(id)?;
+        self.storage.mark_changed(id)?;
+        self.storage.get_component_by_id_mut(id)
     }
     pub fn get_components_dyn_ref(&self, entity: Id) -> Result<Vec<&dyn ComponentTy>> {
+        self.check_alive(entity)?;
         self.storage.get_components_dyn_ref(entity)
     }
     //Rreturns a vector of [DynamicComponent]
     pub fn get_components_dynamic(&self, entity: Id) -> Result<Vec<DynamicComponent>> {
+        self.check_alive(entity)?;
         self.storage.get_entity_owned_components(entity)
     }
+    ///Removes every component of type T from the entity
     pub fn remove_component<T: ComponentTyReqs>(&mut self, entity: Id) -> Result<()> {
-        self.storage.remove_component::<T>(entity)
+        self.remove_component_by_type_id(TypeId::of::<T>(), entity)
     }
+    ///Removes every component of the given type from the entity
     pub fn remove_component_by_type_id(
         &mut self,
         type_id: common::type_id::TypeId,
         entity: Id,
     ) -> Result<()> {
-        self.storage.remove_component_by_type_id(entity, type_id)
+        self.check_alive(entity)?;
+        let ent = self.entities.get_mut(&entity).unwrap();
+        self.storage.remove_component_by_type_id(entity, type_id)?;
+        ent.sig.remove_all(type_id);
+        Ok(())
+    }
+    ///Removes a single component instance, leaving any other components of the same type on the
+    /// owning entity untouched.
+    pub fn remove_component_by_id(&mut self, id: ComponentId) -> Result<()> {
+        let info = self.storage.remove_component_by_id(id)?;
+        if let Some(e) = info
+            .owning_entity
+            .and_then(|owner| self.entities.get_mut(&owner))
+        {
+            e.remove_component_type_id(info.type_id)?;
+        }
+        Ok(())
     }
 
+    fn record_event(&mut self, event: EEntityEvent) -> Tick {
+        self.storage.get_change_log_mut().record(event)
+    }
+    ///Returns the current change tick. Every change made after this call happens at a later tick.
+    pub fn get_tick(&self) -> Tick {
+        self.storage.get_change_log().get_tick()
+    }
+    pub fn get_component_ticks(&self, id: ComponentId) -> Result<ComponentTicks> {
+        Ok(self.storage.get_component_info(id)?.ticks)
+    }
+    ///Returns every event that happened after the given tick, oldest first
+    pub fn get_events_since(&self, tick: Tick) -> impl Iterator<Item = &EEntityEvent> {
+        self.storage
+            .get_change_log()
+            .get_events_since(tick)
+            .map(|(_, e)| e)
+    }
+    ///Removes every recorded event, returning them along with the tick they happened at.
+    /// Ticks are not reset.
+    pub fn drain_events(&mut self) -> Vec<(Tick, EEntityEvent)> {
+        self.storage.get_change_log_mut().drain()
+    }
     ///Runs the given query, returning a vector of entities that match the query.
+    /// Entities with several components of a fetched type yield only the first of them, see [Query].
     pub fn query<'a, Q: QueryTy, P: PredicateTy<'a, Q>>(
         &'a self,
         query: &ecs::query::Query<'a, Q, P>,
     ) -> QueryResult<'a, Q> {
-        let sig = Q::generate_sig();
-        //iterate over all entities, and check if they match the signature of the query
+        let filter = Q::generate_filter();
+        //only the entities of the tables that match the filter of the query need to be checked
         let mut ids = Vec::new();
         let mut components = Vec::new();
-        for (id, entity) in self.entities.iter() {
-            if entity.get_signature().get_singular_signature() == sig {
-                let id = id;
-
-                let qf = QueryFetch::new(*id, &self);
-                if query.predicate().check(qf) {
-                    ids.push(*id);
+        for table in self.storage.get_matching_tables(&filter) {
+            let table = self.storage.get_table(table).unwrap();
+            for id in table.get_entities() {
+                let qf = QueryFetch::new(*id, self);
+                if !query.predicate().check(qf) {
+                    continue;
                 }
+                ids.push(*id);
                 let mut c = Vec::new();
-                for tid in &sig {
-                    let comps = self.storage.get_component_dyn_ref(*tid, *id).unwrap();
+                for tid in &filter.fetch {
+                    let comps = table
+                        .get_column(*tid)
+                        .unwrap()
+                        .get_component_dyn_ref(*id)
+                        .unwrap();
                     c.push(comps);
                 }
+                //optional components are only fetched if the table has them
+                for tid in &filter.optional {
+                    if let Some(comp) = table
+                        .get_column(*tid)
+                        .and_then(|col| col.get_component_dyn_ref(*id).ok())
+                    {
+                        c.push(comp);
+                    }
+                }
                 components.push(c);
             }
         }
         QueryResult::<Q>::new(ids, components)
     }
+    ///Like [Entman::query], but gives mutable access to the matched components, the first of each type.
+    /// Every fetched component is marked as changed, whether or not it is then written to.
+    /// Returns an error if the query fetches the same component type more than once,
+    /// since that would alias a mutable reference.
+    pub fn query_mut<'q, Q: QueryTy, P: for<'b> PredicateTy<'b, Q>>(
+        &mut self,
+        query: &ecs::query::Query<'q, Q, P>,
+    ) -> Result<QueryResultMut<'_, Q>> {
+        let filter = Q::generate_filter();
+        let mut fetched = filter.fetch.clone();
+        fetched.merge(&filter.optional);
+        if fetched.get_singular_signature().get_type_ids().len() != fetched.get_type_ids().len() {
+            return Err(anyhow!(
+                "A mutable query may only fetch each component type once"
+            ));
+        }
+        //predicates only need shared access, so they are checked before borrowing mutably
+        let mut ids = HashSet::new();
+        for table in self.storage.get_matching_tables(&filter) {
+            let table = self.storage.get_table(table).unwrap();
+            for id in table.get_entities() {
+                if query.predicate().check(QueryFetch::new(*id, self)) {
+                    ids.insert(*id);
+                }
+            }
+        }
+        let matches = self.storage.get_matching_components_mut(&filter, &ids)?;
+        Ok(QueryResultMut::new(matches))
+    }
 }
 
 #[cfg(test)]
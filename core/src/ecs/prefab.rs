@@ -0,0 +1,208 @@
+//! Entity cloning and prefabs.
+//!
+//! Any entity can serve as a prefab. Its instances inherit the values of its components until they
+//! are overridden locally, and are brought up to date with [Entman::sync_prefab_instances].
+//! A component type is overridden as soon as a component of that type is added to, or borrowed mutably
+//! from, the instance. This is detected from the change ticks of the components, so no bookkeeping is
+//! required from the caller. Removing an inherited component is not an override, and the next sync restores it.
+use super::*;
+
+//Component types that are never copied from a prefab to its instances
+fn is_inheritable(type_id: TypeId) -> bool {
+    type_id != TypeId::of::<HierarchyComponent>()
+        && type_id != TypeId::of::<PrefabInstanceComponent>()
+}
+fn of_type(comps: &[DynamicComponent], type_id: TypeId) -> Vec<&DynamicComponent> {
+    comps
+        .iter()
+        .filter(|c| c.get_type_id() == type_id)
+        .collect()
+}
+//Serializes the components so that their values can be compared
+fn get_values(comps: &[&DynamicComponent]) -> Result<Vec<serde_json::Value>> {
+    comps
+        .iter()
+        .map(|c| Ok(serde_json::to_value(&c.component)?))
+        .collect()
+}
+
+impl Entman {
+    //Adds a copy of the component, with a new id, to the entity
+    fn insert_component_copy(
+        &mut self,
+        entity: Id,
+        component: &DynamicComponent,
+    ) -> Result<ComponentId> {
+        self.check_alive(entity)?;
+        let id = self.storage.insert_dynamic(component.clone_for(entity))?;
+        self.entities
+            .get_mut(&entity)
+            .unwrap()
+            .add_component_type_id(component.get_type_id());
+        Ok(id)
+    }
+    //Replaces every component of the given type the entity owns with copies of [components]
+    fn replace_components(
+        &mut self,
+        entity: Id,
+        type_id: TypeId,
+        components: &[&DynamicComponent],
+    ) -> Result<()> {
+        if self
+            .get_entity(entity)?
+            .get_signature_ref()
+            .contains(type_id)
+        {
+            self.remove_component_by_type_id(type_id, entity)?;
+        }
+        for c in components {
+            self.insert_component_copy(entity, c)?;
+        }
+        Ok(())
+    }
+    ///Creates a copy of the entity, with new ids for all of its components. The copy is placed under the same
+    /// parent as the original, but does not copy its children.
+    pub fn clone_entity(&mut self, entity: Id) -> Result<Id> {
+        let comps = self.get_components_dynamic(entity)?;
+        let clone = self.add_entity();
+        for c in comps
+            .iter()
+            .filter(|c| c.get_type_id() != TypeId::of::<HierarchyComponent>())
+        {
+            self.insert_component_copy(clone, c)?;
+        }
+        if let Some(parent) = self.get_parent(entity)? {
+            self.set_parent(clone, parent)?;
+        }
+        Ok(clone)
+    }
+    ///Creates a new entity that inherits every component of the prefab
+    pub fn instantiate_prefab(&mut self, prefab: Id) -> Result<Id> {
+        let comps = self.get_components_dynamic(prefab)?;
+        let instance = self.add_entity();
+        for c in comps.iter().filter(|c| is_inheritable(c.get_type_id())) {
+            self.insert_component_copy(instance, c)?;
+        }
+        let synced = self.get_tick();
+        self.add_component(
+            instance,
+            PrefabInstanceComponent {
+                prefab,
+                overrides: Vec::new(),
+                synced,
+            },
+        )?;
+        Ok(instance)
+    }
+    ///Returns the prefab the entity was instantiated from, if any
+    pub fn get_prefab(&self, instance: Id) -> Result<Option<Id>> {
+        self.check_alive(instance)?;
+        Ok(self
+            .storage
+            .get_component_ref::<PrefabInstanceComponent>(instance)
+            .ok()
+            .map(|c| c.component.prefab))
+    }
+    ///Returns every living instance of the prefab
+    pub fn get_prefab_instances(&self, prefab: Id) -> Vec<Id> {
+        self.get_all_living_entities()
+            .into_iter()
+            .filter(|e| matches!(self.get_prefab(*e), Ok(Some(p)) if p == prefab))
+            .collect()
+    }
+    fn get_prefab_link(&self, instance: Id) -> Result<PrefabInstanceComponent> {
+        self.check_alive(instance)?;
+        Ok(self
+            .storage
+            .get_component_ref::<PrefabInstanceComponent>(instance)
+            .map_err(|_| anyhow!("Entity with id {} is not a prefab instance", instance))?
+            .component
+            .clone())
+    }
+    //Stores the link, without counting it as a local change
+    fn set_prefab_link(&mut self, instance: Id, link: PrefabInstanceComponent) -> Result<()> {
+        self.get_component_mut::<PrefabInstanceComponent>(instance)?
+            .component = link;
+        Ok(())
+    }
+    //Records every inherited component type changed on the instance since it was last synced as overridden
+    fn detect_overrides(&mut self, instance: Id) -> Result<PrefabInstanceComponent> {
+        let mut link = self.get_prefab_link(instance)?;
+        for c in self.get_components_dynamic(instance)? {
+            let type_id = c.get_type_id();
+            if is_inheritable(type_id)
+                && !link.overrides.contains(&type_id)
+                && self
+                    .get_component_ticks(c.get_id())?
+                    .is_changed_since(link.synced)
+            {
+                link.overrides.push(type_id);
+            }
+        }
+        Ok(link)
+    }
+    ///Returns the component types of the instance that are no longer inherited from its prefab
+    pub fn get_prefab_overrides(&mut self, instance: Id) -> Result<Vec<TypeId>> {
+        let link = self.detect_overrides(instance)?;
+        let overrides = link.overrides.clone();
+        self.set_prefab_link(instance, link)?;
+        Ok(overrides)
+    }
+    //Copies every component of the prefab the instance still inherits
+    fn sync_instance(&mut self, instance: Id, mut link: PrefabInstanceComponent) -> Result<()> {
+        let prefab_comps = self.get_components_dynamic(link.prefab)?;
+        let instance_comps = self.get_components_dynamic(instance)?;
+        let mut types = Vec::new();
+        for c in prefab_comps.iter().chain(instance_comps.iter()) {
+            let type_id = c.get_type_id();
+            if is_inheritable(type_id)
+                && !link.overrides.contains(&type_id)
+                && !types.contains(&type_id)
+            {
+                types.push(type_id);
+            }
+        }
+        for type_id in types {
+            let from = of_type(&prefab_comps, type_id);
+            let to = of_type(&instance_comps, type_id);
+            if get_values(&from)? != get_values(&to)? {
+                self.replace_components(instance, type_id, &from)?;
+            }
+        }
+        link.synced = self.get_tick();
+        self.set_prefab_link(instance, link)
+    }
+    ///Brings every instance of the prefab up to date with it, leaving overridden components untouched
+    pub fn sync_prefab_instances(&mut self, prefab: Id) -> Result<()> {
+        self.check_alive(prefab)?;
+        for instance in self.get_prefab_instances(prefab) {
+            let link = self.detect_overrides(instance)?;
+            self.sync_instance(instance, link)?;
+        }
+        Ok(())
+    }
+    ///Discards every local override of the instance, including components the prefab does not have
+    pub fn revert_to_prefab(&mut self, instance: Id) -> Result<()> {
+        let mut link = self.detect_overrides(instance)?;
+        link.overrides.clear();
+        self.sync_instance(instance, link)
+    }
+    ///Discards the local override of a single component type of the instance
+    pub fn revert_component_to_prefab(&mut self, instance: Id, type_id: TypeId) -> Result<()> {
+        let mut link = self.detect_overrides(instance)?;
+        link.overrides.retain(|t| *t != type_id);
+        self.sync_instance(instance, link)
+    }
+    ///Copies every overridden component of the instance to its prefab, which then passes them on to all of its
+    /// other instances
+    pub fn apply_overrides_to_prefab(&mut self, instance: Id) -> Result<()> {
+        let mut link = self.detect_overrides(instance)?;
+        let comps = self.get_components_dynamic(instance)?;
+        for type_id in link.overrides.drain(..) {
+            self.replace_components(link.prefab, type_id, &of_type(&comps, type_id))?;
+        }
+        let prefab = link.prefab;
+        self.sync_instance(instance, link)?;
+        self.sync_prefab_instances(prefab)
+    }
+}
@@ -31,6 +31,22 @@ impl<'a, Q: QueryTy> QueryResult<'a, Q> {
             .iter()
             .map(|(id, comps)| (*id, comps.as_slice()))
     }
+    ///Iterates over the matches, yielding the components of each entity as [QueryTy::Item],
+    /// i.e. (&A, &B, ...) for a query of (A, B, ...)
+    pub fn iter_typed(&self) -> impl Iterator<Item = (Id, Q::Item<'a>)> + '_ {
+        self.matches.iter().map(|(id, comps)| {
+            (
+                *id,
+                Q::fetch(comps).expect("Query matched an entity without all of its components"),
+            )
+        })
+    }
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
     pub fn get_component<'b, T: ComponentTy>(&'b self, ent: Id) -> Result<&'b T> {
         if !Q::contains::<T>() {
             return Err(anyhow!(
@@ -38,36 +54,283 @@ impl<'a, Q: QueryTy> QueryResult<'a, Q> {
                 T::get_name()
             ));
         }
-        let comps = self.matches.get(&ent).unwrap();
+        let comps = self
+            .matches
+            .get(&ent)
+            .ok_or(anyhow!("Entity with id {} does not match the query", ent))?;
+        //optional components may be missing
         let comp = comps
             .iter()
             .find(|c| c.get_component_type_id() == T::get_type_id())
-            .unwrap();
+            .ok_or(anyhow!(
+                "Entity with id {} has no component of type {}",
+                ent,
+                T::get_name()
+            ))?;
         comp.downcast_ref()
             .ok_or(anyhow!("Could not downcast component"))
     }
 }
+///Describes which archetype tables a query matches. A table matches if its signature contains every
+/// component in [fetch](QueryFilter::fetch) and [with](QueryFilter::with), and none of the components in
+/// [without](QueryFilter::without). Components in [optional](QueryFilter::optional) never affect matching,
+/// they are only fetched if the entity happens to own them.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct QueryFilter {
+    pub fetch: Signature,
+    pub with: Signature,
+    pub without: Signature,
+    pub optional: Signature,
+}
+impl QueryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    ///Creates a filter that requires and fetches every component in sig
+    pub fn from_sig(sig: Signature) -> Self {
+        QueryFilter {
+            fetch: sig,
+            ..Default::default()
+        }
+    }
+    pub fn merge(&mut self, other: &QueryFilter) {
+        self.fetch.merge(&other.fetch);
+        self.with.merge(&other.with);
+        self.without.merge(&other.without);
+        self.optional.merge(&other.optional);
+    }
+    ///Returns the filter with every part in canonical form, so that equivalent filters compare equal.
+    pub fn get_canonical_filter(&self) -> QueryFilter {
+        QueryFilter {
+            fetch: self.fetch.get_canonical_signature(),
+            with: self.with.get_canonical_signature(),
+            without: self.without.get_canonical_signature(),
+            optional: self.optional.get_canonical_signature(),
+        }
+    }
+    ///Returns true if a table with the given signature matches the filter
+    pub fn matches(&self, sig: &Signature) -> bool {
+        self.fetch.get_type_ids().iter().all(|t| sig.contains(*t))
+            && self.with.get_type_ids().iter().all(|t| sig.contains(*t))
+            && !self.without.get_type_ids().iter().any(|t| sig.contains(*t))
+    }
+}
+
+///[QueryResultMut] holds mutable references to the components of every entity that matched a query
+pub struct QueryResultMut<'a, Q: QueryTy> {
+    query: std::marker::PhantomData<Q>,
+    matches: BTreeMap<Id, Vec<&'a mut dyn ComponentTy>>,
+}
+impl<'a, Q: QueryTy> QueryResultMut<'a, Q> {
+    pub fn new(matches: BTreeMap<Id, Vec<&'a mut dyn ComponentTy>>) -> Self {
+        QueryResultMut {
+            query: PhantomData,
+            matches,
+        }
+    }
+    ///Iterates over the matches, yielding the components of each entity as [QueryTy::ItemMut],
+    /// i.e. (&mut A, &mut B, ...) for a query of (A, B, ...)
+    pub fn iter_typed_mut(&mut self) -> std::vec::IntoIter<(Id, Q::ItemMut<'_>)> {
+        self.matches
+            .iter_mut()
+            .map(|(id, comps)| {
+                let mut comps = comps.iter_mut().map(|c| &mut **c).collect::<Vec<_>>();
+                (
+                    *id,
+                    Q::fetch_mut(&mut comps)
+                        .expect("Query matched an entity without all of its components"),
+                )
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+    pub fn get_component_mut<T: ComponentTy>(&mut self, ent: Id) -> Result<&mut T> {
+        if !Q::contains::<T>() {
+            return Err(anyhow!(
+                "Tried to get component of type {} but query doesn't contain it",
+                T::get_name()
+            ));
+        }
+        let comps = self
+            .matches
+            .get_mut(&ent)
+            .ok_or(anyhow!("Entity with id {} does not match the query", ent))?;
+        let comp = comps
+            .iter_mut()
+            .find(|c| c.get_component_type_id() == T::get_type_id())
+            .ok_or(anyhow!(
+                "Entity with id {} has no component of type {}",
+                ent,
+                T::get_name()
+            ))?;
+        let any: &mut dyn Any = &mut **comp;
+        any.downcast_mut()
+            .ok_or(anyhow!("Could not downcast component"))
+    }
+    pub fn ids(&self) -> impl Iterator<Item = Id> + '_ {
+        self.matches.keys().copied()
+    }
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+}
+impl<'a, Q: QueryTy> IntoIterator for QueryResultMut<'a, Q> {
+    type Item = (Id, Q::ItemMut<'a>);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.matches
+            .into_iter()
+            .map(|(id, mut comps)| {
+                (
+                    id,
+                    Q::fetch_mut(&mut comps)
+                        .expect("Query matched an entity without all of its components"),
+                )
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+///Returns the first component of type T in comps
+fn fetch_ref<'a, T: ComponentTy>(comps: &[&'a dyn ComponentTy]) -> Option<&'a T> {
+    comps
+        .iter()
+        .find(|c| c.get_component_type_id() == T::get_type_id())
+        .and_then(|c| {
+            let any: &'a dyn Any = *c;
+            any.downcast_ref()
+        })
+}
+///Takes the first component of type T out of comps
+fn take_mut<'a, T: ComponentTy>(comps: &mut Vec<&'a mut dyn ComponentTy>) -> Option<&'a mut T> {
+    let index = comps
+        .iter()
+        .position(|c| c.get_component_type_id() == T::get_type_id())?;
+    let any: &'a mut dyn Any = comps.remove(index);
+    any.downcast_mut()
+}
+
 ///The trait representing queryable types
-pub trait QueryTy {
+pub trait QueryTy: 'static {
+    ///The typed view of the components this query fetches for a single entity
+    type Item<'a>
+    where
+        Self: 'a;
+    ///The mutable typed view of the components this query fetches for a single entity
+    type ItemMut<'a>
+    where
+        Self: 'a;
+    ///The components that are required and fetched by the query
     fn generate_sig() -> Signature;
     fn contains<T: ComponentTy>() -> bool;
+    ///Builds the typed view from the fetched components of an entity
+    fn fetch<'a>(comps: &[&'a dyn ComponentTy]) -> Option<Self::Item<'a>>;
+    ///Builds the mutable typed view, taking the components it uses out of comps
+    fn fetch_mut<'a>(comps: &mut Vec<&'a mut dyn ComponentTy>) -> Option<Self::ItemMut<'a>>;
+    ///The full filter of the query, including [With], [Without] and [Option] terms
+    fn generate_filter() -> QueryFilter {
+        QueryFilter::from_sig(Self::generate_sig())
+    }
     // fn from_dyn_vec(vec: Vec<&dyn ComponentTy>) -> Result<Self>
     // where
     //     Self: std::marker::Sized;
 }
 //implement QueryTy for all T that implement TypeIdTy and are ComponentTy
-impl<'a, T: TypeIdTy + ComponentTy> QueryTy for T {
+impl<T: TypeIdTy + ComponentTy> QueryTy for T {
+    type Item<'a> = &'a T;
+    type ItemMut<'a> = &'a mut T;
     fn generate_sig() -> Signature {
         <T as TypeIdTy>::get_type_id().into()
     }
     fn contains<Q: ComponentTy>() -> bool {
         <Q as TypeIdTy>::get_type_id() == <T as TypeIdTy>::get_type_id()
     }
+    fn fetch<'a>(comps: &[&'a dyn ComponentTy]) -> Option<Self::Item<'a>> {
+        fetch_ref(comps)
+    }
+    fn fetch_mut<'a>(comps: &mut Vec<&'a mut dyn ComponentTy>) -> Option<Self::ItemMut<'a>> {
+        take_mut(comps)
+    }
     // fn from_dyn_vec(vec: Vec<&dyn ComponentTy>) -> Result<Self> {
     //     todo!()
     // }
 }
 
+///Query term that requires the entity to own a T, without fetching it
+pub struct With<T>(PhantomData<T>);
+impl<T: ComponentTy> QueryTy for With<T> {
+    type Item<'a> = ();
+    type ItemMut<'a> = ();
+    fn generate_sig() -> Signature {
+        Signature::new()
+    }
+    fn contains<Q: ComponentTy>() -> bool {
+        false
+    }
+    fn fetch<'a>(_: &[&'a dyn ComponentTy]) -> Option<Self::Item<'a>> {
+        Some(())
+    }
+    fn fetch_mut<'a>(_: &mut Vec<&'a mut dyn ComponentTy>) -> Option<Self::ItemMut<'a>> {
+        Some(())
+    }
+    fn generate_filter() -> QueryFilter {
+        QueryFilter {
+            with: T::get_type_id().into(),
+            ..Default::default()
+        }
+    }
+}
+///Query term that excludes every entity owning a T
+pub struct Without<T>(PhantomData<T>);
+impl<T: ComponentTy> QueryTy for Without<T> {
+    type Item<'a> = ();
+    type ItemMut<'a> = ();
+    fn generate_sig() -> Signature {
+        Signature::new()
+    }
+    fn contains<Q: ComponentTy>() -> bool {
+        false
+    }
+    fn fetch<'a>(_: &[&'a dyn ComponentTy]) -> Option<Self::Item<'a>> {
+        Some(())
+    }
+    fn fetch_mut<'a>(_: &mut Vec<&'a mut dyn ComponentTy>) -> Option<Self::ItemMut<'a>> {
+        Some(())
+    }
+    fn generate_filter() -> QueryFilter {
+        QueryFilter {
+            without: T::get_type_id().into(),
+            ..Default::default()
+        }
+    }
+}
+///Query term that fetches T if the entity owns one, but matches regardless
+impl<T: ComponentTy> QueryTy for Option<T> {
+    type Item<'a> = Option<&'a T>;
+    type ItemMut<'a> = Option<&'a mut T>;
+    fn generate_sig() -> Signature {
+        Signature::new()
+    }
+    fn contains<Q: ComponentTy>() -> bool {
+        <Q as TypeIdTy>::get_type_id() == <T as TypeIdTy>::get_type_id()
+    }
+    fn fetch<'a>(comps: &[&'a dyn ComponentTy]) -> Option<Self::Item<'a>> {
+        Some(fetch_ref(comps))
+    }
+    fn fetch_mut<'a>(comps: &mut Vec<&'a mut dyn ComponentTy>) -> Option<Self::ItemMut<'a>> {
+        Some(take_mut(comps))
+    }
+    fn generate_filter() -> QueryFilter {
+        QueryFilter {
+            optional: T::get_type_id().into(),
+            ..Default::default()
+        }
+    }
+}
 nvproc::generate_query_ty_tuple_impls!();
 
 ///The [NullPredicate] always returns true. For internal use only.
@@ -93,13 +356,63 @@ where
     Q: QueryTy,
 {
     fn check(&self, fetch: QueryFetch<'em, Q>) -> bool;
+    ///Returns a predicate that is true only if both self and other are true
+    fn and<P: PredicateTy<'em, Q>>(self, other: P) -> And<Self, P>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+    ///Returns a predicate that is true if either self or other is true
+    fn or<P: PredicateTy<'em, Q>>(self, other: P) -> Or<Self, P>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+    ///Returns a predicate that is true only if self is false
+    fn not(self) -> Not<Self>
+    where
+        Self: Sized,
+    {
+        Not(self)
+    }
 }
 impl<'em, Q: QueryTy, T: Fn<(QueryFetch<'em, Q>,), Output = bool>> PredicateTy<'em, Q> for T {
     fn check(&self, fetch: QueryFetch<'em, Q>) -> bool {
         self(fetch)
     }
 }
+///Predicate that is true only if both of its predicates are true. See [PredicateTy::and]
+pub struct And<A, B>(pub A, pub B);
+impl<'em, Q: QueryTy, A: PredicateTy<'em, Q>, B: PredicateTy<'em, Q>> PredicateTy<'em, Q>
+    for And<A, B>
+{
+    fn check(&self, fetch: QueryFetch<'em, Q>) -> bool {
+        self.0.check(fetch) && self.1.check(fetch)
+    }
+}
+///Predicate that is true if either of its predicates is true. See [PredicateTy::or]
+pub struct Or<A, B>(pub A, pub B);
+impl<'em, Q: QueryTy, A: PredicateTy<'em, Q>, B: PredicateTy<'em, Q>> PredicateTy<'em, Q>
+    for Or<A, B>
+{
+    fn check(&self, fetch: QueryFetch<'em, Q>) -> bool {
+        self.0.check(fetch) || self.1.check(fetch)
+    }
+}
+///Predicate that inverts its predicate. See [PredicateTy::not]
+pub struct Not<A>(pub A);
+impl<'em, Q: QueryTy, A: PredicateTy<'em, Q>> PredicateTy<'em, Q> for Not<A> {
+    fn check(&self, fetch: QueryFetch<'em, Q>) -> bool {
+        !self.0.check(fetch)
+    }
+}
 ///A [Query] that retrieves components, or Entities from} the ECS (Entman)
+///
+/// An entity matches if it owns at least one component of every fetched type. When it owns several components
+/// of the same type, only the first one added is fetched, by [Entman::query], [Entman::query_mut],
+/// [QueryFetch::get] and systems alike. Use [Entman::get_components_of] to reach every instance.
 pub struct Query<'em, T: QueryTy, P = NullPredicate<T>>
 where
     P: PredicateTy<'em, T>,
@@ -120,6 +433,9 @@ impl<'em, T: QueryTy, P: PredicateTy<'em, T>> Query<'em, T, P> {
     fn get_query_sig() -> Signature {
         T::generate_sig()
     }
+    pub fn get_filter() -> QueryFilter {
+        T::generate_filter()
+    }
     pub fn predicate(&self) -> &P {
         &self.predicate
     }
@@ -143,6 +459,13 @@ pub struct QueryFetch<'em, T: QueryTy> {
 
     entman_ref: &'em Entman,
 }
+//QueryFetch is only a handle, so it can be copied regardless of T
+impl<'em, T: QueryTy> Clone for QueryFetch<'em, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<'em, T: QueryTy> Copy for QueryFetch<'em, T> {}
 impl<'a, T: QueryTy> QueryFetch<'a, T> {
     pub fn new(entity_id: Id, entman_ref: &'a Entman) -> Self {
         QueryFetch {
@@ -151,6 +474,35 @@ impl<'a, T: QueryTy> QueryFetch<'a, T> {
             entman_ref,
         }
     }
+    pub fn get_entity_id(&self) -> Id {
+        self.entity_id
+    }
+    ///Returns the typed view of the components of the entity, as in [QueryResult::iter_typed].
+    /// Only the first component of each type is fetched, see [Query].
+    pub fn get(&self) -> Result<T::Item<'a>> {
+        let filter = T::generate_filter();
+        let mut comps = Vec::new();
+        for tid in filter.fetch.get_type_ids() {
+            comps.push(
+                self.entman_ref
+                    .storage
+                    .get_component_dyn_ref(*tid, self.entity_id)?,
+            );
+        }
+        for tid in filter.optional.get_type_ids() {
+            if let Ok(comp) = self
+                .entman_ref
+                .storage
+                .get_component_dyn_ref(*tid, self.entity_id)
+            {
+                comps.push(comp);
+            }
+        }
+        T::fetch(&comps).ok_or(anyhow!(
+            "Entity with id {} does not match the query",
+            self.entity_id
+        ))
+    }
     //Returns component of the given type for the entity the QueryFetch is associated with.
     fn get_component<C: ComponentTyReqs>(&'a self) -> Result<&Component<C>> {
         //check if comp sig exists in signature
@@ -169,8 +521,7 @@ macro_rules! into_tuple {
     };
 
 }
-///A sytem type is one that can execute logic on a given query
-pub struct SystemTy {}
+pub use super::system::SystemTy;
 #[nvproc::query_predicate]
 fn bob_predicate(f: QueryFetch<NameComponent>) -> bool {
     name_component.name == "Bob"
@@ -251,4 +602,204 @@ mod test_query {
         let field = qres3.get_component::<StringFieldComponent>(ent1).unwrap();
         assert_eq!(field.name, "Name");
     }
+    #[test]
+    fn test_query_filters() {
+        let mut entman = Entman::new();
+        let named = entman.add_entity();
+        entman
+            .add_component_default::<NameComponent>(named)
+            .unwrap();
+        let located = entman.add_entity();
+        entman
+            .add_component_default::<NameComponent>(located)
+            .unwrap();
+        entman
+            .add_component_default::<LocationComponent>(located)
+            .unwrap();
+        let field = entman.add_entity();
+        entman
+            .add_component_default::<StringFieldComponent>(field)
+            .unwrap();
+
+        //superset matching
+        let q = Query::<NameComponent>::new();
+        let ids = entman
+            .query(&q)
+            .iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&named) && ids.contains(&located));
+
+        //With requires the component without fetching it
+        let q = Query::<(NameComponent, With<LocationComponent>)>::new();
+        let res = entman.query(&q);
+        let matches = res.iter().collect::<Vec<_>>();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].0, located);
+        assert_eq!(matches[0].1.len(), 1);
+        assert!(res.get_component::<LocationComponent>(located).is_err());
+
+        //Without excludes the component
+        let q = Query::<(NameComponent, Without<LocationComponent>)>::new();
+        let ids = entman
+            .query(&q)
+            .iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![named]);
+
+        //Option fetches the component only if present
+        let q = Query::<(NameComponent, Option<LocationComponent>)>::new();
+        let res = entman.query(&q);
+        assert_eq!(res.iter().count(), 2);
+        assert!(res.get_component::<LocationComponent>(located).is_ok());
+        assert!(res.get_component::<LocationComponent>(named).is_err());
+        assert!(res.get_component::<NameComponent>(named).is_ok());
+
+        let filter = Query::<(NameComponent, Option<LocationComponent>)>::get_filter();
+        assert_eq!(filter.fetch, Signature::from(NameComponent::get_type_id()));
+        assert_eq!(
+            filter.optional,
+            Signature::from(LocationComponent::get_type_id())
+        );
+    }
+    #[test]
+    fn test_iter_typed() {
+        let mut entman = Entman::new();
+        let bob = entman.add_entity();
+        entman
+            .add_component(
+                bob,
+                NameComponent {
+                    name: "Bob".to_string(),
+                    aliases: vec![],
+                },
+            )
+            .unwrap();
+        entman
+            .add_component_default::<LocationComponent>(bob)
+            .unwrap();
+        let jane = entman.add_entity();
+        entman
+            .add_component(
+                jane,
+                NameComponent {
+                    name: "Jane".to_string(),
+                    aliases: vec![],
+                },
+            )
+            .unwrap();
+
+        let q = Query::<(NameComponent, Option<LocationComponent>)>::new();
+        let res = entman.query(&q);
+        let mut names = Vec::new();
+        for (id, (name, loc)) in res.iter_typed() {
+            assert_eq!(loc.is_some(), id == bob);
+            names.push(name.name.clone());
+        }
+        names.sort();
+        assert_eq!(names, vec!["Bob".to_string(), "Jane".to_string()]);
+
+        //typed access inside predicates
+        let pred = |f: QueryFetch<NameComponent>| f.get().unwrap().name == "Jane";
+        let q = Query::from_pred(pred);
+        let mut res = entman.query_mut(&q).unwrap();
+        assert_eq!(res.len(), 1);
+        for (_, name) in res.iter_typed_mut() {
+            name.name = "Janet".to_string();
+        }
+        assert_eq!(
+            entman
+                .get_component_ref::<NameComponent>(jane)
+                .unwrap()
+                .component
+                .name,
+            "Janet"
+        );
+
+        let q = Query::<(NameComponent, LocationComponent)>::new();
+        for (id, (name, _)) in entman.query_mut(&q).unwrap() {
+            assert_eq!(id, bob);
+            name.aliases.push("Robert".to_string());
+        }
+        assert_eq!(
+            entman
+                .get_component_ref::<NameComponent>(bob)
+                .unwrap()
+                .component
+                .aliases,
+            vec!["Robert".to_string()]
+        );
+
+        //a mutable query may not alias a component
+        let q = Query::<(NameComponent, NameComponent)>::new();
+        assert!(entman.query_mut(&q).is_err());
+    }
+    #[test]
+    fn test_predicate_filtering() {
+        let mut entman = Entman::new();
+        let mut add_named = |name: &str, located: bool| {
+            let e = entman.add_entity();
+            entman
+                .add_component(
+                    e,
+                    NameComponent {
+                        name: name.to_string(),
+                        aliases: vec![],
+                    },
+                )
+                .unwrap();
+            if located {
+                entman
+                    .add_component_default::<LocationComponent>(e)
+                    .unwrap();
+            }
+            e
+        };
+        //interleave matching and non-matching entities, across several tables
+        let jane = add_named("Jane", false);
+        let bob = add_named("Bob", false);
+        let carl = add_named("Carl", true);
+        let located_bob = add_named("Bob", true);
+        let names = |res: &QueryResult<NameComponent>| {
+            let mut names = res
+                .iter_typed()
+                .map(|(id, name)| (id, name.name.clone()))
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        let res = entman.query(&Query::from_pred(bob_predicate));
+        assert_eq!(res.len(), 2);
+        for (id, name) in names(&res) {
+            assert!(id == bob || id == located_bob);
+            assert_eq!(name, "Bob");
+        }
+
+        let is_jane = |f: QueryFetch<NameComponent>| f.get().unwrap().name == "Jane";
+        let res = entman.query(&Query::from_pred(bob_predicate.or(is_jane)));
+        assert_eq!(res.len(), 3);
+        assert!(res.get_component::<NameComponent>(carl).is_err());
+        assert_eq!(
+            res.get_component::<NameComponent>(jane).unwrap().name,
+            "Jane"
+        );
+
+        let res = entman.query(&Query::from_pred(bob_predicate.not()));
+        let mut ids = res.iter().map(|(id, _)| id).collect::<Vec<_>>();
+        ids.sort();
+        let mut expected = vec![jane, carl];
+        expected.sort();
+        assert_eq!(ids, expected);
+
+        let is_located = |f: QueryFetch<NameComponent>| {
+            f.entman_ref
+                .get_component_ref::<LocationComponent>(f.get_entity_id())
+                .is_ok()
+        };
+        let res = entman.query(&Query::from_pred(bob_predicate.and(is_located)));
+        assert_eq!(names(&res), vec![(located_bob, "Bob".to_string())]);
+    }
 }
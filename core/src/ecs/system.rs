@@ -0,0 +1,604 @@
+//! Systems run logic over the ECS. A system is any function whose parameters are [SystemParam]s, such as
+//! [QueryResult], [QueryResultMut], `&Project` or `&mut Project`. Systems are registered with a [Schedule],
+//! which runs them in a defined order and checks that their mutable access does not conflict.
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+use super::query::*;
+use super::*;
+use crate::mir::Mir;
+use crate::Project;
+
+pub type SystemId = usize;
+
+///The kind of access a system has to a resource
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EAccess {
+    #[default]
+    None,
+    Read,
+    Write,
+}
+
+///The resources a system reads and writes. Two systems conflict if one of them writes
+/// a resource the other reads or writes.
+#[derive(Debug, Default, Clone)]
+pub struct SystemAccess {
+    reads: HashSet<TypeId>,
+    writes: HashSet<TypeId>,
+    project: EAccess,
+}
+impl SystemAccess {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add_read(&mut self, type_id: TypeId) -> Result<()> {
+        if self.writes.contains(&type_id) {
+            return Err(anyhow!(
+                "Component {} is both read and written by the same system",
+                type_id.get_name_ref()
+            ));
+        }
+        self.reads.insert(type_id);
+        Ok(())
+    }
+    pub fn add_write(&mut self, type_id: TypeId) -> Result<()> {
+        if self.reads.contains(&type_id) || self.writes.contains(&type_id) {
+            return Err(anyhow!(
+                "Component {} is written by the same system more than once, or is also read by it",
+                type_id.get_name_ref()
+            ));
+        }
+        self.writes.insert(type_id);
+        Ok(())
+    }
+    pub fn add_project_access(&mut self, access: EAccess) -> Result<()> {
+        match (self.project, access) {
+            (_, EAccess::None) => {}
+            (EAccess::None, _) | (EAccess::Read, EAccess::Read) => self.project = access,
+            _ => {
+                return Err(anyhow!(
+                    "The project may only be borrowed mutably once by a system"
+                ))
+            }
+        }
+        Ok(())
+    }
+    pub fn get_reads(&self) -> &HashSet<TypeId> {
+        &self.reads
+    }
+    pub fn get_writes(&self) -> &HashSet<TypeId> {
+        &self.writes
+    }
+    pub fn get_project_access(&self) -> EAccess {
+        self.project
+    }
+    ///Returns true if the two accesses may not be reordered relative to each other
+    pub fn conflicts_with(&self, other: &SystemAccess) -> bool {
+        let writes_other = |a: &SystemAccess, b: &SystemAccess| {
+            a.writes
+                .iter()
+                .any(|t| b.reads.contains(t) || b.writes.contains(t))
+        };
+        let project = match (self.project, other.project) {
+            (EAccess::Write, EAccess::None) | (EAccess::None, EAccess::Write) => false,
+            (EAccess::Write, _) | (_, EAccess::Write) => true,
+            _ => false,
+        };
+        project || writes_other(self, other) || writes_other(other, self)
+    }
+}
+
+///The state a system builds from its parameters when it is created
+#[derive(Default)]
+pub struct SystemState {
+    pub access: SystemAccess,
+    //The filter of every query parameter, in parameter order, and whether it is mutable
+    pub requests: Vec<(QueryFilter, bool)>,
+}
+
+///The components fetched for a single query parameter
+pub enum FetchedMatches<'w> {
+    Ref(BTreeMap<Id, Vec<&'w dyn ComponentTy>>),
+    Mut(BTreeMap<Id, Vec<&'w mut dyn ComponentTy>>),
+}
+pub enum ProjectFetch<'w> {
+    Shared(&'w Project),
+    Mut(Option<&'w mut Project>),
+}
+///The resources borrowed from [Mir] for a single run of a system
+pub struct SystemFetch<'w> {
+    project: ProjectFetch<'w>,
+    matches: VecDeque<FetchedMatches<'w>>,
+}
+impl<'w> SystemFetch<'w> {
+    pub fn new(mir: &'w mut Mir<'_>, state: &SystemState) -> Result<Self> {
+        let project = match state.access.get_project_access() {
+            EAccess::Write => ProjectFetch::Mut(Some(&mut mir.proj)),
+            _ => ProjectFetch::Shared(&mir.proj),
+        };
+        let matches = mir.em.storage.get_split_components(&state.requests)?;
+        Ok(SystemFetch {
+            project,
+            matches: matches.into(),
+        })
+    }
+}
+
+///A type that can be used as the parameter of a system
+pub trait SystemParam {
+    type Item<'w>;
+    ///Registers the access of this parameter, failing if it conflicts with the earlier parameters
+    fn init(state: &mut SystemState) -> Result<()>;
+    ///Fetches this parameter from the resources borrowed for the system.
+    /// Parameters are fetched in the same order they were initialized in.
+    fn fetch<'w>(fetch: &mut SystemFetch<'w>) -> Result<Self::Item<'w>>;
+}
+fn fetched_components(filter: &QueryFilter) -> impl Iterator<Item = &TypeId> {
+    filter
+        .fetch
+        .get_type_ids()
+        .iter()
+        .chain(filter.optional.get_type_ids().iter())
+}
+impl<'a, Q: QueryTy> SystemParam for QueryResult<'a, Q> {
+    type Item<'w> = QueryResult<'w, Q>;
+    fn init(state: &mut SystemState) -> Result<()> {
+        let filter = Q::generate_filter();
+        for t in fetched_components(&filter) {
+            state.access.add_read(*t)?;
+        }
+        state.requests.push((filter, false));
+        Ok(())
+    }
+    fn fetch<'w>(fetch: &mut SystemFetch<'w>) -> Result<Self::Item<'w>> {
+        match fetch.matches.pop_front() {
+            Some(FetchedMatches::Ref(matches)) => {
+                let (ids, comps): (Vec<_>, Vec<_>) = matches.into_iter().unzip();
+                Ok(QueryResult::new(ids, comps))
+            }
+            _ => Err(anyhow!("Expected the components of a shared query")),
+        }
+    }
+}
+impl<'a, Q: QueryTy> SystemParam for QueryResultMut<'a, Q> {
+    type Item<'w> = QueryResultMut<'w, Q>;
+    fn init(state: &mut SystemState) -> Result<()> {
+        let filter = Q::generate_filter();
+        for t in fetched_components(&filter) {
+            state.access.add_write(*t)?;
+        }
+        state.requests.push((filter, true));
+        Ok(())
+    }
+    fn fetch<'w>(fetch: &mut SystemFetch<'w>) -> Result<Self::Item<'w>> {
+        match fetch.matches.pop_front() {
+            Some(FetchedMatches::Mut(matches)) => Ok(QueryResultMut::new(matches)),
+            _ => Err(anyhow!("Expected the components of a mutable query")),
+        }
+    }
+}
+impl<'a> SystemParam for &'a Project {
+    type Item<'w> = &'w Project;
+    fn init(state: &mut SystemState) -> Result<()> {
+        state.access.add_project_access(EAccess::Read)
+    }
+    fn fetch<'w>(fetch: &mut SystemFetch<'w>) -> Result<Self::Item<'w>> {
+        match fetch.project {
+            ProjectFetch::Shared(p) => Ok(p),
+            ProjectFetch::Mut(_) => Err(anyhow!("The project is borrowed mutably")),
+        }
+    }
+}
+impl<'a> SystemParam for &'a mut Project {
+    type Item<'w> = &'w mut Project;
+    fn init(state: &mut SystemState) -> Result<()> {
+        state.access.add_project_access(EAccess::Write)
+    }
+    fn fetch<'w>(fetch: &mut SystemFetch<'w>) -> Result<Self::Item<'w>> {
+        match &mut fetch.project {
+            ProjectFetch::Mut(p) => p
+                .take()
+                .ok_or(anyhow!("The project has already been borrowed mutably")),
+            ProjectFetch::Shared(_) => Err(anyhow!("The project is borrowed immutably")),
+        }
+    }
+}
+
+impl Storage {
+    ///Fetches the components of several queries at once. Each component type may be borrowed
+    /// mutably by at most one query, and then by no other query. This is checked by [SystemAccess].
+    /// The components fetched by mutable queries are marked as changed.
+    pub(crate) fn get_split_components(
+        &mut self,
+        requests: &[(QueryFilter, bool)],
+    ) -> Result<Vec<FetchedMatches<'_>>> {
+        for (filter, _) in requests.iter().filter(|(_, is_mut)| *is_mut) {
+            self.mark_fetched_changed(filter, |_| true)?;
+        }
+        let matching = requests
+            .iter()
+            .map(|(filter, _)| self.get_matching_tables(filter))
+            .collect::<Vec<_>>();
+        let mut results = requests
+            .iter()
+            .map(|(_, is_mut)| match is_mut {
+                true => FetchedMatches::Mut(BTreeMap::new()),
+                false => FetchedMatches::Ref(BTreeMap::new()),
+            })
+            .collect::<Vec<_>>();
+        for table in self.tables.iter_mut() {
+            let used = (0..requests.len())
+                .filter(|i| matching[*i].contains(&table.get_id()))
+                .collect::<Vec<_>>();
+            if used.is_empty() {
+                continue;
+            }
+            let entities = table.get_entities().to_vec();
+            let mut written = HashMap::new();
+            let mut read = HashMap::new();
+            for (tid, col) in table.get_columns_mut() {
+                let is_written = used.iter().any(|i| {
+                    let (filter, is_mut) = &requests[*i];
+                    *is_mut && fetched_components(filter).any(|t| t == tid)
+                });
+                if is_written {
+                    written.insert(*tid, col.get_first_components_dyn_mut());
+                } else {
+                    let col: &dyn CommonComponentStoreTy = &**col;
+                    read.insert(*tid, col);
+                }
+            }
+            for i in used {
+                let filter = &requests[i].0;
+                for id in &entities {
+                    match &mut results[i] {
+                        FetchedMatches::Mut(matches) => {
+                            let mut c = Vec::new();
+                            for tid in filter.fetch.get_type_ids() {
+                                c.push(written.get_mut(tid).and_then(|col| col.remove(id)).ok_or(
+                                    anyhow!(
+                                        "Component {} is borrowed by more than one query",
+                                        tid.get_name_ref()
+                                    ),
+                                )?);
+                            }
+                            for tid in filter.optional.get_type_ids() {
+                                if let Some(comp) =
+                                    written.get_mut(tid).and_then(|col| col.remove(id))
+                                {
+                                    c.push(comp);
+                                }
+                            }
+                            matches.insert(*id, c);
+                        }
+                        FetchedMatches::Ref(matches) => {
+                            let mut c = Vec::new();
+                            for tid in filter.fetch.get_type_ids() {
+                                let col = read.get(tid).ok_or(anyhow!(
+                                    "Component {} is borrowed mutably by another query",
+                                    tid.get_name_ref()
+                                ))?;
+                                c.push(col.get_component_dyn_ref(*id)?);
+                            }
+                            for tid in filter.optional.get_type_ids() {
+                                if let Some(comp) = read
+                                    .get(tid)
+                                    .and_then(|col| col.get_component_dyn_ref(*id).ok())
+                                {
+                                    c.push(comp);
+                                }
+                            }
+                            matches.insert(*id, c);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+///A sytem type is one that can execute logic on the ECS and the rest of [Mir]
+pub trait SystemTy: Send + Sync {
+    fn get_name(&self) -> &str;
+    fn get_access(&self) -> &SystemAccess;
+    fn run(&mut self, mir: &mut Mir<'_>) -> Result<()>;
+}
+
+///Converts a function into a system. P is the tuple of the parameters of the function.
+pub trait IntoSystem<P> {
+    fn into_system(self, name: &str) -> Result<Box<dyn SystemTy>>;
+}
+
+///A system built from a function, see [IntoSystem]
+pub struct FunctionSystem<F, P> {
+    name: String,
+    func: F,
+    state: SystemState,
+    marker: PhantomData<fn() -> P>,
+}
+
+macro_rules! impl_function_system {
+    ($($P:ident),*) => {
+        impl<Func, $($P: SystemParam + 'static),*> IntoSystem<($($P,)*)> for Func
+        where
+            Func: Send + Sync + 'static,
+            for<'a> &'a mut Func: FnMut($($P),*) + FnMut($($P::Item<'_>),*),
+        {
+            fn into_system(self, name: &str) -> Result<Box<dyn SystemTy>> {
+                let mut state = SystemState::default();
+                $($P::init(&mut state)?;)*
+                Ok(Box::new(FunctionSystem::<Func, ($($P,)*)> {
+                    name: name.to_string(),
+                    func: self,
+                    state,
+                    marker: PhantomData,
+                }))
+            }
+        }
+        impl<Func, $($P: SystemParam + 'static),*> SystemTy for FunctionSystem<Func, ($($P,)*)>
+        where
+            Func: Send + Sync + 'static,
+            for<'a> &'a mut Func: FnMut($($P),*) + FnMut($($P::Item<'_>),*),
+        {
+            fn get_name(&self) -> &str {
+                &self.name
+            }
+            fn get_access(&self) -> &SystemAccess {
+                &self.state.access
+            }
+            #[allow(non_snake_case)]
+            fn run(&mut self, mir: &mut Mir<'_>) -> Result<()> {
+                let mut fetch = SystemFetch::new(mir, &self.state)?;
+                $(let $P = $P::fetch(&mut fetch)?;)*
+                //calling through a generic function selects the FnMut impl over the fetched items
+                fn call_inner<$($P),*>(mut f: impl FnMut($($P),*), $($P: $P),*) {
+                    f($($P),*)
+                }
+                call_inner(&mut self.func, $($P),*);
+                Ok(())
+            }
+        }
+    };
+}
+impl_function_system!();
+impl_function_system!(P0);
+impl_function_system!(P0, P1);
+impl_function_system!(P0, P1, P2);
+impl_function_system!(P0, P1, P2, P3);
+impl_function_system!(P0, P1, P2, P3, P4);
+impl_function_system!(P0, P1, P2, P3, P4, P5);
+impl_function_system!(P0, P1, P2, P3, P4, P5, P6);
+impl_function_system!(P0, P1, P2, P3, P4, P5, P6, P7);
+
+///A [Schedule] runs a set of systems in a defined order. Systems run in the order they were
+/// added, unless an ordering between them has been given with [Schedule::order].
+#[derive(Default)]
+pub struct Schedule {
+    systems: Vec<Box<dyn SystemTy>>,
+    //(before, after) pairs
+    orderings: Vec<(SystemId, SystemId)>,
+}
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    ///Adds a system to the schedule. Fails if a system with the same name already exists,
+    /// or if the parameters of the system conflict with each other.
+    pub fn add_system<P>(&mut self, name: &str, system: impl IntoSystem<P>) -> Result<SystemId> {
+        let system = system.into_system(name)?;
+        self.add_boxed_system(system)
+    }
+    pub fn add_boxed_system(&mut self, system: Box<dyn SystemTy>) -> Result<SystemId> {
+        if self.get_system_id(system.get_name()).is_some() {
+            return Err(anyhow!(
+                "A system named {} already exists",
+                system.get_name()
+            ));
+        }
+        self.systems.push(system);
+        Ok(self.systems.len() - 1)
+    }
+    pub fn get_system_id(&self, name: &str) -> Option<SystemId> {
+        self.systems.iter().position(|s| s.get_name() == name)
+    }
+    pub fn get_system(&self, id: SystemId) -> Option<&dyn SystemTy> {
+        self.systems.get(id).map(|s| s.as_ref())
+    }
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+    ///Requires the system before to run before the system after
+    pub fn order(&mut self, before: SystemId, after: SystemId) -> Result<()> {
+        if before >= self.systems.len() || after >= self.systems.len() {
+            return Err(anyhow!("System with id {} not found", before.max(after)));
+        }
+        self.orderings.push((before, after));
+        if let Err(e) = self.get_order() {
+            self.orderings.pop();
+            return Err(e);
+        }
+        Ok(())
+    }
+    ///Returns the order the systems run in. Among systems whose order is not constrained,
+    /// the one added first runs first.
+    pub fn get_order(&self) -> Result<Vec<SystemId>> {
+        let mut incoming = vec![0; self.systems.len()];
+        for (_, after) in &self.orderings {
+            incoming[*after] += 1;
+        }
+        let mut ready = (0..self.systems.len())
+            .filter(|s| incoming[*s] == 0)
+            .collect::<BTreeSet<_>>();
+        let mut order = Vec::new();
+        while let Some(next) = ready.pop_first() {
+            order.push(next);
+            for (_, after) in self.orderings.iter().filter(|(b, _)| *b == next) {
+                incoming[*after] -= 1;
+                if incoming[*after] == 0 {
+                    ready.insert(*after);
+                }
+            }
+        }
+        if order.len() != self.systems.len() {
+            return Err(anyhow!("The ordering of the systems contains a cycle"));
+        }
+        Ok(order)
+    }
+    ///Returns every pair of systems whose access conflicts, but whose order relative to
+    /// each other has not been given explicitly.
+    pub fn get_conflicts(&self) -> Vec<(SystemId, SystemId)> {
+        let reachable = (0..self.systems.len())
+            .map(|s| self.get_reachable(s))
+            .collect::<Vec<_>>();
+        let mut conflicts = Vec::new();
+        for a in 0..self.systems.len() {
+            for b in a + 1..self.systems.len() {
+                if self.systems[a]
+                    .get_access()
+                    .conflicts_with(self.systems[b].get_access())
+                    && !reachable[a].contains(&b)
+                    && !reachable[b].contains(&a)
+                {
+                    conflicts.push((a, b));
+                }
+            }
+        }
+        conflicts
+    }
+    //Returns every system that must run after the given system
+    fn get_reachable(&self, system: SystemId) -> HashSet<SystemId> {
+        let mut reached = HashSet::new();
+        let mut stack = vec![system];
+        while let Some(s) = stack.pop() {
+            for (_, after) in self.orderings.iter().filter(|(b, _)| *b == s) {
+                if reached.insert(*after) {
+                    stack.push(*after);
+                }
+            }
+        }
+        reached
+    }
+    ///Runs every system once, in order
+    pub fn run(&mut self, mir: &mut Mir<'_>) -> Result<()> {
+        for id in self.get_order()? {
+            self.systems[id].run(mir)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_system {
+    use super::*;
+    use crate::ecs::component::components::*;
+
+    fn name(s: &str) -> NameComponent {
+        NameComponent {
+            name: s.to_string(),
+            aliases: vec![],
+        }
+    }
+
+    #[test]
+    fn test_run_systems() {
+        let mut mir = Mir::new();
+        let bob = mir.em.add_entity();
+        mir.em.add_component(bob, name("Bob")).unwrap();
+        mir.em
+            .add_component_default::<LocationComponent>(bob)
+            .unwrap();
+        let jane = mir.em.add_entity();
+        mir.em.add_component(jane, name("Jane")).unwrap();
+
+        let mut schedule = Schedule::new();
+        let rename = schedule
+            .add_system(
+                "rename",
+                |mut q: QueryResultMut<(NameComponent, With<LocationComponent>)>,
+                 proj: &Project| {
+                    for (_, (name, _)) in q.iter_typed_mut() {
+                        name.aliases.push(proj.description.clone());
+                    }
+                },
+            )
+            .unwrap();
+        let describe = schedule
+            .add_system(
+                "describe",
+                |q: QueryResult<NameComponent>, proj: &mut Project| {
+                    let mut names = q
+                        .iter_typed()
+                        .map(|(_, n)| n.name.clone())
+                        .collect::<Vec<_>>();
+                    names.sort();
+                    proj.description = names.join(",");
+                },
+            )
+            .unwrap();
+        //describe must run before rename, even though it was added later
+        schedule.order(describe, rename).unwrap();
+        assert_eq!(schedule.get_order().unwrap(), vec![describe, rename]);
+        assert!(schedule.get_conflicts().is_empty());
+        schedule.run(&mut mir).unwrap();
+
+        assert_eq!(mir.proj.description, "Bob,Jane");
+        let bob_name = mir.em.get_component_ref::<NameComponent>(bob).unwrap();
+        assert_eq!(bob_name.component.aliases, vec!["Bob,Jane".to_string()]);
+        let jane_name = mir.em.get_component_ref::<NameComponent>(jane).unwrap();
+        assert!(jane_name.component.aliases.is_empty());
+    }
+    #[test]
+    fn test_system_conflicts() {
+        fn read_names(_: QueryResult<NameComponent>) {}
+        fn write_names(_: QueryResultMut<NameComponent>) {}
+        fn write_locations(_: QueryResultMut<(LocationComponent, Option<NameComponent>)>) {}
+        fn write_project(_: &mut Project) {}
+
+        let mut schedule = Schedule::new();
+        //a system may not borrow the same component both mutably and immutably
+        assert!(schedule
+            .add_system(
+                "aliased",
+                |_: QueryResult<NameComponent>, _: QueryResultMut<NameComponent>| {}
+            )
+            .is_err());
+        assert!(schedule
+            .add_system("project", |_: &Project, _: &mut Project| {})
+            .is_err());
+
+        let read = schedule.add_system("read", read_names).unwrap();
+        let write = schedule.add_system("write", write_names).unwrap();
+        let locations = schedule.add_system("locations", write_locations).unwrap();
+        let project = schedule.add_system("write_project", write_project).unwrap();
+        assert!(schedule.add_system("read", read_names).is_err());
+
+        let mut conflicts = schedule.get_conflicts();
+        conflicts.sort();
+        assert_eq!(
+            conflicts,
+            vec![(read, write), (read, locations), (write, locations)]
+        );
+        assert!(!conflicts
+            .iter()
+            .any(|(a, b)| *a == project || *b == project));
+
+        //explicit ordering resolves conflicts, including transitive orderings
+        schedule.order(read, write).unwrap();
+        schedule.order(write, locations).unwrap();
+        assert!(schedule.get_conflicts().is_empty());
+        assert!(schedule.order(locations, read).is_err());
+
+        let mut mir = Mir::new();
+        let e = mir.em.add_entity();
+        mir.em.add_component(e, name("Bob")).unwrap();
+        mir.em
+            .add_component_default::<LocationComponent>(e)
+            .unwrap();
+        schedule.run(&mut mir).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+//! Archetype tables. Every entity in [Storage](super::Storage) lives in exactly one [ArchetypeTable],
+//! the one whose signature is the set of component types the entity owns. Queries are matched
+//! against tables rather than entities, so the cost of a query scales with the number of matching
+//! entities instead of the total number of entities.
+use super::query::QueryFilter;
+use super::*;
+use std::sync::RwLock;
+
+pub type TableId = usize;
+
+///A table groups together every entity that owns exactly the same set of component types.
+/// Each component type of the table is stored in its own column.
+#[derive(bincode::Encode, bincode::Decode)]
+#[bincode(crate = "common::exports::bincode")]
+pub struct ArchetypeTable {
+    id: TableId,
+    //The canonical signature of this table. See [Signature::get_canonical_signature]
+    signature: Signature,
+    entities: Vec<Id>,
+    //The position of each entity in [entities], for O(1) removal
+    entity_rows: HashMap<Id, usize>,
+    //The columns of this table, hashed by the type id of the component they store
+    columns: HashMap<TypeId, Box<dyn CommonComponentStoreTy>>,
+}
+impl ArchetypeTable {
+    pub fn new(id: TableId, signature: Signature) -> Self {
+        Self {
+            id,
+            signature,
+            entities: Vec::new(),
+            entity_rows: HashMap::new(),
+            columns: HashMap::new(),
+        }
+    }
+    pub fn get_id(&self) -> TableId {
+        self.id
+    }
+    pub fn get_signature_ref(&self) -> &Signature {
+        &self.signature
+    }
+    pub fn get_entities(&self) -> &[Id] {
+        &self.entities
+    }
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+    pub fn contains_entity(&self, entity: Id) -> bool {
+        self.entity_rows.contains_key(&entity)
+    }
+    pub fn has_column(&self, type_id: TypeId) -> bool {
+        self.columns.contains_key(&type_id)
+    }
+    pub fn get_column(&self, type_id: TypeId) -> Option<&dyn CommonComponentStoreTy> {
+        self.columns.get(&type_id).map(|c| c.as_ref())
+    }
+    pub fn get_column_mut(
+        &mut self,
+        type_id: TypeId,
+    ) -> Option<&mut Box<dyn CommonComponentStoreTy>> {
+        self.columns.get_mut(&type_id)
+    }
+    pub fn get_columns(&self) -> impl Iterator<Item = &dyn CommonComponentStoreTy> {
+        self.columns.values().map(|c| c.as_ref())
+    }
+    pub fn get_columns_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (&TypeId, &mut Box<dyn CommonComponentStoreTy>)> {
+        self.columns.iter_mut()
+    }
+    ///Returns the column storing T, creating it if it does not yet exist
+    pub(crate) fn get_or_create_column<T: ComponentTyReqs>(
+        &mut self,
+    ) -> &mut CommonComponentStore<T> {
+        self.columns
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(CommonComponentStore::<T>::new()))
+            .into_store_mut::<T>()
+    }
+    pub(crate) fn insert_column(
+        &mut self,
+        type_id: TypeId,
+        column: Box<dyn CommonComponentStoreTy>,
+    ) {
+        self.columns.entry(type_id).or_insert(column);
+    }
+    pub(crate) fn insert_entity(&mut self, entity: Id) {
+        if !self.entity_rows.contains_key(&entity) {
+            self.entity_rows.insert(entity, self.entities.len());
+            self.entities.push(entity);
+        }
+    }
+    ///Removes the entity from this table, returning all the components it owned.
+    pub(crate) fn take_entity(&mut self, entity: Id) -> Vec<DynamicComponent> {
+        let row = match self.entity_rows.remove(&entity) {
+            Some(row) => row,
+            None => return Vec::new(),
+        };
+        self.entities.swap_remove(row);
+        if let Some(moved) = self.entities.get(row) {
+            self.entity_rows.insert(*moved, row);
+        }
+        let mut comps = Vec::new();
+        for (_, column) in self.columns.iter_mut() {
+            comps.extend(column.take_entity_components(entity));
+        }
+        comps
+    }
+}
+
+///Caches which tables match a given query filter. Because tables are never removed from
+/// [Storage](super::Storage), an entry only ever needs to check the tables created since it was last used.
+#[derive(Default)]
+pub struct QueryCache {
+    entries: RwLock<HashMap<QueryFilter, QueryCacheEntry>>,
+}
+#[derive(Default, Clone)]
+struct QueryCacheEntry {
+    //The number of tables that have been checked against the query
+    checked: usize,
+    matches: Vec<TableId>,
+}
+impl QueryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    ///Returns the ids of every table that matches the given filter.
+    pub fn get_matching_tables(
+        &self,
+        filter: &QueryFilter,
+        tables: &[ArchetypeTable],
+    ) -> Vec<TableId> {
+        let key = filter.get_canonical_filter();
+        if let Some(entry) = self.entries.read().unwrap().get(&key) {
+            if entry.checked == tables.len() {
+                return entry.matches.clone();
+            }
+        }
+        let mut entries = self.entries.write().unwrap();
+        let entry = entries
+            .entry(key.clone())
+            .or_insert_with(QueryCacheEntry::default);
+        for table in &tables[entry.checked..] {
+            if key.matches(&table.signature) {
+                entry.matches.push(table.id);
+            }
+        }
+        entry.checked = tables.len();
+        entry.matches.clone()
+    }
+    pub fn clear(&self) {
+        self.entries.write().unwrap().clear();
+    }
+}
+//The cache is transient, and is simply rebuilt after decoding
+impl bincode::Encode for QueryCache {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        _encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        Ok(())
+    }
+}
+impl bincode::Decode for QueryCache {
+    fn decode<D: bincode::de::Decoder>(
+        _decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(QueryCache::new())
+    }
+}
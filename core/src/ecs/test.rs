@@ -52,7 +52,7 @@ fn test_get_component() {
         .add_component_default::<StringFieldComponent>(entity)
         .unwrap();
     let field_comp = em
-        .get_component_ref::<StringFieldComponent>(entity)
+        .get_component_by_id_ref::<StringFieldComponent>(field_comp_id)
         .unwrap();
     assert_eq!(field_comp.name, String::default());
     assert_eq!(field_comp.value, String::default());
@@ -199,3 +199,610 @@ fn test_generate_component_json() -> Result<()> {
     file.write_all(json.as_bytes())?;
     Ok(())
 }
+#[test]
+fn test_multiple_components_of_same_type() {
+    let mut em = Entman::new();
+    let entity = em.entity_from_archetype(archetypes::CharacterArchetype {});
+    //the character archetype declares Age, Height and Weight
+    let fields = em
+        .get_components_of::<NumericalFieldComponent>(entity)
+        .unwrap();
+    let names = fields.iter().map(|f| f.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["Age", "Height", "Weight"]);
+
+    let extra = em
+        .add_component(
+            entity,
+            NumericalFieldComponent {
+                name: "Strength".to_string(),
+                value: 10.0,
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        em.get_components_of::<NumericalFieldComponent>(entity)
+            .unwrap()
+            .len(),
+        4
+    );
+    let sig = em.get_entity(entity).unwrap().get_signature();
+    assert_eq!(sig.count(NumericalFieldComponent::get_type_id()), 4);
+
+    //remove a single instance
+    let height = em
+        .get_components_of::<NumericalFieldComponent>(entity)
+        .unwrap()[1]
+        .get_id();
+    em.remove_component_by_id(height).unwrap();
+    let names = em
+        .get_components_of::<NumericalFieldComponent>(entity)
+        .unwrap()
+        .iter()
+        .map(|f| f.name.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Age", "Weight", "Strength"]);
+    assert!(em
+        .get_component_by_id_ref::<NumericalFieldComponent>(height)
+        .is_err());
+    em.get_component_by_id_mut::<NumericalFieldComponent>(extra)
+        .unwrap()
+        .get_inner_mut()
+        .value = 12.0;
+    assert_eq!(
+        em.get_component_by_id_ref::<NumericalFieldComponent>(extra)
+            .unwrap()
+            .value,
+        12.0
+    );
+    let sig = em.get_entity(entity).unwrap().get_signature();
+    assert_eq!(sig.count(NumericalFieldComponent::get_type_id()), 3);
+
+    //queries fetch only the first instance of each type
+    let q = Query::<NumericalFieldComponent>::new();
+    let res = em.query(&q);
+    let fetched = res
+        .iter_typed()
+        .map(|(_, f)| f.name.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(fetched, vec!["Age"]);
+
+    //owned entities keep every instance
+    let owned = em.get_entity_owned(entity).unwrap();
+    assert_eq!(
+        owned
+            .components
+            .iter()
+            .filter(|c| c.get_type_id() == NumericalFieldComponent::get_type_id())
+            .count(),
+        3
+    );
+
+    //removing by type removes every instance
+    em.remove_component::<NumericalFieldComponent>(entity)
+        .unwrap();
+    assert!(em
+        .get_components_of::<NumericalFieldComponent>(entity)
+        .unwrap()
+        .is_empty());
+    assert!(!em
+        .get_entity_ref(entity)
+        .unwrap()
+        .has_component::<NumericalFieldComponent>());
+    assert!(em.storage.get_component_info(extra).is_err());
+    assert!(em.storage.get_component_info(height).is_err());
+}
+#[test]
+fn test_archetype_tables() {
+    let mut em = Entman::new();
+    let e1 = em.add_entity();
+    let e2 = em.add_entity();
+    let name_id = em.add_component_default::<NameComponent>(e1).unwrap();
+    em.add_component_default::<NameComponent>(e2).unwrap();
+    //both entities share the same table
+    assert_eq!(
+        em.storage.get_entity_table(e1).unwrap().get_id(),
+        em.storage.get_entity_table(e2).unwrap().get_id()
+    );
+    //adding a new type moves the entity, keeping its component ids
+    em.add_component_default::<StringFieldComponent>(e1)
+        .unwrap();
+    let t1 = em.storage.get_entity_table(e1).unwrap();
+    assert_ne!(
+        t1.get_id(),
+        em.storage.get_entity_table(e2).unwrap().get_id()
+    );
+    assert_eq!(
+        t1.get_signature_ref(),
+        &Signature::from(vec![
+            NameComponent::get_type_id(),
+            StringFieldComponent::get_type_id()
+        ])
+        .get_canonical_signature()
+    );
+    assert_eq!(
+        em.get_component_by_id_ref::<NameComponent>(name_id)
+            .unwrap()
+            .get_id(),
+        name_id
+    );
+    //adding another instance of an existing type does not move the entity
+    let t1 = t1.get_id();
+    let field = em
+        .add_component_default::<StringFieldComponent>(e1)
+        .unwrap();
+    assert_eq!(em.storage.get_entity_table(e1).unwrap().get_id(), t1);
+    //removing one of two instances keeps the entity in its table, removing the last moves it back
+    em.remove_component_by_id(field).unwrap();
+    assert_eq!(em.storage.get_entity_table(e1).unwrap().get_id(), t1);
+    em.remove_component::<StringFieldComponent>(e1).unwrap();
+    assert_eq!(
+        em.storage.get_entity_table(e1).unwrap().get_id(),
+        em.storage.get_entity_table(e2).unwrap().get_id()
+    );
+    //tables survive a round trip through bincode
+    let res = bincode::encode_to_vec(&em, bincode::config::standard()).unwrap();
+    let em2 = bincode::decode_from_slice::<Entman, bincode::config::Configuration>(
+        &*res,
+        bincode::config::standard(),
+    )
+    .unwrap()
+    .0;
+    let q = query::Query::<NameComponent>::new();
+    assert_eq!(em2.query(&q).iter().count(), 2);
+}
+#[test]
+fn test_query_matches_tables() {
+    let mut em = Entman::new();
+    for i in 0..10 {
+        let e = em.add_entity();
+        em.add_component_default::<NameComponent>(e).unwrap();
+        if i % 2 == 0 {
+            em.add_component_default::<LocationComponent>(e).unwrap();
+        }
+    }
+    let q = query::Query::<(LocationComponent, NameComponent)>::new();
+    assert_eq!(em.query(&q).iter().count(), 5);
+    //the cached result must pick up tables created after the first query
+    let e = em.add_entity();
+    em.add_component_default::<LocationComponent>(e).unwrap();
+    em.add_component_default::<NameComponent>(e).unwrap();
+    assert_eq!(em.query(&q).iter().count(), 6);
+    //queries match every table whose signature is a superset of the query
+    let e = em.add_entity();
+    em.add_component_default::<LocationComponent>(e).unwrap();
+    em.add_component_default::<NameComponent>(e).unwrap();
+    em.add_component_default::<StringFieldComponent>(e).unwrap();
+    assert_eq!(em.query(&q).iter().count(), 7);
+    let q = query::Query::<NameComponent>::new();
+    assert_eq!(em.query(&q).iter().count(), 12);
+}
+#[test]
+fn test_change_detection() {
+    let mut em = Entman::new();
+    let e = em.add_entity();
+    let name = em.add_component_default::<NameComponent>(e).unwrap();
+    let loc = em.add_component_default::<LocationComponent>(e).unwrap();
+    let synced = em.get_tick();
+    let ticks = em.get_component_ticks(name).unwrap();
+    assert!(ticks.is_added_since(0));
+    assert!(!ticks.is_changed_since(synced));
+
+    em.get_component_mut::<NameComponent>(e)
+        .unwrap()
+        .component
+        .name = "Bob".to_string();
+    assert!(em
+        .get_component_ticks(name)
+        .unwrap()
+        .is_changed_since(synced));
+    assert!(!em.get_component_ticks(name).unwrap().is_added_since(synced));
+    assert!(!em
+        .get_component_ticks(loc)
+        .unwrap()
+        .is_changed_since(synced));
+    em.remove_component::<LocationComponent>(e).unwrap();
+    em.remove_entity(e).unwrap();
+
+    let events = em.get_events_since(synced).cloned().collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![
+            EEntityEvent::ComponentChanged {
+                entity: e,
+                component: name,
+                type_id: NameComponent::get_type_id(),
+            },
+            EEntityEvent::ComponentRemoved {
+                entity: e,
+                component: loc,
+                type_id: LocationComponent::get_type_id(),
+            },
+            EEntityEvent::EntityRemoved(e),
+        ]
+    );
+    let drained = em.drain_events();
+    assert_eq!(drained.len(), 6);
+    assert_eq!(drained[0].1, EEntityEvent::EntityCreated(e));
+    assert_eq!(
+        drained[1].1,
+        EEntityEvent::ComponentAdded {
+            entity: e,
+            component: name,
+            type_id: NameComponent::get_type_id(),
+        }
+    );
+    assert_eq!(em.get_events_since(0).count(), 0);
+    //ticks keep increasing after draining
+    let tick = em.get_tick();
+    em.add_entity();
+    assert!(em.get_tick() > tick);
+}
+#[test]
+fn test_entity_handles() {
+    let mut em = Entman::new();
+    let e = em.add_entity();
+    let name = em
+        .add_component(
+            e,
+            NameComponent {
+                name: "Bob".to_string(),
+                aliases: vec![],
+            },
+        )
+        .unwrap();
+    let handle = em.get_handle(e).unwrap();
+    assert_eq!(em.get_handle_status(handle), EHandleStatus::Alive);
+    assert_eq!(em.check_handle(handle).unwrap(), e);
+
+    em.remove_entity(e).unwrap();
+    assert_eq!(em.get_handle_status(handle), EHandleStatus::Removed);
+    assert_eq!(em.get_entity_count(), 0);
+    assert!(em.is_removed(e));
+    let err = em.get_component_ref::<NameComponent>(e).err().unwrap();
+    assert_eq!(
+        err.downcast_ref::<EEntityError>(),
+        Some(&EEntityError::Removed(e))
+    );
+    assert!(em.remove_entity(e).is_err());
+
+    //restoring keeps the components and their ids
+    em.restore_entity(e).unwrap();
+    assert_eq!(em.get_handle_status(handle), EHandleStatus::Alive);
+    assert_eq!(
+        em.get_component_ref::<NameComponent>(e).unwrap().get_id(),
+        name
+    );
+    assert_eq!(em.get_all_living_entities(), vec![e]);
+
+    let owned = em.get_entity_owned(e).unwrap();
+    em.purge_entity(e).unwrap();
+    assert_eq!(em.get_handle_status(handle), EHandleStatus::Stale);
+    let err = em.check_handle(handle).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<EEntityError>(),
+        Some(&EEntityError::Stale(handle))
+    );
+    assert!(em.restore_entity(e).is_err());
+
+    //bringing the id back advances the generation, so the old handle stays stale
+    em.entity_from_owned(owned).unwrap();
+    assert_eq!(em.get_handle_status(handle), EHandleStatus::Stale);
+    let new_handle = em.get_handle(e).unwrap();
+    assert_eq!(new_handle.get_generation(), handle.get_generation() + 1);
+    assert_eq!(em.get_handle_status(new_handle), EHandleStatus::Alive);
+
+    let unknown = EntityHandle::new(uuid::gen_128(), 0);
+    assert_eq!(em.get_handle_status(unknown), EHandleStatus::NeverExisted);
+    let err = em.get_entity_clone(unknown.get_id()).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<EEntityError>(),
+        Some(&EEntityError::NeverExisted(unknown.get_id()))
+    );
+}
+#[test]
+fn test_entity_hierarchy() {
+    let mut em = Entman::new();
+    let planet = em.add_entity();
+    let continent = em.add_entity();
+    let city = em.add_entity();
+    let tavern = em.add_entity();
+    let sword = em.add_entity();
+    for e in [planet, continent, city, tavern] {
+        em.add_component_default::<LocationComponent>(e).unwrap();
+    }
+    em.set_parent(continent, planet).unwrap();
+    em.set_parent(city, continent).unwrap();
+    em.set_parent(tavern, city).unwrap();
+    em.set_parent(sword, tavern).unwrap();
+
+    assert_eq!(em.get_parent(city).unwrap(), Some(continent));
+    assert_eq!(em.get_children(continent).unwrap(), vec![city]);
+    assert_eq!(
+        em.get_ancestors(tavern).unwrap(),
+        vec![city, continent, planet]
+    );
+    assert_eq!(em.get_root(sword).unwrap(), planet);
+    assert_eq!(
+        em.get_descendants(planet).unwrap(),
+        vec![continent, city, tavern, sword]
+    );
+    assert_eq!(
+        em.get_descendants_with::<LocationComponent>(continent)
+            .unwrap(),
+        vec![city, tavern]
+    );
+    //cycles are rejected
+    assert!(em.set_parent(planet, tavern).is_err());
+    assert!(em.set_parent(city, city).is_err());
+    assert_eq!(em.get_parent(planet).unwrap(), None);
+
+    //reparenting detaches the entity from its previous parent
+    em.set_parent(sword, city).unwrap();
+    assert_eq!(em.get_children(city).unwrap(), vec![tavern, sword]);
+    assert!(em.get_children(tavern).unwrap().is_empty());
+
+    let tree = em.get_subtree_owned(continent).unwrap();
+    assert_eq!(tree.len(), 4);
+    let removed = em.remove_entity_recursive(continent).unwrap();
+    assert_eq!(removed, vec![continent, city, tavern, sword]);
+    assert_eq!(em.get_entity_count(), 1);
+    assert!(em.get_children(planet).unwrap().is_empty());
+
+    //restoring the removed entities rebuilds the subtree
+    for e in removed.iter() {
+        em.restore_entity(*e).unwrap();
+    }
+    assert_eq!(
+        em.get_descendants(planet).unwrap(),
+        vec![continent, city, tavern, sword]
+    );
+
+    //a purged subtree can be brought back from its owned copy
+    em.remove_entity_recursive(continent).unwrap();
+    em.purge_removed_entities().unwrap();
+    em.entity_tree_from_owned(tree).unwrap();
+    assert_eq!(em.get_parent(continent).unwrap(), Some(planet));
+    assert_eq!(
+        em.get_descendants(planet).unwrap(),
+        vec![continent, city, tavern, sword]
+    );
+}
+#[test]
+fn test_clone_entity_and_prefabs() {
+    let mut em = Entman::new();
+    let name = |n: &str| NameComponent {
+        name: n.to_string(),
+        aliases: vec![],
+    };
+    let field = |v: &str| StringFieldComponent {
+        name: "rank".to_string(),
+        value: v.to_string(),
+    };
+    let town = em.add_entity();
+    let guard = em.add_entity();
+    em.set_parent(guard, town).unwrap();
+    let name_id = em.add_component(guard, name("Guard")).unwrap();
+    em.add_component(guard, field("Private")).unwrap();
+
+    //clones get new component ids, and the same parent
+    let clone = em.clone_entity(guard).unwrap();
+    let cloned_name = em.get_component_ref::<NameComponent>(clone).unwrap();
+    assert_ne!(cloned_name.get_id(), name_id);
+    assert_eq!(cloned_name.component.name, "Guard");
+    assert_eq!(em.get_children(town).unwrap(), vec![guard, clone]);
+    assert_eq!(em.get_prefab(clone).unwrap(), None);
+
+    let first = em.instantiate_prefab(guard).unwrap();
+    let second = em.instantiate_prefab(guard).unwrap();
+    assert_eq!(em.get_prefab(first).unwrap(), Some(guard));
+    let mut instances = em.get_prefab_instances(guard);
+    instances.sort();
+    let mut expected = vec![first, second];
+    expected.sort();
+    assert_eq!(instances, expected);
+    assert!(em.get_prefab_overrides(first).unwrap().is_empty());
+
+    //instances inherit the prefab values until overridden
+    em.get_component_mut::<NameComponent>(first)
+        .unwrap()
+        .component
+        .name = "Captain".to_string();
+    em.get_component_mut::<NameComponent>(guard)
+        .unwrap()
+        .component
+        .name = "Town Guard".to_string();
+    em.get_component_mut::<StringFieldComponent>(guard)
+        .unwrap()
+        .component
+        .value = "Corporal".to_string();
+    em.sync_prefab_instances(guard).unwrap();
+    let get_name = |em: &Entman, e| {
+        em.get_component_ref::<NameComponent>(e)
+            .unwrap()
+            .component
+            .name
+            .clone()
+    };
+    let get_rank = |em: &Entman, e| {
+        em.get_component_ref::<StringFieldComponent>(e)
+            .unwrap()
+            .component
+            .value
+            .clone()
+    };
+    assert_eq!(get_name(&em, first), "Captain");
+    assert_eq!(get_rank(&em, first), "Corporal");
+    assert_eq!(get_name(&em, second), "Town Guard");
+    assert_eq!(
+        em.get_prefab_overrides(first).unwrap(),
+        vec![NameComponent::get_type_id()]
+    );
+
+    //applying the overrides passes them on to every instance
+    em.apply_overrides_to_prefab(first).unwrap();
+    assert_eq!(get_name(&em, guard), "Captain");
+    assert_eq!(get_name(&em, second), "Captain");
+    assert!(em.get_prefab_overrides(first).unwrap().is_empty());
+
+    //reverting discards the local changes, including components the prefab does not have
+    em.get_component_mut::<StringFieldComponent>(second)
+        .unwrap()
+        .component
+        .value = "General".to_string();
+    em.add_component_default::<LocationComponent>(second)
+        .unwrap();
+    em.revert_component_to_prefab(second, StringFieldComponent::get_type_id())
+        .unwrap();
+    assert_eq!(get_rank(&em, second), "Corporal");
+    assert!(em.get_component_ref::<LocationComponent>(second).is_ok());
+    em.revert_to_prefab(second).unwrap();
+    assert!(em.get_component_ref::<LocationComponent>(second).is_err());
+    assert_eq!(get_name(&em, second), "Captain");
+}
+#[test]
+fn test_lifespans() {
+    use crate::khronos::{Coef, Cycle, DateTime, ELeapCondition, System, UnitCycle};
+    let month = Cycle::new(1, "Month", "", Coef::Inverted(12), None, 0)
+        .unwrap()
+        .with_div_lengths(vec![31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31])
+        .unwrap()
+        .with_leap_rule(2, 1, ELeapCondition::gregorian())
+        .unwrap();
+    let day = Cycle::new(2, "Day", "", Coef::Inverted(31), None, 1).unwrap();
+    let unit = UnitCycle {
+        name: String::from("Year"),
+        description: String::new(),
+    };
+    let system = System::from_cycles(unit, vec![month, day]).unwrap();
+    let date = |d: &str| DateTime::from_cycle_string(d, &system).unwrap();
+
+    let mut em = Entman::new();
+    let elder = em.add_entity();
+    let child = em.add_entity();
+    let stranger = em.add_entity();
+    em.set_birth(elder, Some(date("2000:3:1")), &system)
+        .unwrap();
+    em.set_death(elder, Some(date("2060:7:15")), &system)
+        .unwrap();
+    em.set_birth(child, Some(date("2030:2:14")), &system)
+        .unwrap();
+    assert_eq!(em.get_birth(child).unwrap(), Some(date("2030:2:14")));
+    assert_eq!(
+        em.get_lifespan_calendar(child).unwrap(),
+        Some(system.get_id())
+    );
+    assert_eq!(em.get_death(child).unwrap(), None);
+    assert_eq!(em.get_birth(stranger).unwrap(), None);
+
+    //ages are counted in birthdays, even across leap years
+    assert_eq!(
+        em.age_at(elder, &system, &date("2030:2:28"), 0).unwrap(),
+        29
+    );
+    assert_eq!(em.age_at(elder, &system, &date("2030:3:1"), 0).unwrap(), 30);
+    assert_eq!(em.age_at(elder, &system, &date("2000:3:31"), 1).unwrap(), 0);
+    assert_eq!(em.age_at(elder, &system, &date("2000:4:1"), 1).unwrap(), 1);
+    assert_eq!(em.age_at(child, &system, &date("2030:2:20"), 2).unwrap(), 6);
+    //the dead stop ageing
+    assert_eq!(em.age_at(elder, &system, &date("2100:1:1"), 0).unwrap(), 60);
+    assert!(em.age_at(child, &system, &date("2029:1:1"), 0).is_err());
+    assert!(em.age_at(stranger, &system, &date("2029:1:1"), 0).is_err());
+
+    assert_eq!(
+        em.get_alive_at(&system, &date("2010")).unwrap(),
+        vec![elder]
+    );
+    let mut alive = em.get_alive_at(&system, &date("2040:1:1")).unwrap();
+    alive.sort();
+    let mut both = vec![elder, child];
+    both.sort();
+    assert_eq!(alive, both);
+    assert_eq!(
+        em.get_alive_at(&system, &date("2060:7:15")).unwrap(),
+        vec![child]
+    );
+    assert!(!em.is_alive_at(stranger, &system, &date("2040")).unwrap());
+
+    //death may not come before birth, whichever is set last
+    assert!(em
+        .set_death(child, Some(date("2029:12:31")), &system)
+        .is_err());
+    assert!(em
+        .set_birth(elder, Some(date("2060:7:16")), &system)
+        .is_err());
+    assert_eq!(em.get_birth(elder).unwrap(), Some(date("2000:3:1")));
+    em.set_death(child, Some(date("2030:2:14")), &system)
+        .unwrap();
+    //dates are only read in the calendar they were set with
+    let other = System::from_cycles(
+        UnitCycle {
+            name: String::from("Year"),
+            description: String::new(),
+        },
+        vec![],
+    )
+    .unwrap();
+    assert!(em.age_at(elder, &other, &date("2030"), 0).is_err());
+    assert!(em.set_death(elder, None, &other).is_err());
+    em.set_birth(stranger, Some(date("2030")), &other).unwrap();
+}
+#[test]
+fn test_event_entities() {
+    let mut em = Entman::new();
+    let hero = em.add_entity();
+    let villain = em.add_entity();
+    let guard = em.add_entity();
+    let castle = em.add_entity();
+    let siege = em
+        .add_event(EventComponent::new("Siege", "The castle falls").with_location(castle))
+        .unwrap();
+    em.add_participant(siege, hero, EEventRole::Protagonist)
+        .unwrap();
+    em.add_participant(siege, villain, EEventRole::Antagonist)
+        .unwrap();
+    em.add_participant(siege, guard, EEventRole::Witness)
+        .unwrap();
+    //the guard does not survive the siege
+    em.add_participant(siege, guard, EEventRole::Victim)
+        .unwrap();
+    let duel = em.add_event(EventComponent::new("Duel", "")).unwrap();
+    em.add_participant(duel, hero, EEventRole::Protagonist)
+        .unwrap();
+    em.add_participant(duel, villain, EEventRole::Other(String::from("Challenger")))
+        .unwrap();
+
+    assert_eq!(em.get_events_involving(hero).unwrap(), vec![siege, duel]);
+    assert_eq!(em.get_events_involving(guard).unwrap(), vec![siege]);
+    assert_eq!(
+        em.get_events_with_role(guard, &EEventRole::Victim).unwrap(),
+        vec![siege]
+    );
+    assert!(em
+        .get_events_with_role(guard, &EEventRole::Witness)
+        .unwrap()
+        .is_empty());
+    assert_eq!(em.get_participants(siege).unwrap().len(), 3);
+    assert_eq!(em.get_events_at_location(castle), vec![siege]);
+    assert!(em
+        .add_participant(hero, villain, EEventRole::Witness)
+        .is_err());
+
+    em.remove_participant(duel, villain).unwrap();
+    assert_eq!(em.get_events_involving(villain).unwrap(), vec![siege]);
+
+    //removed events are skipped, and purged ones forgotten by their participants
+    em.remove_entity(duel).unwrap();
+    assert_eq!(em.get_events_involving(hero).unwrap(), vec![siege]);
+    em.restore_entity(duel).unwrap();
+    assert_eq!(em.get_events_involving(hero).unwrap(), vec![siege, duel]);
+    em.purge_entity(duel).unwrap();
+    assert_eq!(
+        em.get_component_ref::<EventParticipationComponent>(hero)
+            .unwrap()
+            .component
+            .get_events(),
+        &[siege]
+    );
+    em.purge_entity(guard).unwrap();
+    assert_eq!(em.get_event(siege).unwrap().get_participants().len(), 2);
+}
@@ -1,6 +1,13 @@
+use std::cmp::Ordering;
 use std::fmt;
 
+use crate::ecs::Id;
 use common::exports::anyhow::{anyhow, Result};
+use common::exports::*;
+use common::uuid;
+use serde::{Deserialize, Serialize};
+
+pub mod format;
 
 ///The real world time system.
 pub struct NativeSystem(chrono::DateTime<chrono::Utc>);
@@ -8,13 +15,62 @@ pub struct NativeSystem(chrono::DateTime<chrono::Utc>);
 ///A general time trait  that conforms to the TimeSystem interface
 pub trait TimeSystemTy {}
 pub type CycleId = u32;
+pub type EpochId = u32;
+//A division of a cycle within one instance of its parent, with its length in the cycle that divides it
+struct Division {
+    number: u32,
+    length: u32,
+    intercalary: bool,
+}
+//The measures of a date, with the unit cycle counted forwards from the start of its epoch
+struct Moments {
+    chain: Vec<Cycle>,
+    values: Vec<f64>,
+    delta: f64,
+    backwards: bool,
+    epoch: EpochId,
+}
+impl Moments {
+    fn to_date(&self) -> DateTime {
+        let mut measures = self
+            .chain
+            .iter()
+            .zip(self.values.iter())
+            .map(|(cycle, moment)| CycleMeasure {
+                cycle: cycle.clone(),
+                moment: *moment,
+            })
+            .collect::<Vec<_>>();
+        if self.backwards {
+            measures[0].moment = -measures[0].moment;
+        }
+        DateTime {
+            measures,
+            epoch: self.epoch,
+        }
+    }
+}
+//Where to continue stepping through the divisions of a parent
+enum EStepFrom {
+    Current,
+    BeforeFirst,
+    AfterLast,
+}
+//The tolerance used when comparing coefficients
+const EPSILON: f64 = 1e-9;
+//Returns the tolerance used when comparing a number of unit cycles, which loses precision as it grows
+pub(crate) fn get_tolerance(units: f64) -> f64 {
+    (units.abs() + 1.0) * 1e-14
+}
 ///A [Cycle] is implictely defined in terms of a Unit Cycle, which is the base unit of time
 /// for a given system
 
 ///Represents a coefficient of another Cycle. Normal is a non fractional multiple of
 /// of the cycle, inverted is a fractional multiple of the cycle.
 ///
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Coef {
     Inverted(u32),
     Normal(u32),
@@ -32,18 +88,87 @@ impl From<u32> for Coef {
         Coef::Normal(coef)
     }
 }
-impl From<f32> for Coef {
-    fn from(coef: f32) -> Self {
-        if coef < 1.0 {
-            Coef::Inverted((1.0 / (coef as f32)) as u32)
-        } else {
-            Coef::Normal((coef) as u32)
+///Only whole multiples and whole fractions (1/n) of a cycle can be represented
+impl TryFrom<f64> for Coef {
+    type Error = anyhow::Error;
+    fn try_from(coef: f64) -> Result<Self> {
+        if !coef.is_finite() || coef <= 0.0 {
+            return Err(anyhow!("{} is not a valid coefficient", coef));
+        }
+        let (coef, inverse) = match coef < 1.0 {
+            true => (Coef::Inverted((1.0 / coef).round() as u32), 1.0 / coef),
+            false => (Coef::Normal(coef.round() as u32), coef),
+        };
+        if (inverse - coef.mag() as f64).abs() > EPSILON {
+            return Err(anyhow!(
+                "{} is neither a whole multiple nor a whole fraction of a cycle",
+                inverse
+            ));
         }
+        Ok(coef)
     }
 }
 //impl comparison
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+///A predicate on the index of a parent cycle (e.g. the number of the year), deciding whether a [LeapRule] applies
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ELeapCondition {
+    ///Holds for every index that is a multiple of the first value, once shifted by the second
+    Every(i64, i64),
+    All(Vec<ELeapCondition>),
+    Any(Vec<ELeapCondition>),
+    Not(Box<ELeapCondition>),
+}
+impl ELeapCondition {
+    ///Every 4th year, except every 100th year that is not a 400th year
+    pub fn gregorian() -> Self {
+        ELeapCondition::All(vec![
+            ELeapCondition::Every(4, 0),
+            ELeapCondition::Any(vec![
+                ELeapCondition::Not(Box::new(ELeapCondition::Every(100, 0))),
+                ELeapCondition::Every(400, 0),
+            ]),
+        ])
+    }
+    pub fn holds(&self, index: i64) -> bool {
+        match self {
+            ELeapCondition::Every(0, _) => false,
+            ELeapCondition::Every(every, offset) => (index - offset).rem_euclid(*every) == 0,
+            ELeapCondition::All(conditions) => conditions.iter().all(|c| c.holds(index)),
+            ELeapCondition::Any(conditions) => conditions.iter().any(|c| c.holds(index)),
+            ELeapCondition::Not(condition) => !condition.holds(index),
+        }
+    }
+}
+///Lengthens one division of a cycle by [extra] of the cycle that divides it, whenever the condition holds
+/// for the index of the parent (e.g. February gains a day in leap years)
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LeapRule {
+    ///The number of the division, counted from 1. Intercalary periods are numbered after the regular divisions.
+    pub division: u32,
+    pub extra: u32,
+    pub condition: ELeapCondition,
+}
+///A stretch of time that belongs to none of the divisions of a cycle, such as the festival days between the last
+/// month and the new year. An intercalary period with a length of 0 only exists when a [LeapRule] lengthens it.
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Intercalary {
+    pub name: String,
+    ///The regular division it follows, 0 placing it before the first one
+    pub after: u32,
+    ///The length in the cycle that divides this one
+    pub length: u32,
+}
+
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Cycle {
     id: CycleId,
     pub name: String,
@@ -53,13 +178,24 @@ pub struct Cycle {
     pub div_names: Option<Vec<String>>,
     ///The cycle this cycle is relative to
     pub parent: CycleId,
+    ///The length of each division in the cycle that divides this one (e.g. the number of days in each month).
+    /// The coef of the dividing cycle is then ignored.
+    pub div_lengths: Option<Vec<u32>>,
+    pub leap_rules: Vec<LeapRule>,
+    pub intercalary: Vec<Intercalary>,
 }
 //The unit cycle is the base unit of time for a given system, it is arbitraily defined as one ("unity")
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, PartialEq)]
 pub struct UnitCycle {
     pub name: String,
     pub description: String,
 }
 impl Cycle {
+    pub fn get_id(&self) -> CycleId {
+        self.id
+    }
     pub fn new(
         id: CycleId,
         name: &str,
@@ -83,74 +219,265 @@ impl Cycle {
             coef,
             div_names,
             parent,
+            div_lengths: None,
+            leap_rules: Vec::new(),
+            intercalary: Vec::new(),
         })
     }
+    ///Sets the length of each division, which must line up with the div names
+    pub fn with_div_lengths(mut self, div_lengths: Vec<u32>) -> Result<Self> {
+        if (div_lengths.len() as u32) != self.coef.mag() {
+            return Err(anyhow!(
+                "The number of div lengths must match the number of coefs"
+            ));
+        }
+        self.div_lengths = Some(div_lengths);
+        Ok(self)
+    }
+    ///Adds an intercalary period, numbered after the regular divisions and any earlier intercalary period
+    pub fn with_intercalary(mut self, name: &str, after: u32, length: u32) -> Result<Self> {
+        if after > self.coef.mag() {
+            return Err(anyhow!(
+                "{} cannot follow division {}, as {} only has {}",
+                name,
+                after,
+                self.name,
+                self.coef.mag()
+            ));
+        }
+        self.intercalary.push(Intercalary {
+            name: String::from(name),
+            after,
+            length,
+        });
+        Ok(self)
+    }
+    pub fn with_leap_rule(
+        mut self,
+        division: u32,
+        extra: u32,
+        condition: ELeapCondition,
+    ) -> Result<Self> {
+        if division == 0 || division > self.coef.mag() + self.intercalary.len() as u32 {
+            return Err(anyhow!("{} has no division {}", self.name, division));
+        }
+        self.leap_rules.push(LeapRule {
+            division,
+            extra,
+            condition,
+        });
+        Ok(self)
+    }
+    //Checks that the names, lengths, intercalary periods and leap rules of the divisions line up with the coef,
+    // since they may have been edited after the cycle was built
+    fn verify_divisions(&self) -> Result<()> {
+        let mag = self.coef.mag();
+        if self
+            .div_names
+            .as_ref()
+            .map_or(false, |n| n.len() as u32 != mag)
+        {
+            return Err(anyhow!(
+                "{} has {} divisions, but not as many names",
+                self.name,
+                mag
+            ));
+        }
+        if self
+            .div_lengths
+            .as_ref()
+            .map_or(false, |l| l.len() as u32 != mag)
+        {
+            return Err(anyhow!(
+                "{} has {} divisions, but not as many lengths",
+                self.name,
+                mag
+            ));
+        }
+        if let Some(period) = self.intercalary.iter().find(|i| i.after > mag) {
+            return Err(anyhow!(
+                "{} cannot follow division {}, as {} only has {}",
+                period.name,
+                period.after,
+                self.name,
+                mag
+            ));
+        }
+        let divisions = mag + self.intercalary.len() as u32;
+        if let Some(rule) = self
+            .leap_rules
+            .iter()
+            .find(|r| r.division == 0 || r.division > divisions)
+        {
+            return Err(anyhow!("{} has no division {}", self.name, rule.division));
+        }
+        Ok(())
+    }
+    ///Whether the divisions of the cycle differ in length, or are interrupted by intercalary periods
+    pub fn is_irregular(&self) -> bool {
+        self.div_lengths.is_some() || !self.intercalary.is_empty() || !self.leap_rules.is_empty()
+    }
+    ///Returns the name of a division, counted from 1, including the intercalary periods numbered after the regular ones
+    pub fn get_division_name(&self, division: u32) -> Option<&str> {
+        let mag = self.coef.mag();
+        match division {
+            0 => None,
+            d if d <= mag => self
+                .div_names
+                .as_ref()
+                .and_then(|names| names.get((d - 1) as usize))
+                .map(|name| name.as_str()),
+            d => self
+                .intercalary
+                .get((d - mag - 1) as usize)
+                .map(|i| i.name.as_str()),
+        }
+    }
 }
 
 /// A [CycleMeasure] is a point in time represented as a multiple of a given [Cycle]
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, PartialEq)]
 pub struct CycleMeasure {
     pub cycle: Cycle,
     pub moment: f64,
 }
 ///Represents a single moment in time
+///
+/// Dates have no order of their own, since two dates may measure different cycles, from different epochs, some
+/// of which count backwards. Use [System::compare] or [System::sort_dates] to order them.
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, PartialEq)]
 pub struct DateTime {
     pub measures: Vec<CycleMeasure>,
+    ///The [Epoch] the unit cycle measure counts from, 0 being the universal epoch
+    pub epoch: EpochId,
 }
 impl DateTime {
     pub fn new(measures: Vec<CycleMeasure>) -> Self {
-        DateTime { measures }
+        DateTime { measures, epoch: 0 }
+    }
+    pub fn with_epoch(mut self, epoch: EpochId) -> Self {
+        self.epoch = epoch;
+        self
     }
     ///Returns a data time from a string of the form "CYCLEMEASURE1:CYCLEMEASURE2:CYCLEMEASURE3:..."
     /// Where a CYCLEMEASURE is just a number representing the coefficent of the measure
     /// So, if you had a system with a year, month, week and day,and wanted to calculate the first day of the third week of the second month and of 2020th year,
     /// Where a year is the unit cycle
     ///  you would use the string "2020:2:3:1"
-    pub fn from_cycle_string(cycle_string: &str, system: &System) -> Self {
-        let mut measures: Vec<CycleMeasure> = Vec::new();
-        let mut cycle_string_iter = cycle_string.split(":").collect::<Vec<&str>>();
-        //make sure the lengths are the same
-        if cycle_string_iter.len() != system.cycles.len() + 1 {
-            panic!(
-                "The number of cycles in the string must match the number of cycles in the system"
-            );
+    pub fn from_cycle_string(cycle_string: &str, system: &System) -> Result<Self> {
+        let date_cycles = system.get_date_cycles()?;
+        let moments = cycle_string
+            .split(':')
+            .map(|m| {
+                m.trim()
+                    .parse::<f64>()
+                    .map_err(|e| anyhow!("Invalid measure '{}': {}", m, e))
+            })
+            .collect::<Result<Vec<f64>>>()?;
+        if moments.len() > date_cycles.len() {
+            return Err(anyhow!(
+                "The string has {} measures, but the system only has {} cycles",
+                moments.len(),
+                date_cycles.len()
+            ));
         }
-
-        let cycle_iter = system.cycles.iter();
-        //zip together
-        for (cycle, coef) in cycle_iter.zip(cycle_string_iter) {
-            let coef = coef.parse::<f32>().unwrap();
-            let coef = Coef::from(coef);
-            let measure = CycleMeasure {
-                cycle: cycle.clone(),
-                moment: coef.mag() as f64,
-            };
-            measures.push(measure);
-        }
-
-        DateTime { measures }
+        let date = DateTime::new(
+            date_cycles
+                .into_iter()
+                .zip(moments)
+                .map(|(cycle, moment)| CycleMeasure { cycle, moment })
+                .collect(),
+        );
+        system.verify_date(&date)?;
+        Ok(date)
     }
 }
-//implement Display
+///Writes the date in the form read by [DateTime::from_cycle_string]. [format::DateFormat] writes it the way a world would.
 impl fmt::Display for DateTime {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut string = String::new();
-        for measure in &self.measures {
-            string.push_str(&format!("{}:{}", measure.cycle.name, measure.moment));
-        }
-        write!(f, "{}", string)
+        let moments = self
+            .measures
+            .iter()
+            .map(|m| m.moment.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}", moments.join(":"))
     }
 }
 ///An [Epoch] is a starting point for a long stretch of time (e.g. B.C.E, A.D. etc)
 /// All [Epoch]s are implicitly defined in terms universal 0 Epoch
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, PartialEq)]
 pub struct Epoch {
+    id: EpochId,
     pub name: String,
+    ///A short form of the name, such as "A.D."
+    pub abbreviation: String,
     //The number of Unit Cycles from the universal 0 Epoch
     pub delta_from_zero: f64,
+    ///Whether the unit cycles count backwards from the start of the epoch, as the years B.C.E do
+    pub backwards: bool,
+}
+impl Epoch {
+    pub fn new(id: EpochId, name: &str, abbreviation: &str, delta_from_zero: f64) -> Self {
+        Epoch {
+            id,
+            name: String::from(name),
+            abbreviation: String::from(abbreviation),
+            delta_from_zero,
+            backwards: false,
+        }
+    }
+    pub fn counting_backwards(mut self) -> Self {
+        self.backwards = true;
+        self
+    }
+    pub fn get_id(&self) -> EpochId {
+        self.id
+    }
+}
+///Places a [System] on an absolute timeline shared with other systems. The timeline is measured in a cycle
+/// the systems have in common, such as a day, and dates are converted from one system to another through it.
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, PartialEq)]
+pub struct Anchor {
+    ///The cycle of the system the absolute timeline is counted in. Every one of them is counted exactly,
+    /// leap days included.
+    pub cycle: CycleId,
+    ///The length of that cycle on the absolute timeline
+    pub scale: f64,
+    ///The absolute moment the universal 0 Epoch of the system starts at
+    pub offset: f64,
+}
+impl Anchor {
+    pub fn new(cycle: CycleId, scale: f64, offset: f64) -> Self {
+        Anchor {
+            cycle,
+            scale,
+            offset,
+        }
+    }
 }
 ///This is an attempt at a general time system.
+///
+/// A date is measured in the unit cycle, followed by the chain of cycles that each divide the previous one
+/// (e.g. Year:Month:Day:Hour). The unit cycle is counted from its [Epoch], while every division is
+/// counted from 1, so "2020:2:1" is the first day of the second month of the 2020th year.
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, PartialEq)]
 pub struct System {
+    id: Id,
     pub unit_cycle: UnitCycle,
     pub cycles: Vec<Cycle>,
+    pub epochs: Vec<Epoch>,
+    ///Where the system lies on the absolute timeline it shares with the other systems of a project
+    anchor: Option<Anchor>,
 }
 
 impl System {
@@ -161,42 +488,796 @@ impl System {
                 description: String::from("The base unit of time"),
             },
             cycles: Vec::new(),
+            epochs: Vec::new(),
+            anchor: None,
+            id: uuid::gen_128(),
         }
     }
-    pub fn from_cycles(unit: UnitCycle, cycles: impl IntoIterator<Item = Cycle>) -> Self {
-        System {
+    pub fn get_id(&self) -> Id {
+        self.id
+    }
+    pub fn from_cycles(unit: UnitCycle, cycles: impl IntoIterator<Item = Cycle>) -> Result<Self> {
+        let system = System {
             unit_cycle: unit,
             cycles: cycles.into_iter().collect(),
-        }
+            epochs: Vec::new(),
+            anchor: None,
+            id: uuid::gen_128(),
+        };
+        system.verify_cycles()?;
+        Ok(system)
     }
     ///For a cycle list to be valid, it must have the following properties:
-    /// 1. None of the cycles should have a coef of less than 0
-    /// 2. No cycle can have more than one cycles referencing it as a parent
-    ///
-
-    fn verify_cycles(cycles: Vec<Cycle>) -> Result<()> {
-        if cycles.iter().any(|cycle| {
-            cycle.parent != 0 && cycles.iter().filter(|c| c.id == cycle.parent).count() > 1
-        }) {
-            return Err(anyhow!("Cycle cannot be a child more than once"));
+    /// 1. Every cycle has a unique, non zero id, 0 being the unit cycle
+    /// 2. None of the cycles should have a coef of 0
+    /// 3. Every parent exists, and following the parents always leads back to the unit cycle
+    /// 4. No cycle can be divided by more than one cycle
+    /// 5. Irregular cycles are divided by another cycle, which their lengths are measured in,
+    ///    and which cannot be irregular itself
+    fn verify_cycles(&self) -> Result<()> {
+        for cycle in &self.cycles {
+            if cycle.is_irregular() {
+                let child = self.get_dividing_cycle(cycle.id);
+                if !matches!(cycle.coef, Coef::Inverted(_)) || child.is_none() {
+                    return Err(anyhow!(
+                        "Irregular cycle {} must divide its parent, and be divided by another cycle",
+                        cycle.name
+                    ));
+                }
+                if child.unwrap().is_irregular() {
+                    return Err(anyhow!(
+                        "{} takes its length from {}, so cannot be irregular itself",
+                        child.unwrap().name,
+                        cycle.name
+                    ));
+                }
+            }
+            if cycle.id == 0 || self.cycles.iter().filter(|c| c.id == cycle.id).count() > 1 {
+                return Err(anyhow!(
+                    "Cycle {} must have a unique, non zero id",
+                    cycle.name
+                ));
+            }
+            if cycle.coef.mag() == 0 {
+                return Err(anyhow!("Cycle {} cannot have a coef of 0", cycle.name));
+            }
+            cycle.verify_divisions()?;
+            if matches!(cycle.coef, Coef::Inverted(_))
+                && self
+                    .cycles
+                    .iter()
+                    .filter(|c| c.parent == cycle.parent && matches!(c.coef, Coef::Inverted(_)))
+                    .count()
+                    > 1
+            {
+                return Err(anyhow!("Cycle cannot be a child more than once"));
+            }
+            //the length of a cycle is only defined if its parents lead back to the unit cycle
+            self.get_cycle_length(cycle.id)?;
         }
-
         Ok(())
     }
-    pub fn add_cycle(&mut self, cycle: Cycle) {
+    pub fn add_cycle(&mut self, cycle: Cycle) -> Result<()> {
         self.cycles.push(cycle);
+        if let Err(e) = self.verify_cycles() {
+            self.cycles.pop();
+            return Err(e);
+        }
+        Ok(())
     }
     pub fn get_cycle(&self, cycle_id: CycleId) -> Option<&Cycle> {
         self.cycles.iter().find(|c| c.id == cycle_id)
     }
-    pub fn get_epoch(&self, epoch_id: CycleId) -> Option<&Epoch> {
-        todo!()
+    //Returns the cycle that divides the given one, if any
+    fn get_dividing_cycle(&self, cycle_id: CycleId) -> Option<&Cycle> {
+        self.cycles
+            .iter()
+            .find(|c| c.parent == cycle_id && matches!(c.coef, Coef::Inverted(_)))
+    }
+    ///Returns the unit cycle as a [Cycle] with the id 0, so that it can be measured like any other cycle
+    pub fn get_unit(&self) -> Cycle {
+        Cycle {
+            id: 0,
+            name: self.unit_cycle.name.clone(),
+            description: self.unit_cycle.description.clone(),
+            coef: Coef::Normal(1),
+            div_names: None,
+            parent: 0,
+            div_lengths: None,
+            leap_rules: Vec::new(),
+            intercalary: Vec::new(),
+        }
+    }
+    ///Returns the cycles a date is measured in: the unit cycle, followed by each cycle that divides the previous one
+    pub fn get_date_cycles(&self) -> Result<Vec<Cycle>> {
+        let mut chain = vec![self.get_unit()];
+        let mut current = 0;
+        while let Some(cycle) = self.get_dividing_cycle(current) {
+            if chain.len() > self.cycles.len() {
+                return Err(anyhow!("The cycles of the system contain a loop"));
+            }
+            chain.push(cycle.clone());
+            current = cycle.id;
+        }
+        Ok(chain)
+    }
+    ///Returns the length of the cycle in unit cycles. The divisions of an irregular cycle are given their length
+    /// in a parent without any leap day, so this is only exact for regular cycles.
+    pub fn get_cycle_length(&self, cycle_id: CycleId) -> Result<f64> {
+        let (multiple, fraction) = self.get_cycle_ratio(cycle_id)?;
+        Ok(multiple / fraction)
+    }
+    //Returns the length of the cycle in unit cycles as a whole multiple and a whole fraction,
+    // which stay exact when converting between cycles
+    fn get_cycle_ratio(&self, cycle_id: CycleId) -> Result<(f64, f64)> {
+        let (mut multiple, mut fraction) = (1.0, 1.0);
+        let mut current = cycle_id;
+        let mut depth = 0;
+        while current != 0 {
+            let cycle = self
+                .get_cycle(current)
+                .ok_or_else(|| anyhow!("Cycle with id {} does not exist", current))?;
+            let parent = self.get_cycle(cycle.parent).filter(|p| p.is_irregular());
+            match (cycle.coef, parent) {
+                (Coef::Inverted(0) | Coef::Normal(0), _) => {
+                    return Err(anyhow!("Cycle {} cannot have a coef of 0", cycle.name))
+                }
+                //the parent is measured by the divisions it has in a year without leap days
+                (Coef::Inverted(mag), Some(parent)) => {
+                    let regular = match &parent.div_lengths {
+                        Some(lengths) => lengths.iter().sum::<u32>(),
+                        None => parent.coef.mag() * mag,
+                    };
+                    let intercalary = parent.intercalary.iter().map(|i| i.length).sum::<u32>();
+                    multiple *= parent.coef.mag() as f64;
+                    fraction *= (regular + intercalary) as f64;
+                }
+                (Coef::Inverted(mag), None) => fraction *= mag as f64,
+                (Coef::Normal(mag), _) => multiple *= mag as f64,
+            }
+            depth += 1;
+            if depth > self.cycles.len() {
+                return Err(anyhow!(
+                    "The parents of cycle {} contain a loop",
+                    cycle.name
+                ));
+            }
+            current = cycle.parent;
+        }
+        Ok((multiple, fraction))
+    }
+    ///Returns how many [cycle_2]s fit in one [cycle_1]
+    pub fn convert_to_cycle(&self, cycle_1: &Cycle, cycle_2: &Cycle) -> Result<f64> {
+        let (multiple_1, fraction_1) = self.get_cycle_ratio(cycle_1.id)?;
+        let (multiple_2, fraction_2) = self.get_cycle_ratio(cycle_2.id)?;
+        Ok((multiple_1 * fraction_2) / (fraction_1 * multiple_2))
+    }
+    pub fn add_epoch(&mut self, epoch: Epoch) -> Result<()> {
+        if epoch.id == 0 || self.get_epoch(epoch.id).is_some() {
+            return Err(anyhow!(
+                "Epoch {} must have a unique, non zero id",
+                epoch.name
+            ));
+        }
+        self.epochs.push(epoch);
+        Ok(())
+    }
+    pub fn get_epoch(&self, epoch_id: EpochId) -> Option<&Epoch> {
+        self.epochs.iter().find(|e| e.id == epoch_id)
+    }
+    //Returns the start of the epoch, and whether it counts backwards
+    fn get_epoch_offset(&self, epoch_id: EpochId) -> Result<(f64, bool)> {
+        if epoch_id == 0 {
+            return Ok((0.0, false));
+        }
+        self.get_epoch(epoch_id)
+            .map(|e| (e.delta_from_zero, e.backwards))
+            .ok_or_else(|| anyhow!("Epoch with id {} does not exist", epoch_id))
+    }
+    //Makes sure the date measures the cycles of this system, in order
+    fn check_measures(&self, date: &DateTime) -> Result<Vec<Cycle>> {
+        let chain = self.get_date_cycles()?;
+        if date.measures.is_empty() || date.measures.len() > chain.len() {
+            return Err(anyhow!(
+                "A date must have between 1 and {} measures",
+                chain.len()
+            ));
+        }
+        for (measure, cycle) in date.measures.iter().zip(chain.iter()) {
+            if measure.cycle.id != cycle.id {
+                return Err(anyhow!(
+                    "Expected a measure of {}, found {}",
+                    cycle.name,
+                    measure.cycle.name
+                ));
+            }
+            if !measure.moment.is_finite() {
+                return Err(anyhow!("The measure of {} is not finite", cycle.name));
+            }
+        }
+        Ok(chain)
+    }
+    fn get_moments(&self, date: &DateTime) -> Result<Moments> {
+        let chain = self.check_measures(date)?;
+        let (delta, backwards) = self.get_epoch_offset(date.epoch)?;
+        let mut values = date.measures.iter().map(|m| m.moment).collect::<Vec<_>>();
+        if backwards {
+            values[0] = -values[0];
+        }
+        Ok(Moments {
+            chain,
+            values,
+            delta,
+            backwards,
+            epoch: date.epoch,
+        })
+    }
+    //Returns the divisions of the cycle at [level] within the instance of its parent the moments lie in, in the
+    // order they occur. Leap rules hold for the number of the parent division, or for the number of the unit cycle
+    // counted from the universal 0 Epoch.
+    fn get_divisions(&self, moments: &Moments, level: usize) -> Result<Vec<Division>> {
+        let cycle = &moments.chain[level];
+        let parent = &moments.chain[level - 1];
+        let parent_number = moments.values[level - 1].floor();
+        let parent_index = match level {
+            1 => (parent_number + moments.delta).floor() as i64,
+            _ => parent_number as i64,
+        };
+        let regular = match parent.is_irregular() {
+            true => self
+                .get_divisions(moments, level - 1)?
+                .into_iter()
+                .find(|d| d.number as f64 == parent_number)
+                .map(|d| d.length)
+                .ok_or_else(|| anyhow!("{} {} does not exist", parent.name, parent_number))?,
+            false => cycle.coef.mag(),
+        };
+        let child_length = moments.chain.get(level + 1).map_or(1, |c| c.coef.mag());
+        let mut divisions = Vec::new();
+        for after in 0..=regular {
+            for (i, period) in cycle.intercalary.iter().enumerate() {
+                if period.after == after {
+                    divisions.push(Division {
+                        number: regular + 1 + i as u32,
+                        length: period.length,
+                        intercalary: true,
+                    });
+                }
+            }
+            if after < regular {
+                //the lengths are checked when the cycles are verified, but may have been edited since
+                let length = match &cycle.div_lengths {
+                    Some(l) => *l.get(after as usize).ok_or_else(|| {
+                        anyhow!("{} has no length for division {}", cycle.name, after + 1)
+                    })?,
+                    None => child_length,
+                };
+                divisions.push(Division {
+                    number: after + 1,
+                    length,
+                    intercalary: false,
+                });
+            }
+        }
+        for rule in cycle
+            .leap_rules
+            .iter()
+            .filter(|r| r.condition.holds(parent_index))
+        {
+            if let Some(d) = divisions.iter_mut().find(|d| d.number == rule.division) {
+                d.length += rule.extra;
+            }
+        }
+        divisions.retain(|d| d.length > 0);
+        Ok(divisions)
+    }
+    //Moves the moment at [level] by a whole number of divisions, carrying into its parents. Intercalary periods
+    // are skipped unless [intercalary] is set, and a moment within one counts as the division before it.
+    fn step(
+        &self,
+        moments: &mut Moments,
+        level: usize,
+        amount: i64,
+        intercalary: bool,
+    ) -> Result<()> {
+        if level == 0 {
+            moments.values[0] += amount as f64;
+            return Ok(());
+        }
+        let mut remaining = amount;
+        let mut from = EStepFrom::Current;
+        loop {
+            let divisions = self.get_divisions(moments, level)?;
+            let numbers = divisions
+                .iter()
+                .filter(|d| intercalary || !d.intercalary)
+                .map(|d| d.number)
+                .collect::<Vec<_>>();
+            if numbers.is_empty() {
+                return Err(anyhow!(
+                    "{} has no divisions to step through",
+                    moments.chain[level - 1].name
+                ));
+            }
+            let len = numbers.len() as i64;
+            let position = match from {
+                EStepFrom::BeforeFirst => -1,
+                EStepFrom::AfterLast => len,
+                EStepFrom::Current => {
+                    let current = moments.values[level].floor();
+                    let index = divisions
+                        .iter()
+                        .position(|d| d.number as f64 == current)
+                        .ok_or_else(|| {
+                            anyhow!("{} {} does not exist", moments.chain[level].name, current)
+                        })?;
+                    divisions[..=index]
+                        .iter()
+                        .filter(|d| intercalary || !d.intercalary)
+                        .count() as i64
+                        - 1
+                }
+            };
+            let target = position + remaining;
+            if (0..len).contains(&target) {
+                moments.values[level] = numbers[target as usize] as f64;
+                return Ok(());
+            }
+            //intercalary periods of the parent have divisions of their own, so they are never skipped
+            if target >= len {
+                remaining = target - len + 1;
+                self.step(moments, level - 1, 1, true)?;
+                from = EStepFrom::BeforeFirst;
+            } else {
+                remaining = target;
+                self.step(moments, level - 1, -1, true)?;
+                from = EStepFrom::AfterLast;
+            }
+        }
+    }
+    //Carries every moment that lies outside of its parent into the parent, from the unit cycle down,
+    // so that the 14th month becomes the second month of the next year. Fractions of a division are kept.
+    fn carry(&self, moments: &mut Moments) -> Result<()> {
+        for level in 1..moments.values.len() {
+            let whole = moments.values[level].floor();
+            if !self
+                .get_divisions(moments, level)?
+                .iter()
+                .any(|d| d.number as f64 == whole)
+            {
+                let fraction = moments.values[level] - whole;
+                moments.values[level] = 1.0;
+                self.step(moments, level, whole as i64 - 1, false)?;
+                moments.values[level] += fraction;
+            }
+        }
+        Ok(())
+    }
+    //Moves every moment below [level] that no longer exists to the last division before it, so that adding
+    // a month to the 31st of January gives the last day of February
+    fn clamp(&self, moments: &mut Moments, level: usize) -> Result<()> {
+        for level in level + 1..moments.values.len() {
+            let whole = moments.values[level].floor();
+            let divisions = self.get_divisions(moments, level)?;
+            if !divisions.iter().any(|d| d.number as f64 == whole) {
+                let number = divisions
+                    .iter()
+                    .map(|d| d.number)
+                    .filter(|n| (*n as f64) < whole)
+                    .max()
+                    .or_else(|| divisions.first().map(|d| d.number))
+                    .ok_or_else(|| anyhow!("{} has no divisions", moments.chain[level - 1].name))?;
+                moments.values[level] += number as f64 - whole;
+            }
+        }
+        Ok(())
+    }
+    ///Makes sure the date belongs to this system, and that each division exists within its parent
+    /// (e.g. there is no 13th month, nor a 30th of February)
+    pub fn verify_date(&self, date: &DateTime) -> Result<()> {
+        let moments = self.get_moments(date)?;
+        for level in 1..moments.values.len() {
+            let whole = moments.values[level].floor();
+            if !self
+                .get_divisions(&moments, level)?
+                .iter()
+                .any(|d| d.number as f64 == whole)
+            {
+                return Err(anyhow!(
+                    "{} {} does not exist within {} {}",
+                    moments.chain[level].name,
+                    moments.values[level],
+                    moments.chain[level - 1].name,
+                    moments.values[level - 1]
+                ));
+            }
+        }
+        Ok(())
+    }
+    ///Returns the number of unit cycles from the universal 0 Epoch to the date
+    pub fn to_units(&self, date: &DateTime) -> Result<f64> {
+        let mut moments = self.get_moments(date)?;
+        self.carry(&mut moments)?;
+        let mut units = moments.delta + moments.values[0];
+        //the part of the unit cycle the current division spans
+        let mut span = 1.0;
+        for level in 1..moments.values.len() {
+            let divisions = self.get_divisions(&moments, level)?;
+            let total = divisions.iter().map(|d| d.length).sum::<u32>() as f64;
+            let whole = moments.values[level].floor();
+            let index = divisions
+                .iter()
+                .position(|d| d.number as f64 == whole)
+                .ok_or_else(|| anyhow!("{} {} does not exist", moments.chain[level].name, whole))?;
+            let before = divisions[..index].iter().map(|d| d.length).sum::<u32>() as f64;
+            let length = divisions[index].length as f64;
+            units += span * (before + (moments.values[level] - whole) * length) / total;
+            span *= length / total;
+        }
+        Ok(units)
+    }
+    ///Returns the date that lies the given number of unit cycles from the universal 0 Epoch, measured from [epoch]
+    pub fn from_units(&self, units: f64, epoch: EpochId) -> Result<DateTime> {
+        let depth = self.get_date_cycles()?.len();
+        self.units_to_date(units, epoch, depth)
+    }
+    //Measures the date in the first [depth] cycles, the last of which keeps any fraction left
+    fn units_to_date(&self, units: f64, epoch: EpochId, depth: usize) -> Result<DateTime> {
+        if !units.is_finite() {
+            return Err(anyhow!("Cannot measure a date of {} unit cycles", units));
+        }
+        let (delta, backwards) = self.get_epoch_offset(epoch)?;
+        let tolerance = get_tolerance(units);
+        let relative = units - delta;
+        let mut whole = relative.floor();
+        if relative - whole > 1.0 - tolerance {
+            whole += 1.0;
+        }
+        let mut rest = (relative - whole).max(0.0);
+        let mut moments = Moments {
+            chain: self.get_date_cycles()?,
+            values: vec![whole],
+            delta,
+            backwards,
+            epoch,
+        };
+        let mut span = 1.0;
+        for level in 1..depth.min(moments.chain.len()) {
+            let divisions = self.get_divisions(&moments, level)?;
+            let total = divisions.iter().map(|d| d.length).sum::<u32>() as f64;
+            //the position within the parent, measured in the cycle that divides this one
+            let position = rest / span * total;
+            let position_tolerance = tolerance / span * total;
+            let mut before = 0.0;
+            let mut index = 0;
+            for (i, d) in divisions.iter().enumerate() {
+                index = i;
+                if position - before < d.length as f64 - position_tolerance
+                    || i == divisions.len() - 1
+                {
+                    break;
+                }
+                before += d.length as f64;
+            }
+            let length = divisions[index].length as f64;
+            rest = (rest - span * before / total).max(0.0);
+            span *= length / total;
+            moments.values.push(divisions[index].number as f64);
+        }
+        //whatever is left is a fraction of the finest cycle measured
+        let fraction = rest / span;
+        if rest > tolerance {
+            *moments.values.last_mut().unwrap() += fraction;
+        }
+        Ok(moments.to_date())
+    }
+    ///Carries every division that lies outside of its parent into the parent (e.g. the 14th month becomes
+    /// the second month of the next year), keeping the same measures and [Epoch]
+    pub fn normalize(&self, date: &DateTime) -> Result<DateTime> {
+        let mut moments = self.get_moments(date)?;
+        self.carry(&mut moments)?;
+        Ok(moments.to_date())
     }
-    pub fn convert_to_cycle(&self, cycle_1: Cycle, cycle_2: Cycle) -> f64 {
-        todo!()
+    ///Measures the same moment from another [Epoch]
+    pub fn convert_epoch(&self, date: &DateTime, epoch: EpochId) -> Result<DateTime> {
+        self.units_to_date(self.to_units(date)?, epoch, date.measures.len())
+    }
+    ///Orders two dates of the system, whichever cycles and epochs they are measured in. This is the only way
+    /// dates are ordered.
+    pub fn compare(&self, a: &DateTime, b: &DateTime) -> Result<Ordering> {
+        let (a, b) = (self.to_units(a)?, self.to_units(b)?);
+        if (a - b).abs() < get_tolerance(a.abs().max(b.abs())) {
+            return Ok(Ordering::Equal);
+        }
+        a.partial_cmp(&b)
+            .ok_or_else(|| anyhow!("Cannot compare {} and {}", a, b))
+    }
+    ///Sorts the dates from the earliest to the latest
+    pub fn sort_dates(&self, dates: &mut [DateTime]) -> Result<()> {
+        for date in dates.iter() {
+            self.to_units(date)?;
+        }
+        dates.sort_by(|a, b| self.compare(a, b).unwrap_or(Ordering::Equal));
+        Ok(())
+    }
+    ///Adds [amount] of the cycle to the date. Whole amounts of the cycles the date is measured in are added to
+    /// that measure, and carried into the larger cycles, so adding a month to the 14th of June gives the 14th of July,
+    /// and adding one to the 31st of January gives the last day of February. Intercalary periods are skipped when
+    /// adding the cycle they interrupt. Any other amount is added as its length in unit cycles. Negative amounts go back in time.
+    pub fn add(&self, date: &DateTime, amount: f64, cycle_id: CycleId) -> Result<DateTime> {
+        let level = date.measures.iter().position(|m| m.cycle.id == cycle_id);
+        match level {
+            Some(level) if amount.fract() == 0.0 => {
+                let mut moments = self.get_moments(date)?;
+                self.carry(&mut moments)?;
+                let fraction = moments.values[level] - moments.values[level].floor();
+                self.step(&mut moments, level, amount as i64, false)?;
+                moments.values[level] += fraction;
+                self.clamp(&mut moments, level)?;
+                Ok(moments.to_date())
+            }
+            _ => {
+                let units = self.to_units(date)? + amount * self.get_cycle_length(cycle_id)?;
+                self.units_to_date(units, date.epoch, date.measures.len())
+            }
+        }
+    }
+    pub fn subtract(&self, date: &DateTime, amount: f64, cycle_id: CycleId) -> Result<DateTime> {
+        self.add(date, -amount, cycle_id)
+    }
+    ///Returns the number of the cycles from [a] to [b], which is negative if [b] comes first.
+    /// It is measured with [System::get_cycle_length], so ignores leap days.
+    pub fn difference(&self, a: &DateTime, b: &DateTime, cycle_id: CycleId) -> Result<f64> {
+        Ok((self.to_units(b)? - self.to_units(a)?) / self.get_cycle_length(cycle_id)?)
+    }
+    pub fn set_anchor(&mut self, anchor: Anchor) -> Result<()> {
+        if !(anchor.scale > 0.0 && anchor.scale.is_finite() && anchor.offset.is_finite()) {
+            return Err(anyhow!(
+                "An anchor must have a positive scale and a finite offset"
+            ));
+        }
+        self.get_anchor_level(&self.get_date_cycles()?, &anchor)?;
+        self.anchor = Some(anchor);
+        Ok(())
+    }
+    ///Anchors the system so that the given date of the system falls on the given absolute moment,
+    /// e.g. so that the first day of its calendar is the first day of another's year 2000
+    pub fn set_anchor_at(
+        &mut self,
+        cycle_id: CycleId,
+        scale: f64,
+        date: &DateTime,
+        absolute: f64,
+    ) -> Result<()> {
+        self.set_anchor(Anchor::new(cycle_id, scale, 0.0))?;
+        let offset = absolute - self.to_absolute(date)?;
+        self.set_anchor(Anchor::new(cycle_id, scale, offset))
+    }
+    pub fn get_anchor(&self) -> Option<&Anchor> {
+        self.anchor.as_ref()
+    }
+    fn get_anchor_level(&self, chain: &[Cycle], anchor: &Anchor) -> Result<usize> {
+        chain
+            .iter()
+            .position(|c| c.id == anchor.cycle)
+            .ok_or_else(|| anyhow!("Dates are not measured in cycle {}", anchor.cycle))
+    }
+    //Returns the number of the cycle at level [k] within the division at level [j] the moments lie in.
+    // Every moment below [j] is discarded.
+    fn count_within(&self, moments: &mut Moments, j: usize, k: usize) -> Result<f64> {
+        moments.values.truncate(j + 1);
+        if j == k {
+            return Ok(1.0);
+        }
+        let divisions = self.get_divisions(moments, j + 1)?;
+        //below the irregular cycles, every division holds the same number of the cycle
+        if moments.chain[j + 1..k].iter().all(|c| !c.is_irregular()) {
+            let regular = moments.chain[j + 2..=k]
+                .iter()
+                .map(|c| c.coef.mag() as f64)
+                .product::<f64>();
+            return Ok(divisions.len() as f64 * regular);
+        }
+        //the length of the divisions of an irregular cycle is the number of the cycle that divides it
+        if moments.chain[j + 2..k].iter().all(|c| !c.is_irregular()) {
+            let regular = moments.chain[j + 3..=k]
+                .iter()
+                .map(|c| c.coef.mag() as f64)
+                .product::<f64>();
+            return Ok(divisions.iter().map(|d| d.length as f64).sum::<f64>() * regular);
+        }
+        let mut count = 0.0;
+        for d in divisions {
+            moments.values.truncate(j + 1);
+            moments.values.push(d.number as f64);
+            count += self.count_within(moments, j + 1, k)?;
+        }
+        moments.values.truncate(j + 1);
+        Ok(count)
+    }
+    //Returns the number of the cycle at [level] from the universal 0 Epoch to the start of the unit cycle [year]
+    fn count_to_year(&self, chain: &[Cycle], year: i64, level: usize) -> Result<f64> {
+        let mut moments = Moments {
+            chain: chain.to_vec(),
+            values: vec![0.0],
+            delta: 0.0,
+            backwards: false,
+            epoch: 0,
+        };
+        if chain[1..=level].iter().all(|c| !c.is_irregular()) {
+            return Ok(year as f64 * self.count_within(&mut moments, 0, level)?);
+        }
+        let (from, to, sign) = match year >= 0 {
+            true => (0, year, 1.0),
+            false => (year, 0, -1.0),
+        };
+        let mut count = 0.0;
+        for y in from..to {
+            moments.values = vec![y as f64];
+            count += sign * self.count_within(&mut moments, 0, level)?;
+        }
+        Ok(count)
+    }
+    ///Returns the moment of the date on the absolute timeline the system is anchored to
+    pub fn to_absolute(&self, date: &DateTime) -> Result<f64> {
+        let anchor = self
+            .anchor
+            .as_ref()
+            .ok_or_else(|| anyhow!("The system is not anchored to an absolute timeline"))?;
+        let date = match date.epoch {
+            0 => date.clone(),
+            _ => self.convert_epoch(date, 0)?,
+        };
+        let mut moments = self.get_moments(&date)?;
+        self.carry(&mut moments)?;
+        let level = self.get_anchor_level(&moments.chain, anchor)?;
+        //the measures the date leaves out lie at the start of their parent
+        while moments.values.len() <= level {
+            let first = self
+                .get_divisions(&moments, moments.values.len())?
+                .first()
+                .map(|d| d.number)
+                .ok_or_else(|| anyhow!("{} has no divisions", moments.chain[level].name))?;
+            moments.values.push(first as f64);
+        }
+        let mut count =
+            self.count_to_year(&moments.chain, moments.values[0].floor() as i64, level)?;
+        for l in 1..=level {
+            let current = moments.values[l].floor();
+            for d in self.get_divisions(&moments, l)? {
+                if d.number as f64 == current {
+                    break;
+                }
+                let mut scratch = Moments {
+                    chain: moments.chain.clone(),
+                    values: moments.values[..l].to_vec(),
+                    ..moments
+                };
+                scratch.values.push(d.number as f64);
+                count += self.count_within(&mut scratch, l, level)?;
+            }
+        }
+        //the finer measures are a fraction of the anchor cycle
+        let mut fraction = moments.values[level] - moments.values[level].floor();
+        let mut span = 1.0;
+        for l in level + 1..moments.values.len() {
+            let divisions = self.get_divisions(&moments, l)?;
+            let total = divisions.iter().map(|d| d.length).sum::<u32>() as f64;
+            let whole = moments.values[l].floor();
+            let index = divisions
+                .iter()
+                .position(|d| d.number as f64 == whole)
+                .ok_or_else(|| anyhow!("{} {} does not exist", moments.chain[l].name, whole))?;
+            let before = divisions[..index].iter().map(|d| d.length).sum::<u32>() as f64;
+            let length = divisions[index].length as f64;
+            fraction += span * (before + (moments.values[l] - whole) * length) / total;
+            span *= length / total;
+        }
+        Ok(anchor.offset + anchor.scale * (count + fraction))
+    }
+    ///Returns the date of the system at the given moment of the absolute timeline, measured from [epoch]
+    pub fn from_absolute(&self, absolute: f64, epoch: EpochId) -> Result<DateTime> {
+        let anchor = self
+            .anchor
+            .as_ref()
+            .ok_or_else(|| anyhow!("The system is not anchored to an absolute timeline"))?;
+        if !absolute.is_finite() {
+            return Err(anyhow!("Cannot measure the absolute moment {}", absolute));
+        }
+        let count = (absolute - anchor.offset) / anchor.scale;
+        let tolerance = get_tolerance(count);
+        let chain = self.get_date_cycles()?;
+        let level = self.get_anchor_level(&chain, anchor)?;
+        //start from the nominal length of the unit cycle, then walk to the one the moment lies in
+        let per_unit = self.convert_to_cycle(&chain[0], &chain[level])?;
+        let mut year = (count / per_unit).floor() as i64;
+        let mut start = self.count_to_year(&chain, year, level)?;
+        let mut moments = Moments {
+            chain,
+            values: vec![year as f64],
+            delta: 0.0,
+            backwards: false,
+            epoch: 0,
+        };
+        loop {
+            moments.values = vec![year as f64];
+            let length = self.count_within(&mut moments, 0, level)?;
+            if count < start - tolerance {
+                year -= 1;
+                moments.values = vec![year as f64];
+                start -= self.count_within(&mut moments, 0, level)?;
+            } else if count >= start + length - tolerance {
+                start += length;
+                year += 1;
+            } else {
+                break;
+            }
+        }
+        moments.values = vec![year as f64];
+        let mut rest = (count - start).max(0.0);
+        for l in 1..=level {
+            let divisions = self.get_divisions(&moments, l)?;
+            let mut number = 0;
+            for (i, d) in divisions.iter().enumerate() {
+                let mut scratch = Moments {
+                    chain: moments.chain.clone(),
+                    values: moments.values.clone(),
+                    ..moments
+                };
+                scratch.values.push(d.number as f64);
+                let length = self.count_within(&mut scratch, l, level)?;
+                number = d.number;
+                if rest < length - tolerance || i == divisions.len() - 1 {
+                    break;
+                }
+                rest = (rest - length).max(0.0);
+            }
+            moments.values.push(number as f64);
+        }
+        //what is left is a fraction of the anchor cycle, measured in the finer cycles
+        let mut span = 1.0;
+        for l in level + 1..moments.chain.len() {
+            let divisions = self.get_divisions(&moments, l)?;
+            let total = divisions.iter().map(|d| d.length).sum::<u32>() as f64;
+            let position = rest / span * total;
+            let position_tolerance = tolerance / span * total;
+            let mut before = 0.0;
+            let mut index = 0;
+            for (i, d) in divisions.iter().enumerate() {
+                index = i;
+                if position - before < d.length as f64 - position_tolerance
+                    || i == divisions.len() - 1
+                {
+                    break;
+                }
+                before += d.length as f64;
+            }
+            let length = divisions[index].length as f64;
+            rest = (rest - span * before / total).max(0.0);
+            span *= length / total;
+            moments.values.push(divisions[index].number as f64);
+        }
+        if rest > tolerance {
+            *moments.values.last_mut().unwrap() += rest / span;
+        }
+        let date = moments.to_date();
+        match epoch {
+            0 => Ok(date),
+            _ => self.convert_epoch(&date, epoch),
+        }
+    }
+    ///Converts a date of this system to the date of [other] at the same absolute moment, measured from [epoch]
+    pub fn convert_to_system(
+        &self,
+        date: &DateTime,
+        other: &System,
+        epoch: EpochId,
+    ) -> Result<DateTime> {
+        other.from_absolute(self.to_absolute(date)?, epoch)
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Duration {
     pub start: DateTime,
     pub end: DateTime,
@@ -205,8 +1286,13 @@ impl Duration {
     pub fn new(start: DateTime, end: DateTime) -> Self {
         Duration { start, end }
     }
-    pub fn get_duration(&self) -> f64 {
-        todo!()
+    ///Returns the length of the duration in unit cycles
+    pub fn get_duration(&self, system: &System) -> Result<f64> {
+        system.difference(&self.start, &self.end, 0)
+    }
+    ///Returns the length of the duration in the given cycle
+    pub fn get_duration_in(&self, system: &System, cycle_id: CycleId) -> Result<f64> {
+        system.difference(&self.start, &self.end, cycle_id)
     }
 }
 
@@ -219,6 +1305,7 @@ where
 #[cfg(test)]
 mod test_khronos {
     use super::*;
+    use std::collections::HashMap;
 
     #[test]
     fn test_basic() {
@@ -281,7 +1368,452 @@ mod test_khronos {
                 psuedo_minute,
                 psuedo_second,
             ],
+        )
+        .unwrap();
+        let date_time = DateTime::from_cycle_string("2022:1:3:5:13:54:54", &system).unwrap();
+        assert_eq!(date_time.measures.len(), 7);
+        assert_eq!(date_time.measures[0].cycle.name, "Year");
+        assert_eq!(date_time.measures[3].moment, 5.0);
+    }
+    //A year of 12 months of 30 days of 24 hours, with years counted before and after the founding
+    fn get_test_system() -> System {
+        let unit_cycle = UnitCycle {
+            name: String::from("Year"),
+            description: String::from("A year"),
+        };
+        let mut system = System::from_cycles(
+            unit_cycle,
+            vec![
+                Cycle::new(1, "Month", "A month", Coef::Inverted(12), None, 0).unwrap(),
+                Cycle::new(2, "Day", "A day", Coef::Inverted(30), None, 1).unwrap(),
+                Cycle::new(3, "Hour", "An hour", Coef::Inverted(24), None, 2).unwrap(),
+                Cycle::new(4, "Decade", "Ten years", Coef::Normal(10), None, 0).unwrap(),
+            ],
+        )
+        .unwrap();
+        system
+            .add_epoch(Epoch::new(1, "After Founding", "AF", 0.0))
+            .unwrap();
+        system
+            .add_epoch(Epoch::new(2, "Before Founding", "BF", 0.0).counting_backwards())
+            .unwrap();
+        system
+    }
+    fn get_moments(date: &DateTime) -> Vec<f64> {
+        date.measures.iter().map(|m| m.moment).collect()
+    }
+    #[test]
+    fn test_units() {
+        let system = get_test_system();
+        let date = DateTime::from_cycle_string("2022:3:15:13", &system).unwrap();
+        let units = system.to_units(&date).unwrap();
+        let expected = 2022.0 + 2.0 / 12.0 + 14.0 / 360.0 + 12.0 / 8640.0;
+        assert!((units - expected).abs() < EPSILON);
+        assert_eq!(system.from_units(units, 0).unwrap(), date);
+        //fractions are kept by the finest measure
+        let noon = DateTime::from_cycle_string("2022:3:15.5", &system).unwrap();
+        assert_eq!(get_moments(&noon), vec![2022.0, 3.0, 15.5]);
+        assert_eq!(
+            get_moments(
+                &system
+                    .from_units(system.to_units(&noon).unwrap(), 0)
+                    .unwrap()
+            ),
+            vec![2022.0, 3.0, 15.0, 13.0]
+        );
+        let month = system.get_cycle(1).unwrap();
+        let day = system.get_cycle(2).unwrap();
+        assert_eq!(system.convert_to_cycle(month, day).unwrap(), 30.0);
+        let decade = system.get_cycle(4).unwrap();
+        assert_eq!(system.convert_to_cycle(decade, month).unwrap(), 120.0);
+    }
+    #[test]
+    fn test_arithmetic() {
+        let system = get_test_system();
+        let date = DateTime::from_cycle_string("2022:12:30:23", &system).unwrap();
+        let later = system.add(&date, 2.0, 3).unwrap();
+        assert_eq!(get_moments(&later), vec![2023.0, 1.0, 1.0, 1.0]);
+        let earlier = system.subtract(&later, 13.0, 1).unwrap();
+        assert_eq!(get_moments(&earlier), vec![2021.0, 12.0, 1.0, 1.0]);
+        //amounts that are not whole are added as lengths of time
+        let half = system.add(&date, 0.5, 2).unwrap();
+        assert_eq!(get_moments(&half), vec![2023.0, 1.0, 1.0, 11.0]);
+        //cycles the date is not measured in also work
+        let decade = system.add(&date, 1.0, 4).unwrap();
+        assert_eq!(get_moments(&decade), vec![2032.0, 12.0, 30.0, 23.0]);
+        assert!((system.difference(&date, &decade, 4).unwrap() - 1.0).abs() < EPSILON);
+
+        let unnormalized = DateTime::new(vec![
+            CycleMeasure {
+                cycle: system.get_unit(),
+                moment: 2022.0,
+            },
+            CycleMeasure {
+                cycle: system.get_cycle(1).unwrap().clone(),
+                moment: 14.0,
+            },
+        ]);
+        assert!(system.verify_date(&unnormalized).is_err());
+        assert_eq!(
+            get_moments(&system.normalize(&unnormalized).unwrap()),
+            vec![2023.0, 2.0]
+        );
+
+        let duration = Duration::new(
+            DateTime::from_cycle_string("2022:1:1", &system).unwrap(),
+            DateTime::from_cycle_string("2022:2:11", &system).unwrap(),
+        );
+        assert!((duration.get_duration_in(&system, 2).unwrap() - 40.0).abs() < EPSILON);
+        assert!((duration.get_duration(&system).unwrap() - 40.0 / 360.0).abs() < EPSILON);
+    }
+    #[test]
+    fn test_epochs_and_ordering() {
+        let system = get_test_system();
+        let bf = DateTime::from_cycle_string("100:6", &system)
+            .unwrap()
+            .with_epoch(2);
+        let af = DateTime::from_cycle_string("5:1", &system)
+            .unwrap()
+            .with_epoch(1);
+        assert_eq!(system.compare(&bf, &af).unwrap(), Ordering::Less);
+        //the unit cycles count backwards, but their divisions still count forwards
+        let converted = system.convert_epoch(&bf, 1).unwrap();
+        assert_eq!(get_moments(&converted), vec![-100.0, 6.0]);
+        assert_eq!(system.compare(&bf, &converted).unwrap(), Ordering::Equal);
+        let next_year = system.add(&bf, 1.0, 0).unwrap();
+        assert_eq!(get_moments(&next_year), vec![99.0, 6.0]);
+        assert_eq!(next_year.epoch, 2);
+        //100 BF comes before 99 BF
+        assert_eq!(system.compare(&bf, &next_year).unwrap(), Ordering::Less);
+
+        let mut dates = vec![af.clone(), next_year.clone(), bf.clone()];
+        system.sort_dates(&mut dates).unwrap();
+        assert_eq!(dates, vec![bf, next_year, af]);
+    }
+    #[test]
+    fn test_invalid_dates() {
+        let system = get_test_system();
+        assert!(DateTime::from_cycle_string("2022:x", &system).is_err());
+        assert!(DateTime::from_cycle_string("2022:13", &system).is_err());
+        assert!(DateTime::from_cycle_string("2022:0", &system).is_err());
+        assert!(DateTime::from_cycle_string("2022:1:1:1:1", &system).is_err());
+        let unknown_epoch = DateTime::from_cycle_string("2022", &system)
+            .unwrap()
+            .with_epoch(3);
+        assert!(system.to_units(&unknown_epoch).is_err());
+        assert!(system.get_cycle_length(9).is_err());
+        assert!(system.from_units(f64::NAN, 0).is_err());
+
+        let mut system = System::new();
+        let month = Cycle::new(1, "Month", "A month", Coef::Inverted(12), None, 0).unwrap();
+        system.add_cycle(month.clone()).unwrap();
+        assert!(system.add_cycle(month).is_err());
+        let orphan = Cycle::new(2, "Day", "A day", Coef::Inverted(30), None, 7).unwrap();
+        assert!(system.add_cycle(orphan).is_err());
+        assert_eq!(system.cycles.len(), 1);
+
+        assert_eq!(Coef::try_from(0.25).unwrap(), Coef::Inverted(4));
+        assert_eq!(Coef::try_from(3.0).unwrap(), Coef::Normal(3));
+        assert!(Coef::try_from(1.5).is_err());
+        assert!(Coef::try_from(-2.0).is_err());
+    }
+    //A Year of 12 Months with the Gregorian leap years, divided into Days
+    pub(super) fn get_gregorian_system() -> System {
+        let names = [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ];
+        let month = Cycle::new(
+            1,
+            "Month",
+            "A month",
+            Coef::Inverted(12),
+            Some(names.iter().map(|n| n.to_string()).collect()),
+            0,
+        )
+        .unwrap()
+        .with_div_lengths(vec![31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31])
+        .unwrap()
+        .with_leap_rule(2, 1, ELeapCondition::gregorian())
+        .unwrap();
+        let day = Cycle::new(2, "Day", "A day", Coef::Inverted(31), None, 1).unwrap();
+        let unit_cycle = UnitCycle {
+            name: String::from("Year"),
+            description: String::from("A year"),
+        };
+        System::from_cycles(unit_cycle, vec![month, day]).unwrap()
+    }
+    fn date(system: &System, cycle_string: &str) -> DateTime {
+        DateTime::from_cycle_string(cycle_string, system).unwrap()
+    }
+    #[test]
+    fn test_irregular_cycles() {
+        let system = get_gregorian_system();
+        assert!(DateTime::from_cycle_string("2024:2:29", &system).is_ok());
+        assert!(DateTime::from_cycle_string("2000:2:29", &system).is_ok());
+        assert!(DateTime::from_cycle_string("2023:2:29", &system).is_err());
+        assert!(DateTime::from_cycle_string("1900:2:29", &system).is_err());
+        assert!(DateTime::from_cycle_string("2023:4:31", &system).is_err());
+        assert_eq!(
+            system.get_cycle(1).unwrap().get_division_name(2),
+            Some("February")
+        );
+        //names may be edited after the cycle is created
+        let mut month = system.get_cycle(1).unwrap().clone();
+        month.div_names.as_mut().unwrap().truncate(1);
+        assert_eq!(month.get_division_name(2), None);
+        //but lengths, leap rules and intercalary periods must still line up with the divisions
+        let mut edited = system.clone();
+        let month = edited.cycles.iter_mut().find(|c| c.id == 1).unwrap();
+        month.div_lengths.as_mut().unwrap().truncate(11);
+        assert!(DateTime::from_cycle_string("2023:12:31", &edited).is_err());
+        assert!(edited.verify_cycles().is_err());
+        let mut edited = system.clone();
+        let month = edited.cycles.iter_mut().find(|c| c.id == 1).unwrap();
+        month.leap_rules[0].division = 13;
+        assert!(edited.verify_cycles().is_err());
+
+        let add = |d: &str, amount: f64, cycle: CycleId| {
+            get_moments(&system.add(&date(&system, d), amount, cycle).unwrap())
+        };
+        assert_eq!(add("2023:1:31", 1.0, 1), vec![2023.0, 2.0, 28.0]);
+        assert_eq!(add("2024:1:31", 1.0, 1), vec![2024.0, 2.0, 29.0]);
+        assert_eq!(add("2024:2:28", 1.0, 2), vec![2024.0, 2.0, 29.0]);
+        assert_eq!(add("2024:2:29", 1.0, 2), vec![2024.0, 3.0, 1.0]);
+        assert_eq!(add("2024:3:1", -1.0, 2), vec![2024.0, 2.0, 29.0]);
+        assert_eq!(add("2023:12:31", 1.0, 2), vec![2024.0, 1.0, 1.0]);
+        assert_eq!(add("2023:1:1", 365.0, 2), vec![2024.0, 1.0, 1.0]);
+        assert_eq!(add("2024:1:1", 365.0, 2), vec![2024.0, 12.0, 31.0]);
+        assert_eq!(add("2024:2:29", 1.0, 0), vec![2025.0, 2.0, 28.0]);
+
+        //a day is a 366th of a leap year
+        let last = date(&system, "2024:12:31");
+        let units = system.to_units(&last).unwrap();
+        assert!((units - (2024.0 + 365.0 / 366.0)).abs() < EPSILON);
+        assert_eq!(system.from_units(units, 0).unwrap(), last);
+        let leap_day = date(&system, "2024:2:29.5");
+        let units = system.to_units(&leap_day).unwrap();
+        let round_trip = system.from_units(units, 0).unwrap();
+        assert_eq!(
+            system.compare(&round_trip, &leap_day).unwrap(),
+            Ordering::Equal
+        );
+        assert_eq!(round_trip.measures[2].moment.floor(), 29.0);
+        assert_eq!(
+            system
+                .compare(&leap_day, &date(&system, "2024:3:1"))
+                .unwrap(),
+            Ordering::Less
+        );
+        assert_eq!(
+            get_moments(&system.normalize(&date(&system, "2023:2")).unwrap()),
+            vec![2023.0, 2.0]
+        );
+        let overflow = DateTime::new(vec![
+            CycleMeasure {
+                cycle: system.get_unit(),
+                moment: 2023.0,
+            },
+            CycleMeasure {
+                cycle: system.get_cycle(1).unwrap().clone(),
+                moment: 2.0,
+            },
+            CycleMeasure {
+                cycle: system.get_cycle(2).unwrap().clone(),
+                moment: 30.0,
+            },
+        ]);
+        assert_eq!(
+            get_moments(&system.normalize(&overflow).unwrap()),
+            vec![2023.0, 3.0, 2.0]
+        );
+
+        //lengths are measured in a year without leap days
+        let (month, day) = (system.get_cycle(1).unwrap(), system.get_cycle(2).unwrap());
+        assert!((system.get_cycle_length(2).unwrap() - 1.0 / 365.0).abs() < EPSILON);
+        assert_eq!(system.convert_to_cycle(month, day).unwrap(), 365.0 / 12.0);
+
+        assert!(Cycle::new(3, "Month", "", Coef::Inverted(12), None, 0)
+            .unwrap()
+            .with_div_lengths(vec![30; 11])
+            .is_err());
+        //the lengths of an irregular cycle are measured in the cycle that divides it
+        let undivided = Cycle::new(3, "Month", "", Coef::Inverted(12), None, 0)
+            .unwrap()
+            .with_div_lengths(vec![30; 12])
+            .unwrap();
+        assert!(System::from_cycles(system.unit_cycle, vec![undivided]).is_err());
+    }
+    #[test]
+    fn test_intercalary_days() {
+        //12 months of 30 days, with festival days between them and a leap day every 4 years
+        let month = Cycle::new(1, "Month", "A month", Coef::Inverted(12), None, 0)
+            .unwrap()
+            .with_intercalary("Midwinter", 1, 1)
+            .unwrap()
+            .with_intercalary("Greengrass", 4, 1)
+            .unwrap()
+            .with_intercalary("Midsummer", 7, 1)
+            .unwrap()
+            .with_intercalary("Shieldmeet", 7, 0)
+            .unwrap()
+            .with_intercalary("Highharvestide", 9, 1)
+            .unwrap()
+            .with_intercalary("Feast of the Moon", 11, 1)
+            .unwrap()
+            .with_leap_rule(16, 1, ELeapCondition::Every(4, 0))
+            .unwrap();
+        let day = Cycle::new(2, "Day", "A day", Coef::Inverted(30), None, 1).unwrap();
+        let unit_cycle = UnitCycle {
+            name: String::from("Year"),
+            description: String::from("A year"),
+        };
+        let system = System::from_cycles(unit_cycle, vec![month, day]).unwrap();
+        assert_eq!(
+            system.get_cycle(1).unwrap().get_division_name(13),
+            Some("Midwinter")
+        );
+        assert!(DateTime::from_cycle_string("1372:16:1", &system).is_ok());
+        assert!(DateTime::from_cycle_string("1373:16:1", &system).is_err());
+        assert!(DateTime::from_cycle_string("1372:13:2", &system).is_err());
+
+        let add = |d: &str, amount: f64, cycle: CycleId| {
+            get_moments(&system.add(&date(&system, d), amount, cycle).unwrap())
+        };
+        //festival days belong to no month, but are days all the same
+        assert_eq!(add("1372:1:30", 1.0, 2), vec![1372.0, 13.0, 1.0]);
+        assert_eq!(add("1372:13:1", 1.0, 2), vec![1372.0, 2.0, 1.0]);
+        assert_eq!(add("1372:15:1", 1.0, 2), vec![1372.0, 16.0, 1.0]);
+        assert_eq!(add("1373:15:1", 1.0, 2), vec![1373.0, 8.0, 1.0]);
+        assert_eq!(add("1372:1:1", 366.0, 2), vec![1373.0, 1.0, 1.0]);
+        //and are skipped when counting months
+        assert_eq!(add("1372:1:15", 1.0, 1), vec![1372.0, 2.0, 15.0]);
+        assert_eq!(add("1372:13:1", 1.0, 1), vec![1372.0, 2.0, 1.0]);
+        assert_eq!(add("1372:12:5", 1.0, 1), vec![1373.0, 1.0, 5.0]);
+        //a leap day that does not exist in the next year falls back to the division before it
+        assert_eq!(add("1372:16:1", 1.0, 0), vec![1373.0, 15.0, 1.0]);
+
+        let midwinter = date(&system, "1372:13:1");
+        let units = system.to_units(&midwinter).unwrap();
+        assert!((units - (1372.0 + 30.0 / 366.0)).abs() < EPSILON);
+        assert_eq!(system.from_units(units, 0).unwrap(), midwinter);
+        let mut dates = vec![
+            date(&system, "1372:2:1"),
+            date(&system, "1372:13:1"),
+            date(&system, "1372:1:30"),
+        ];
+        system.sort_dates(&mut dates).unwrap();
+        assert_eq!(
+            dates.iter().map(get_moments).collect::<Vec<_>>(),
+            vec![
+                vec![1372.0, 1.0, 30.0],
+                vec![1372.0, 13.0, 1.0],
+                vec![1372.0, 2.0, 1.0]
+            ]
         );
-        let date_time = DateTime::from_cycle_string("2022:1:3:5:13:54:54", &system);
+    }
+    //13 moons of 28 days followed by a Year Day, with a Leap Day after the sixth moon of every 4th year
+    pub(super) fn get_lunar_system() -> System {
+        let moon = Cycle::new(1, "Moon", "A lunar month", Coef::Inverted(13), None, 0)
+            .unwrap()
+            .with_intercalary("Year Day", 13, 1)
+            .unwrap()
+            .with_intercalary("Leap Day", 6, 0)
+            .unwrap()
+            .with_leap_rule(15, 1, ELeapCondition::Every(4, 0))
+            .unwrap();
+        let day = Cycle::new(2, "Day", "A day", Coef::Inverted(28), None, 1).unwrap();
+        let unit_cycle = UnitCycle {
+            name: String::from("Year"),
+            description: String::from("A lunar year"),
+        };
+        System::from_cycles(unit_cycle, vec![moon, day]).unwrap()
+    }
+    #[test]
+    fn test_cross_system_conversion() {
+        let mut gregorian = get_gregorian_system();
+        let mut lunar = get_lunar_system();
+        assert!(gregorian
+            .to_absolute(&date(&gregorian, "2000:1:1"))
+            .is_err());
+        assert!(gregorian.set_anchor(Anchor::new(9, 1.0, 0.0)).is_err());
+        gregorian.set_anchor(Anchor::new(2, 1.0, 0.0)).unwrap();
+        //the first lunar year starts on the first day of the gregorian year 2000
+        let start = gregorian
+            .to_absolute(&date(&gregorian, "2000:1:1"))
+            .unwrap();
+        assert_eq!(start, 730485.0);
+        lunar
+            .set_anchor_at(2, 1.0, &date(&lunar, "0:1:1"), start)
+            .unwrap();
+        lunar
+            .add_epoch(Epoch::new(1, "Before Landing", "BL", 0.0).counting_backwards())
+            .unwrap();
+
+        let to_lunar = |d: &str, epoch: EpochId| {
+            get_moments(
+                &gregorian
+                    .convert_to_system(&date(&gregorian, d), &lunar, epoch)
+                    .unwrap(),
+            )
+        };
+        assert_eq!(to_lunar("2000:1:1", 0), vec![0.0, 1.0, 1.0]);
+        assert_eq!(to_lunar("2000:1:29", 0), vec![0.0, 2.0, 1.0]);
+        assert_eq!(to_lunar("2000:6:17", 0), vec![0.0, 15.0, 1.0]);
+        assert_eq!(to_lunar("2000:6:18", 0), vec![0.0, 7.0, 1.0]);
+        assert_eq!(to_lunar("2000:12:31", 0), vec![0.0, 14.0, 1.0]);
+        assert_eq!(to_lunar("2001:1:1", 0), vec![1.0, 1.0, 1.0]);
+        assert_eq!(to_lunar("1999:12:31", 0), vec![-1.0, 14.0, 1.0]);
+        assert_eq!(to_lunar("1999:12:31", 1), vec![1.0, 14.0, 1.0]);
+        assert_eq!(to_lunar("2000:1:1.5", 0), vec![0.0, 1.0, 1.5]);
+
+        for d in [
+            "2000:2:29",
+            "2023:7:4",
+            "1999:12:31.25",
+            "-4:2:29",
+            "1:1:1",
+            "2400:12:31",
+        ] {
+            let original = date(&gregorian, d);
+            let converted = gregorian.convert_to_system(&original, &lunar, 0).unwrap();
+            let back = lunar.convert_to_system(&converted, &gregorian, 0).unwrap();
+            assert_eq!(
+                gregorian.compare(&original, &back).unwrap(),
+                Ordering::Equal
+            );
+            assert_eq!(get_moments(&back)[..2], get_moments(&original)[..2]);
+        }
+        for d in ["0:15:1", "5:14:1", "-10:3:12", "4:15:1"] {
+            let original = date(&lunar, d);
+            let converted = lunar.convert_to_system(&original, &gregorian, 0).unwrap();
+            let back = gregorian.convert_to_system(&converted, &lunar, 0).unwrap();
+            assert_eq!(get_moments(&back), get_moments(&original));
+        }
+
+        let mut project = crate::Project::new("Two cultures");
+        let gregorian_id = project.add_calendar(gregorian);
+        let lunar_id = project.add_calendar(lunar);
+        let new_year = date(project.get_calendar(gregorian_id).unwrap(), "2001:1:1");
+        let converted = project
+            .convert_date(&new_year, gregorian_id, lunar_id)
+            .unwrap();
+        assert_eq!(get_moments(&converted), vec![1.0, 1.0, 1.0]);
+        assert!(project.convert_date(&new_year, gregorian_id, 7).is_err());
+        let bytes =
+            bincode::encode_to_vec(&project.calendars, bincode::config::standard()).unwrap();
+        let (calendars, _): (HashMap<Id, System>, usize) =
+            bincode::decode_from_slice(&bytes, bincode::config::standard()).unwrap();
+        assert_eq!(calendars, project.calendars);
     }
 }
@@ -0,0 +1,454 @@
+//! Templates that write [DateTime]s the way a world writes them, and read them back.
+//!
+//! A template mixes literal text with fields in braces, such as `"{Day:name}, {Day} {Month:name} {Year} {Epoch}"`.
+//! Each field names a cycle of the [System], and how its measure is written:
+//! - `{Month}` writes the number, and `{Minute:2}` pads it with zeros to 2 digits
+//! - `{Day:ord}` writes it as an ordinal, such as 1st or 22nd
+//! - `{Month:name}` writes the name of the division, or of the intercalary period
+//! - `{Epoch}` writes the abbreviation of the [Epoch], and `{Epoch:name}` its full name
+//!
+//! Braces are written by doubling them. When reading, names are matched regardless of case, any run of
+//! whitespace matches any other, and the epoch may be left out.
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EFieldStyle {
+    ///The number, padded with zeros to the given width
+    Number(usize),
+    Ordinal,
+    Name,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EFormatToken {
+    Literal(String),
+    Cycle {
+        cycle: String,
+        style: EFieldStyle,
+    },
+    ///The abbreviation of the epoch, or its full name
+    Epoch {
+        full_name: bool,
+    },
+}
+///A template, parsed once so it can be used for any number of dates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateFormat {
+    tokens: Vec<EFormatToken>,
+}
+///Returns the English suffix of an ordinal number (st, nd, rd or th)
+pub fn get_ordinal_suffix(number: i64) -> &'static str {
+    match (number.abs() % 10, number.abs() % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+fn write_number(moment: f64, width: usize) -> String {
+    match moment.fract() == 0.0 {
+        true => format!("{:0width$}", moment as i64, width = width),
+        false => format!("{}", moment),
+    }
+}
+//Returns the rest of the text, if it starts with [prefix] regardless of case
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let mut chars = text.char_indices();
+    for p in prefix.chars() {
+        match chars.next() {
+            Some((_, c)) if c.to_lowercase().eq(p.to_lowercase()) => continue,
+            _ => return None,
+        }
+    }
+    Some(chars.as_str())
+}
+//Returns the value of the longest candidate the text starts with, and the rest of the text
+fn match_longest<'a, T: Copy>(text: &'a str, candidates: &[(&str, T)]) -> Option<(T, &'a str)> {
+    candidates
+        .iter()
+        .filter(|(name, _)| !name.is_empty())
+        .filter_map(|(name, value)| {
+            strip_prefix_ignore_case(text, name).map(|rest| (name.chars().count(), *value, rest))
+        })
+        .max_by_key(|(len, _, _)| *len)
+        .map(|(_, value, rest)| (value, rest))
+}
+//Reads a number, with an optional sign and fraction, returning it and the rest of the text
+fn read_number(text: &str) -> Result<(f64, &str)> {
+    let end = text
+        .char_indices()
+        .find(|(i, c)| !(c.is_ascii_digit() || *c == '.' || (*i == 0 && *c == '-')))
+        .map_or(text.len(), |(i, _)| i);
+    let number = text[..end]
+        .parse::<f64>()
+        .map_err(|_| anyhow!("Expected a number at '{}'", text))?;
+    Ok((number, &text[end..]))
+}
+
+impl DateFormat {
+    pub fn new(template: &str) -> Result<Self> {
+        let mut tokens = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(anyhow!("Unmatched '}}' in template '{}'", template)),
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => {
+                                return Err(anyhow!("Unclosed '{{' in template '{}'", template))
+                            }
+                        }
+                    }
+                    if !literal.is_empty() {
+                        tokens.push(EFormatToken::Literal(std::mem::take(&mut literal)));
+                    }
+                    tokens.push(Self::parse_field(&field)?);
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            tokens.push(EFormatToken::Literal(literal));
+        }
+        Ok(DateFormat { tokens })
+    }
+    fn parse_field(field: &str) -> Result<EFormatToken> {
+        let (name, style) = match field.split_once(':') {
+            Some((name, style)) => (name.trim(), style.trim()),
+            None => (field.trim(), ""),
+        };
+        if name.is_empty() {
+            return Err(anyhow!("The field '{{{}}}' does not name a cycle", field));
+        }
+        if name == "Epoch" {
+            return match style {
+                "" => Ok(EFormatToken::Epoch { full_name: false }),
+                "name" => Ok(EFormatToken::Epoch { full_name: true }),
+                _ => Err(anyhow!("Unknown style '{}' for the epoch", style)),
+            };
+        }
+        let style = match style {
+            "" => EFieldStyle::Number(0),
+            "ord" => EFieldStyle::Ordinal,
+            "name" => EFieldStyle::Name,
+            width => EFieldStyle::Number(
+                width
+                    .parse::<usize>()
+                    .map_err(|_| anyhow!("Unknown style '{}' for {}", width, name))?,
+            ),
+        };
+        Ok(EFormatToken::Cycle {
+            cycle: String::from(name),
+            style,
+        })
+    }
+    pub fn get_tokens(&self) -> &[EFormatToken] {
+        &self.tokens
+    }
+    ///Writes the date with the template
+    pub fn format(&self, system: &System, date: &DateTime) -> Result<String> {
+        let mut text = String::new();
+        for token in &self.tokens {
+            match token {
+                EFormatToken::Literal(literal) => text.push_str(literal),
+                EFormatToken::Cycle { cycle, style } => {
+                    let measure = date
+                        .measures
+                        .iter()
+                        .find(|m| &m.cycle.name == cycle)
+                        .ok_or_else(|| anyhow!("The date has no measure of {}", cycle))?;
+                    match style {
+                        EFieldStyle::Number(width) => {
+                            text.push_str(&write_number(measure.moment, *width))
+                        }
+                        EFieldStyle::Ordinal => {
+                            text.push_str(&write_number(measure.moment, 0));
+                            text.push_str(get_ordinal_suffix(measure.moment.floor() as i64));
+                        }
+                        EFieldStyle::Name => {
+                            let division = measure.moment.floor();
+                            let name = match division >= 1.0 {
+                                true => measure.cycle.get_division_name(division as u32),
+                                false => None,
+                            };
+                            text.push_str(name.ok_or_else(|| {
+                                anyhow!("{} {} has no name", cycle, measure.moment)
+                            })?);
+                        }
+                    }
+                }
+                EFormatToken::Epoch { full_name } => {
+                    if let Some(epoch) = system.get_epoch(date.epoch) {
+                        text.push_str(match full_name {
+                            true => &epoch.name,
+                            false => &epoch.abbreviation,
+                        });
+                    }
+                }
+            }
+        }
+        //the universal epoch has no name, so leave out the space before it
+        Ok(String::from(text.trim_end()))
+    }
+    ///Reads a date written with the template
+    pub fn parse(&self, system: &System, text: &str) -> Result<DateTime> {
+        let chain = system.get_date_cycles()?;
+        let mut moments: Vec<Option<f64>> = vec![None; chain.len()];
+        let mut epoch = 0;
+        let mut rest = text.trim_start();
+        for token in &self.tokens {
+            match token {
+                EFormatToken::Literal(literal) => {
+                    for c in literal.chars() {
+                        rest = match c.is_whitespace() {
+                            true => rest.trim_start(),
+                            false => {
+                                strip_prefix_ignore_case(rest, &c.to_string()).ok_or_else(|| {
+                                    anyhow!("Expected '{}' at '{}'", literal.trim(), rest)
+                                })?
+                            }
+                        };
+                    }
+                }
+                EFormatToken::Cycle { cycle, style } => {
+                    let level = chain
+                        .iter()
+                        .position(|c| &c.name == cycle)
+                        .ok_or_else(|| anyhow!("Dates are not measured in {}", cycle))?;
+                    let moment = match style {
+                        EFieldStyle::Number(_) => {
+                            let (number, r) = read_number(rest)?;
+                            rest = r;
+                            number
+                        }
+                        EFieldStyle::Ordinal => {
+                            let (number, r) = read_number(rest)?;
+                            let suffixes = [("st", ()), ("nd", ()), ("rd", ()), ("th", ())];
+                            rest = match_longest(r, &suffixes).map_or(r, |(_, r)| r);
+                            number
+                        }
+                        EFieldStyle::Name => {
+                            let c = &chain[level];
+                            let names = (1..=c.coef.mag() + c.intercalary.len() as u32)
+                                .filter_map(|d| c.get_division_name(d).map(|n| (n, d)))
+                                .collect::<Vec<_>>();
+                            let (division, r) = match_longest(rest, &names).ok_or_else(|| {
+                                anyhow!("Expected a name of {} at '{}'", cycle, rest)
+                            })?;
+                            rest = r;
+                            division as f64
+                        }
+                    };
+                    match moments[level] {
+                        Some(m) if m != moment => {
+                            return Err(anyhow!("{} is given as both {} and {}", cycle, m, moment))
+                        }
+                        _ => moments[level] = Some(moment),
+                    }
+                }
+                EFormatToken::Epoch { .. } => {
+                    let names = system
+                        .epochs
+                        .iter()
+                        .flat_map(|e| [(e.abbreviation.as_str(), e.id), (e.name.as_str(), e.id)])
+                        .collect::<Vec<_>>();
+                    if let Some((id, r)) = match_longest(rest, &names) {
+                        epoch = id;
+                        rest = r;
+                    }
+                }
+            }
+        }
+        if !rest.trim().is_empty() {
+            return Err(anyhow!(
+                "Unexpected '{}' at the end of the date",
+                rest.trim()
+            ));
+        }
+        let depth = moments
+            .iter()
+            .rposition(|m| m.is_some())
+            .ok_or_else(|| anyhow!("The template has no cycles"))?
+            + 1;
+        let measures = chain
+            .into_iter()
+            .zip(moments)
+            .take(depth)
+            .map(|(cycle, moment)| match moment {
+                Some(moment) => Ok(CycleMeasure { cycle, moment }),
+                None => Err(anyhow!("The date is missing its {}", cycle.name)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let date = DateTime::new(measures).with_epoch(epoch);
+        system.verify_date(&date)?;
+        Ok(date)
+    }
+}
+
+impl System {
+    ///Writes the date with a template, see [DateFormat]
+    pub fn format_date(&self, date: &DateTime, template: &str) -> Result<String> {
+        DateFormat::new(template)?.format(self, date)
+    }
+    ///Reads a date written with a template, see [DateFormat]
+    pub fn parse_date(&self, text: &str, template: &str) -> Result<DateTime> {
+        DateFormat::new(template)?.parse(self, text)
+    }
+}
+
+#[cfg(test)]
+mod test_format {
+    use super::super::test_khronos::{get_gregorian_system, get_lunar_system};
+    use super::*;
+
+    #[test]
+    fn test_format_and_parse() {
+        let mut system = get_gregorian_system();
+        system
+            .add_epoch(Epoch::new(1, "Anno Domini", "AD", 0.0))
+            .unwrap();
+        system
+            .add_epoch(Epoch::new(2, "Before Christ", "BC", 1.0).counting_backwards())
+            .unwrap();
+        let long = DateFormat::new("{Day:ord} of {Month:name}, {Year} {Epoch}").unwrap();
+        let leap_day = DateTime::from_cycle_string("2024:2:29", &system)
+            .unwrap()
+            .with_epoch(1);
+        assert_eq!(
+            long.format(&system, &leap_day).unwrap(),
+            "29th of February, 2024 AD"
+        );
+        assert_eq!(
+            long.parse(&system, "29th  of february, 2024 ad").unwrap(),
+            leap_day
+        );
+        assert_eq!(
+            system
+                .parse_date(
+                    "29 of February, 2024 Anno Domini",
+                    "{Day:ord} of {Month:name}, {Year} {Epoch}"
+                )
+                .unwrap(),
+            leap_day
+        );
+        let ides = system
+            .parse_date(
+                "15th of March, 44 BC",
+                "{Day:ord} of {Month:name}, {Year} {Epoch}",
+            )
+            .unwrap();
+        assert_eq!(ides.epoch, 2);
+        assert_eq!(
+            system
+                .format_date(&ides, "{Day} {Month:name} {Year} {Epoch:name}")
+                .unwrap(),
+            "15 March 44 Before Christ"
+        );
+        //the universal epoch has no abbreviation
+        let universal = DateTime::from_cycle_string("2024:2:9", &system).unwrap();
+        assert_eq!(
+            long.format(&system, &universal).unwrap(),
+            "9th of February, 2024"
+        );
+        assert_eq!(
+            long.parse(&system, "9th of February, 2024").unwrap(),
+            universal
+        );
+
+        let iso = DateFormat::new("{Year}-{Month:2}-{Day:2}").unwrap();
+        assert_eq!(iso.format(&system, &universal).unwrap(), "2024-02-09");
+        assert_eq!(iso.parse(&system, "2024-02-09").unwrap(), universal);
+        assert!(iso.parse(&system, "2024-13-09").is_err());
+        assert!(iso.parse(&system, "2023-02-29").is_err());
+        assert!(iso.parse(&system, "2024-02").is_err());
+        assert!(iso.parse(&system, "2024-02-09 12").is_err());
+        assert!(system.parse_date("9 2024", "{Day} {Year}").is_err());
+        assert_eq!(
+            system.format_date(&universal, "{{{Year}}}").unwrap(),
+            "{2024}"
+        );
+        assert_eq!(universal.to_string(), "2024:2:9");
+
+        let ordinals = [1, 2, 3, 4, 11, 12, 13, 21, 22, 101, 111]
+            .map(|n| format!("{}{}", n, get_ordinal_suffix(n)));
+        assert_eq!(
+            ordinals,
+            [
+                "1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "22nd", "101st",
+                "111th"
+            ]
+        );
+        assert!(DateFormat::new("{Day").is_err());
+        assert!(DateFormat::new("Day}").is_err());
+        assert!(DateFormat::new("{Day:bogus}").is_err());
+        assert!(DateFormat::new("{:name}").is_err());
+    }
+    #[test]
+    fn test_format_division_names() {
+        //the names of intercalary periods are written like those of the divisions
+        let system = get_lunar_system();
+        let year_day = DateTime::from_cycle_string("3:14:1", &system).unwrap();
+        assert_eq!(
+            system.format_date(&year_day, "{Moon:name} {Year}").unwrap(),
+            "Year Day 3"
+        );
+        assert_eq!(
+            system
+                .parse_date("year day 3", "{Moon:name} {Year}")
+                .unwrap(),
+            DateTime::from_cycle_string("3:14", &system).unwrap()
+        );
+        //the moons themselves have no names
+        let moon = DateTime::from_cycle_string("3:2:1", &system).unwrap();
+        assert!(system.format_date(&moon, "{Moon:name} {Year}").is_err());
+        assert!(system.format_date(&moon, "{Week} {Year}").is_err());
+
+        //the days of the week, named by a cycle of their own
+        let names = [
+            "Sunday",
+            "Monday",
+            "Tuesday",
+            "Wednesday",
+            "Thursday",
+            "Friday",
+            "Saturday",
+        ];
+        let month = Cycle::new(1, "Month", "", Coef::Inverted(12), None, 0).unwrap();
+        let week = Cycle::new(2, "Week", "", Coef::Inverted(4), None, 1).unwrap();
+        let day = Cycle::new(
+            3,
+            "Day",
+            "",
+            Coef::Inverted(7),
+            Some(names.iter().map(|n| n.to_string()).collect()),
+            2,
+        )
+        .unwrap();
+        let unit = UnitCycle {
+            name: String::from("Year"),
+            description: String::new(),
+        };
+        let system = System::from_cycles(unit, vec![month, week, day]).unwrap();
+        let template = "{Day:name}, the {Day:ord} day of week {Week} of month {Month}, {Year}";
+        let date = DateTime::from_cycle_string("2022:1:3:5", &system).unwrap();
+        let text = system.format_date(&date, template).unwrap();
+        assert_eq!(text, "Thursday, the 5th day of week 3 of month 1, 2022");
+        assert_eq!(system.parse_date(&text, template).unwrap(), date);
+        assert!(system
+            .parse_date("Monday, the 5th day of week 3 of month 1, 2022", template)
+            .is_err());
+    }
+}
@@ -13,9 +13,12 @@ use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 mod action;
 pub mod binary_storage;
+pub mod compile;
 pub mod ecs;
 pub mod map;
 pub mod mir;
+pub mod story;
+pub mod timeline;
 use common::exports::serde::*;
 use common::exports::*;
 use common::{
@@ -24,6 +27,8 @@ use common::{
     uuid,
 };
 use ecs::{Entman, Id};
+pub use story::{Act, Scene, Story};
+pub use timeline::Timeline;
 ///A [Note] represents a note that can be created by the user.
 
 #[nvproc::bincode_derive]
@@ -43,23 +48,106 @@ pub struct Progression {
     description: String,
     involved_entities: Vec<Id>,
     text: String,
-    ordering: u32,
+    ///The place of the progression in the narrative. Only its order relative to the ranks of the other
+    /// progressions of its [Manuscript] matters, which assigns it.
+    rank: f64,
+    ///When the progression takes place in the story, if it is known
+    story_date: Option<khronos::DateTime>,
+    tags: Vec<String>,
+    status: EProgressionStatus,
+    kind: EProgressionKind,
+}
+///How far the writing of a [Progression] has come
+#[nvproc::bincode_derive]
+#[derive(Debug, Copy, PartialEq, Eq)]
+pub enum EProgressionStatus {
+    Outline,
+    Draft,
+    Revised,
+    Final,
+}
+///Whether a [Progression] opens a new chapter, or continues the current one as a new scene
+#[nvproc::bincode_derive]
+#[derive(Debug, Copy, PartialEq, Eq)]
+pub enum EProgressionKind {
+    Chapter,
+    Scene,
 }
 //impl Progression
 impl Progression {
+    ///Creates a progression with the given ordering as its rank. Ranks are assigned by the [Manuscript] the
+    /// progression is added to, so the ordering is replaced as soon as it is.
+    #[deprecated(
+        note = "progressions are ordered by their manuscript, use Progression::new_draft instead"
+    )]
     pub fn new(name: String, description: String, text: String, ordering: u32) -> Self {
+        Progression {
+            rank: ordering as f64,
+            ..Progression::new_draft(name, description, text)
+        }
+    }
+    ///Creates a draft chapter, to be placed in a [Manuscript]
+    pub fn new_draft(name: String, description: String, text: String) -> Self {
         Progression {
             id: common::uuid::gen_128(),
             name,
             description,
             involved_entities: Vec::new(),
             text,
-            ordering,
+            rank: 0.0,
+            story_date: None,
+            tags: Vec::new(),
+            status: EProgressionStatus::Draft,
+            kind: EProgressionKind::Chapter,
         }
     }
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+    pub fn get_id(&self) -> Id {
+        self.id
+    }
+    pub fn get_rank(&self) -> f64 {
+        self.rank
+    }
+    pub fn get_story_date(&self) -> Option<&khronos::DateTime> {
+        self.story_date.as_ref()
+    }
+    pub fn set_story_date(&mut self, date: Option<khronos::DateTime>) {
+        self.story_date = date;
+    }
     pub fn add_involved_entity(&mut self, entity: Id) {
         self.involved_entities.push(entity);
     }
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.has_tag(tag) {
+            self.tags.push(String::from(tag));
+        }
+    }
+    pub fn remove_tag(&mut self, tag: &str) {
+        self.tags.retain(|t| t != tag);
+    }
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+    pub fn get_tags(&self) -> &[String] {
+        &self.tags
+    }
+    pub fn get_status(&self) -> EProgressionStatus {
+        self.status
+    }
+    pub fn set_status(&mut self, status: EProgressionStatus) {
+        self.status = status;
+    }
+    pub fn get_kind(&self) -> EProgressionKind {
+        self.kind
+    }
+    pub fn set_kind(&mut self, kind: EProgressionKind) {
+        self.kind = kind;
+    }
 }
 //impl PartialEq and Hash for Progression based on entity id
 impl PartialEq for Progression {
@@ -93,22 +181,112 @@ impl Manuscript {
             progressions: HashMap::new(),
         }
     }
-    pub fn add_progression(&mut self, progression: Progression) -> Result<(), String> {
-        //check if we already have a progression with this ordering
-        if self
-            .progressions
+    //Returns a rank that places a progression at [index] of the narrative order, if there is room left
+    // between the ranks of the progressions around it
+    fn get_rank_at(&self, index: usize) -> Option<f64> {
+        let ranks = self
+            .get_ordered_progressions()
+            .iter()
+            .map(|p| p.rank)
+            .collect::<Vec<_>>();
+        let before = index.checked_sub(1).and_then(|i| ranks.get(i)).copied();
+        let after = ranks.get(index).copied();
+        let rank = match (before, after) {
+            (None, None) => 1.0,
+            (Some(b), None) => b + 1.0,
+            (None, Some(a)) => a - 1.0,
+            (Some(b), Some(a)) => (b + a) / 2.0,
+        };
+        match before.map_or(false, |b| rank <= b) || after.map_or(false, |a| rank >= a) {
+            true => None,
+            false => Some(rank),
+        }
+    }
+    //Like [Manuscript::get_rank_at], but spreads out the ranks of the other progressions first if there is
+    // no room left between them
+    fn make_rank_at(&mut self, index: usize) -> f64 {
+        match self.get_rank_at(index) {
+            Some(rank) => rank,
+            None => {
+                self.spread_ranks();
+                //the ranks are now 1, 2, 3..., so there is room halfway past the rank of the one before
+                index as f64 + 0.5
+            }
+        }
+    }
+    //Ranks the progressions 1, 2, 3... in their current order
+    fn spread_ranks(&mut self) {
+        let order = self
+            .get_ordered_progressions()
             .iter()
-            .any(|(_, p)| p.ordering == progression.ordering)
-        {
-            Err(format!(
-                "Manuscript already has a progression with ordering {}",
-                progression.ordering
+            .map(|p| p.id)
+            .collect::<Vec<_>>();
+        self.set_order(order);
+    }
+    //Ranks the progressions 1, 2, 3... in the given order
+    fn set_order(&mut self, order: Vec<Id>) {
+        for (i, id) in order.into_iter().enumerate() {
+            self.progressions.get_mut(&id).unwrap().rank = (i + 1) as f64;
+        }
+    }
+    fn insert_at(&mut self, mut progression: Progression, index: usize) -> Result<()> {
+        if self.progressions.contains_key(&progression.id) {
+            return Err(anyhow!(
+                "Manuscript already has the progression '{}'",
+                progression.name
+            ));
+        }
+        if index > self.progressions.len() {
+            return Err(anyhow!(
+                "Position {} is past the end of the manuscript, at {}",
+                index,
+                self.progressions.len()
+            ));
+        }
+        progression.rank = self.make_rank_at(index);
+        self.progressions.insert(progression.id, progression);
+        Ok(())
+    }
+    fn get_position_of(&self, progression_id: Id) -> Result<usize> {
+        self.get_position(progression_id).ok_or_else(|| {
+            anyhow!(
+                "Progression with id {} is not in the manuscript",
+                progression_id
             )
-            .to_string())
-        } else {
-            self.progressions.insert(progression.id, progression);
-            Ok(())
+        })
+    }
+    ///Adds the progression at the end of the manuscript
+    pub fn add_progression(&mut self, progression: Progression) -> Result<()> {
+        self.insert_at(progression, self.progressions.len())
+    }
+    ///Inserts the progression just before another
+    pub fn insert_before(&mut self, progression: Progression, before: Id) -> Result<()> {
+        let index = self.get_position_of(before)?;
+        self.insert_at(progression, index)
+    }
+    ///Inserts the progression just after another
+    pub fn insert_after(&mut self, progression: Progression, after: Id) -> Result<()> {
+        let index = self.get_position_of(after)?;
+        self.insert_at(progression, index + 1)
+    }
+    ///Moves the progression to the given position in the narrative, where 0 is the first
+    pub fn move_to(&mut self, progression_id: Id, index: usize) -> Result<()> {
+        self.get_position_of(progression_id)?;
+        if index >= self.progressions.len() {
+            return Err(anyhow!(
+                "Position {} is past the end of the manuscript, at {}",
+                index,
+                self.progressions.len() - 1
+            ));
         }
+        let progression = self.progressions.remove(&progression_id).unwrap();
+        self.insert_at(progression, index)
+    }
+    ///Returns the position of the progression in the narrative, where 0 is the first
+    pub fn get_position(&self, progression_id: Id) -> Option<usize> {
+        self.get_ordered_progressions()
+            .iter()
+            .position(|p| p.id == progression_id)
     }
     pub fn get_progression(&self, progression_id: Id) -> Option<&Progression> {
         self.progressions.get(&progression_id)
@@ -122,11 +300,46 @@ impl Manuscript {
     pub fn get_all_progressions(&self) -> Vec<&Progression> {
         self.progressions.values().collect()
     }
+    ///Returns the progressions in the order they are told
     pub fn get_ordered_progressions(&self) -> Vec<&Progression> {
         let mut sorted = self.progressions.values().collect::<Vec<&Progression>>();
-        sorted.sort_by(|a, b| a.ordering.cmp(&b.ordering));
+        sorted.sort_by(|a, b| a.rank.total_cmp(&b.rank));
         sorted
     }
+    ///Returns the progressions in the order they take place in the story, measured in the calendar of their
+    /// dates. Progressions without a date follow, in the order they are told.
+    pub fn get_chronological_progressions(
+        &self,
+        calendar: &khronos::System,
+    ) -> Result<Vec<&Progression>> {
+        let (mut dated, undated): (Vec<_>, Vec<_>) = self
+            .get_ordered_progressions()
+            .into_iter()
+            .partition(|p| p.story_date.is_some());
+        for p in dated.iter() {
+            calendar.verify_date(p.story_date.as_ref().unwrap())?;
+        }
+        dated.sort_by(|a, b| {
+            calendar
+                .compare(
+                    a.story_date.as_ref().unwrap(),
+                    b.story_date.as_ref().unwrap(),
+                )
+                .unwrap()
+        });
+        dated.extend(undated);
+        Ok(dated)
+    }
+    ///Reorders the narrative to follow the chronology of the story, see [Manuscript::get_chronological_progressions]
+    pub fn reorder_chronologically(&mut self, calendar: &khronos::System) -> Result<()> {
+        let order = self
+            .get_chronological_progressions(calendar)?
+            .iter()
+            .map(|p| p.id)
+            .collect::<Vec<_>>();
+        self.set_order(order);
+        Ok(())
+    }
 }
 
 //impl PartialEq and Hash for Manuscript based on entity id
@@ -216,6 +429,30 @@ impl ProjectMetaData {
             time_meta: TimeMetaData::new(),
         }
     }
+    pub fn get_author(&self) -> &str {
+        &self.author
+    }
+    pub fn set_author(&mut self, author: &str) {
+        self.author = String::from(author);
+    }
+    pub fn get_description(&self) -> &str {
+        &self.description
+    }
+    pub fn set_description(&mut self, description: &str) {
+        self.description = String::from(description);
+    }
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn set_name(&mut self, name: &str) {
+        self.name = String::from(name);
+    }
+    pub fn get_version(&self) -> &str {
+        &self.version
+    }
+    pub fn set_version(&mut self, version: &str) {
+        self.version = String::from(version);
+    }
 }
 #[nvproc::bincode_derive]
 pub struct Project {
@@ -223,10 +460,13 @@ pub struct Project {
     pub project_meta_data: ProjectMetaData,
     pub description: String,
     pub manuscripts: HashMap<Id, Manuscript>,
-    pub scenes: HashMap<Id, Scene>,
+    ///The acts, scenes and events of the story, in the order they are told
+    pub story: Story,
     pub arcs: HashMap<Id, WorldArc>,
     pub timelines: HashMap<Id, Timeline>,
     pub notes: HashMap<Id, Note>,
+    ///The calendars of the cultures of the world, all anchored to the same absolute timeline
+    pub calendars: HashMap<Id, khronos::System>,
 }
 impl Project {
     pub fn new(description: &str) -> Self {
@@ -235,10 +475,11 @@ impl Project {
             project_meta_data: ProjectMetaData::new(),
             description: String::from(description),
             manuscripts: HashMap::new(),
-            scenes: HashMap::new(),
+            story: Story::new(),
             arcs: HashMap::new(),
             timelines: HashMap::new(),
             notes: HashMap::new(),
+            calendars: HashMap::new(),
         }
     }
     pub fn new_empty() -> Self {
@@ -247,10 +488,11 @@ impl Project {
             project_meta_data: ProjectMetaData::new(),
             description: String::new(),
             manuscripts: HashMap::new(),
-            scenes: HashMap::new(),
+            story: Story::new(),
             arcs: HashMap::new(),
             timelines: HashMap::new(),
             notes: HashMap::new(),
+            calendars: HashMap::new(),
         }
     }
     pub fn set_metadata(&mut self, meta_data: ProjectMetaData) {
@@ -271,17 +513,70 @@ impl Project {
     pub fn get_all_manuscripts(&self) -> Vec<&Manuscript> {
         self.manuscripts.values().collect()
     }
-}
-
-///An [Event] represents a mostly singular point in the narrative with a well defined time and place.
-#[nvproc::bincode_derive]
-#[nvproc::serde_derive]
-pub struct Event {
-    id: Id,
-    pub name: String,
-    pub location: String,
-    pub description: String,
-    involved_entities: Vec<Id>,
+    ///Assembles the text of the manuscript into a single document, with the metadata of the project as front matter
+    pub fn compile_manuscript(
+        &self,
+        manuscript_id: Id,
+        options: &compile::CompileOptions,
+    ) -> Result<String> {
+        let manuscript = self
+            .get_manuscript(manuscript_id)
+            .ok_or_else(|| anyhow!("Manuscript with id {} does not exist", manuscript_id))?;
+        Ok(compile::compile(
+            manuscript,
+            Some(&self.project_meta_data),
+            options,
+        ))
+    }
+    pub fn add_calendar(&mut self, calendar: khronos::System) -> Id {
+        let id = calendar.get_id();
+        self.calendars.insert(id, calendar);
+        id
+    }
+    pub fn get_calendar(&self, calendar_id: Id) -> Option<&khronos::System> {
+        self.calendars.get(&calendar_id)
+    }
+    pub fn get_calendar_mut(&mut self, calendar_id: Id) -> Option<&mut khronos::System> {
+        self.calendars.get_mut(&calendar_id)
+    }
+    pub fn remove_calendar(&mut self, calendar_id: Id) -> Option<khronos::System> {
+        self.calendars.remove(&calendar_id)
+    }
+    ///Converts a date of one calendar to the date of another at the same moment, measured from their universal 0 Epoch
+    pub fn convert_date(
+        &self,
+        date: &khronos::DateTime,
+        from: Id,
+        to: Id,
+    ) -> Result<khronos::DateTime> {
+        let get = |id| {
+            self.get_calendar(id)
+                .ok_or_else(|| anyhow!("Calendar with id {} does not exist", id))
+        };
+        get(from)?.convert_to_system(date, get(to)?, 0)
+    }
+    pub fn add_timeline(&mut self, timeline: Timeline) -> Id {
+        let id = timeline.get_id();
+        self.timelines.insert(id, timeline);
+        id
+    }
+    ///Creates an empty timeline measured in one of the calendars of the project
+    pub fn new_timeline(&mut self, name: &str, calendar_id: Id) -> Result<Id> {
+        let calendar = self
+            .get_calendar(calendar_id)
+            .ok_or_else(|| anyhow!("Calendar with id {} does not exist", calendar_id))?
+            .clone();
+        Ok(self.add_timeline(Timeline::new(name, calendar)))
+    }
+    pub fn get_timeline(&self, timeline_id: Id) -> Option<&Timeline> {
+        self.timelines.get(&timeline_id)
+    }
+    pub fn get_timeline_mut(&mut self, timeline_id: Id) -> Option<&mut Timeline> {
+        self.timelines.get_mut(&timeline_id)
+    }
+    pub fn remove_timeline(&mut self, timeline_id: Id) -> Option<Timeline> {
+        self.timelines.remove(&timeline_id)
+    }
 }
 
 ///An [WorldArc] is a series of events that involve many [Entity]s.
@@ -291,22 +586,12 @@ pub struct WorldArc {
     id: Id,
     pub name: String,
     pub description: String,
-    pub events: Vec<Event>,
+    ///The entities of the events, each with an [EventComponent](ecs::component::components::EventComponent)
+    pub events: Vec<Id>,
 }
 #[nvproc::bincode_derive]
 
-pub struct Timeline {}
-#[nvproc::bincode_derive]
-
 pub struct Arc {}
-#[nvproc::bincode_derive]
-
-///A scene is a collection of [Entity]s and [Event]s.
-pub struct Scene {
-    pub id: Id,
-    pub name: String,
-    pub description: String,
-}
 
 ///A simple generic system for tracking time
 pub mod khronos;
@@ -0,0 +1,450 @@
+//! The structure of the story: Acts, divided into Scenes, made of Events.
+//!
+//! A [Story] keeps its [Act]s, the [Scene]s of each act and the events of each scene in the order they are
+//! told. Events are the entities of the world with an [EventComponent](crate::ecs::component::components::EventComponent),
+//! and scenes link to the entities present in them and the [Progression]s that realise them on the page.
+use super::*;
+
+///A scene is a collection of [Entity]s and the events they take part in.
+#[nvproc::bincode_derive]
+pub struct Scene {
+    pub id: Id,
+    pub name: String,
+    pub description: String,
+    ///The character whose point of view the scene is told from
+    pub pov: Option<Id>,
+    ///The entity of the place the scene is set in
+    pub location: Option<Id>,
+    present_entities: Vec<Id>,
+    progressions: Vec<Id>,
+    events: Vec<Id>,
+}
+impl Scene {
+    pub fn new(name: &str, description: &str) -> Self {
+        Scene {
+            id: uuid::gen_128(),
+            name: String::from(name),
+            description: String::from(description),
+            pov: None,
+            location: None,
+            present_entities: Vec::new(),
+            progressions: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+    pub fn get_id(&self) -> Id {
+        self.id
+    }
+    pub fn add_present_entity(&mut self, entity: Id) {
+        if !self.present_entities.contains(&entity) {
+            self.present_entities.push(entity);
+        }
+    }
+    pub fn remove_present_entity(&mut self, entity: Id) {
+        self.present_entities.retain(|e| *e != entity);
+    }
+    pub fn get_present_entities(&self) -> &[Id] {
+        &self.present_entities
+    }
+    ///Links the scene to a [Progression] that realises it
+    pub fn add_progression(&mut self, progression: Id) {
+        if !self.progressions.contains(&progression) {
+            self.progressions.push(progression);
+        }
+    }
+    pub fn remove_progression(&mut self, progression: Id) {
+        self.progressions.retain(|p| *p != progression);
+    }
+    pub fn get_progressions(&self) -> &[Id] {
+        &self.progressions
+    }
+    //Events are only added through the [Story], which checks that they are told in a single scene
+    fn insert_event(&mut self, index: usize, event: Id) -> Result<()> {
+        check_index(index, self.events.len())?;
+        self.events.insert(index, event);
+        Ok(())
+    }
+    fn remove_event(&mut self, event: Id) -> bool {
+        let len = self.events.len();
+        self.events.retain(|e| *e != event);
+        self.events.len() != len
+    }
+    ///Returns the events of the scene, in the order they are told. Events are added to a scene with
+    /// [Story::add_event].
+    pub fn get_events(&self) -> &[Id] {
+        &self.events
+    }
+    //Appends the contents of another scene, keeping the point of view and location of this one if it has them
+    fn absorb(&mut self, other: Scene) {
+        self.pov = self.pov.or(other.pov);
+        self.location = self.location.or(other.location);
+        for e in other.present_entities {
+            self.add_present_entity(e);
+        }
+        for p in other.progressions {
+            self.add_progression(p);
+        }
+        self.events.extend(other.events);
+    }
+}
+
+///An act is a sequence of [Scene]s
+#[nvproc::bincode_derive]
+pub struct Act {
+    id: Id,
+    pub name: String,
+    pub description: String,
+    scenes: Vec<Scene>,
+}
+impl Act {
+    pub fn new(name: &str, description: &str) -> Self {
+        Act {
+            id: uuid::gen_128(),
+            name: String::from(name),
+            description: String::from(description),
+            scenes: Vec::new(),
+        }
+    }
+    pub fn get_id(&self) -> Id {
+        self.id
+    }
+    ///Returns the scenes of the act, in the order they are told
+    pub fn get_scenes(&self) -> &[Scene] {
+        &self.scenes
+    }
+}
+
+fn check_index(index: usize, len: usize) -> Result<()> {
+    match index <= len {
+        true => Ok(()),
+        false => Err(anyhow!("Position {} is past the end, at {}", index, len)),
+    }
+}
+
+///The acts of the story, in the order they are told
+#[nvproc::bincode_derive]
+#[derive(Default)]
+pub struct Story {
+    acts: Vec<Act>,
+}
+impl Story {
+    pub fn new() -> Self {
+        Story { acts: Vec::new() }
+    }
+    pub fn get_acts(&self) -> &[Act] {
+        &self.acts
+    }
+    fn get_act_index(&self, act_id: Id) -> Result<usize> {
+        self.acts
+            .iter()
+            .position(|a| a.id == act_id)
+            .ok_or_else(|| anyhow!("Act with id {} does not exist", act_id))
+    }
+    //Returns the index of the act of the scene, and of the scene within it
+    fn get_scene_index(&self, scene_id: Id) -> Result<(usize, usize)> {
+        self.acts
+            .iter()
+            .enumerate()
+            .find_map(|(a, act)| {
+                act.scenes
+                    .iter()
+                    .position(|s| s.id == scene_id)
+                    .map(|s| (a, s))
+            })
+            .ok_or_else(|| anyhow!("Scene with id {} does not exist", scene_id))
+    }
+    ///Adds the act at the end of the story
+    pub fn add_act(&mut self, act: Act) -> Id {
+        let id = act.id;
+        self.acts.push(act);
+        id
+    }
+    pub fn insert_act(&mut self, index: usize, act: Act) -> Result<Id> {
+        check_index(index, self.acts.len())?;
+        let id = act.id;
+        self.acts.insert(index, act);
+        Ok(id)
+    }
+    pub fn get_act(&self, act_id: Id) -> Option<&Act> {
+        self.acts.iter().find(|a| a.id == act_id)
+    }
+    pub fn get_act_mut(&mut self, act_id: Id) -> Option<&mut Act> {
+        self.acts.iter_mut().find(|a| a.id == act_id)
+    }
+    ///Removes the act, along with all of its scenes
+    pub fn remove_act(&mut self, act_id: Id) -> Option<Act> {
+        let index = self.get_act_index(act_id).ok()?;
+        Some(self.acts.remove(index))
+    }
+    ///Moves the act to the given position among the acts
+    pub fn move_act(&mut self, act_id: Id, index: usize) -> Result<()> {
+        let from = self.get_act_index(act_id)?;
+        check_index(index, self.acts.len() - 1)?;
+        let act = self.acts.remove(from);
+        self.acts.insert(index, act);
+        Ok(())
+    }
+    ///Splits the act in two before the scene at [index], returning the id of the new act, which follows
+    /// the original and holds the rest of its scenes
+    pub fn split_act(&mut self, act_id: Id, index: usize, name: &str) -> Result<Id> {
+        let a = self.get_act_index(act_id)?;
+        check_index(index, self.acts[a].scenes.len())?;
+        let mut act = Act::new(name, "");
+        act.scenes = self.acts[a].scenes.split_off(index);
+        self.insert_act(a + 1, act)
+    }
+    ///Appends the scenes of the second act to the first, and removes the second
+    pub fn merge_acts(&mut self, first: Id, second: Id) -> Result<()> {
+        if first == second {
+            return Err(anyhow!("Cannot merge act {} with itself", first));
+        }
+        self.get_act_index(first)?;
+        self.get_act_index(second)?;
+        let scenes = self.remove_act(second).unwrap().scenes;
+        let a = self.get_act_index(first)?;
+        self.acts[a].scenes.extend(scenes);
+        Ok(())
+    }
+    ///Adds the scene at the end of the act
+    pub fn add_scene(&mut self, act_id: Id, scene: Scene) -> Result<Id> {
+        let a = self.get_act_index(act_id)?;
+        self.insert_scene(act_id, self.acts[a].scenes.len(), scene)
+    }
+    pub fn insert_scene(&mut self, act_id: Id, index: usize, scene: Scene) -> Result<Id> {
+        if self.get_scene(scene.id).is_some() {
+            return Err(anyhow!("Scene '{}' is already in the story", scene.name));
+        }
+        let a = self.get_act_index(act_id)?;
+        check_index(index, self.acts[a].scenes.len())?;
+        let id = scene.id;
+        self.acts[a].scenes.insert(index, scene);
+        Ok(id)
+    }
+    pub fn get_scene(&self, scene_id: Id) -> Option<&Scene> {
+        let (a, s) = self.get_scene_index(scene_id).ok()?;
+        Some(&self.acts[a].scenes[s])
+    }
+    pub fn get_scene_mut(&mut self, scene_id: Id) -> Option<&mut Scene> {
+        let (a, s) = self.get_scene_index(scene_id).ok()?;
+        Some(&mut self.acts[a].scenes[s])
+    }
+    ///Returns the id of the act the scene is in
+    pub fn get_scene_act(&self, scene_id: Id) -> Option<Id> {
+        let (a, _) = self.get_scene_index(scene_id).ok()?;
+        Some(self.acts[a].id)
+    }
+    pub fn remove_scene(&mut self, scene_id: Id) -> Option<Scene> {
+        let (a, s) = self.get_scene_index(scene_id).ok()?;
+        Some(self.acts[a].scenes.remove(s))
+    }
+    ///Moves the scene to the given position in an act, which may be the one it is already in
+    pub fn move_scene(&mut self, scene_id: Id, act_id: Id, index: usize) -> Result<()> {
+        let (from, s) = self.get_scene_index(scene_id)?;
+        let to = self.get_act_index(act_id)?;
+        let len = self.acts[to].scenes.len() - if from == to { 1 } else { 0 };
+        check_index(index, len)?;
+        let scene = self.acts[from].scenes.remove(s);
+        self.acts[to].scenes.insert(index, scene);
+        Ok(())
+    }
+    ///Splits the scene in two before the event at [index], returning the id of the new scene. It follows the
+    /// original, holds the rest of its events, and is set in the same place, from the same point of view, with
+    /// the same entities present. The progressions stay linked to the original scene only.
+    pub fn split_scene(&mut self, scene_id: Id, index: usize, name: &str) -> Result<Id> {
+        let (a, s) = self.get_scene_index(scene_id)?;
+        let original = &mut self.acts[a].scenes[s];
+        check_index(index, original.events.len())?;
+        let mut scene = Scene::new(name, "");
+        scene.pov = original.pov;
+        scene.location = original.location;
+        scene.present_entities = original.present_entities.clone();
+        scene.events = original.events.split_off(index);
+        let id = scene.id;
+        self.acts[a].scenes.insert(s + 1, scene);
+        Ok(id)
+    }
+    ///Appends the events of the second scene to the first, along with the entities present in it and the
+    /// progressions that realise it, and removes the second. The first keeps its own point of view and location,
+    /// if it has them.
+    pub fn merge_scenes(&mut self, first: Id, second: Id) -> Result<()> {
+        if first == second {
+            return Err(anyhow!("Cannot merge scene {} with itself", first));
+        }
+        self.get_scene_index(first)?;
+        self.get_scene_index(second)?;
+        let second = self.remove_scene(second).unwrap();
+        self.get_scene_mut(first).unwrap().absorb(second);
+        Ok(())
+    }
+    ///Adds the event at the end of the scene
+    pub fn add_event(&mut self, scene_id: Id, event: Id) -> Result<()> {
+        let (a, s) = self.get_scene_index(scene_id)?;
+        self.insert_event(scene_id, self.acts[a].scenes[s].events.len(), event)
+    }
+    ///Inserts the event at the given position in the scene. An event is told in a single scene, so it may not
+    /// already be in any scene of the story.
+    pub fn insert_event(&mut self, scene_id: Id, index: usize, event: Id) -> Result<()> {
+        if let Some(other) = self.get_event_scene(event) {
+            return Err(anyhow!(
+                "Event {} is already in scene '{}'",
+                event,
+                self.get_scene(other).unwrap().name
+            ));
+        }
+        let (a, s) = self.get_scene_index(scene_id)?;
+        self.acts[a].scenes[s].insert_event(index, event)
+    }
+    ///Removes the event from the scene it is told in, returning the id of the scene
+    pub fn remove_event(&mut self, event: Id) -> Option<Id> {
+        let scene_id = self.get_event_scene(event)?;
+        self.get_scene_mut(scene_id).unwrap().remove_event(event);
+        Some(scene_id)
+    }
+    ///Moves the event to the given position in a scene, which may be the one it is already in
+    pub fn move_event(&mut self, event: Id, scene_id: Id, index: usize) -> Result<()> {
+        let from = self
+            .get_event_scene(event)
+            .ok_or_else(|| anyhow!("Event {} is not in any scene", event))?;
+        let to = self
+            .get_scene(scene_id)
+            .ok_or_else(|| anyhow!("Scene with id {} does not exist", scene_id))?;
+        let len = to.events.len() - if from == scene_id { 1 } else { 0 };
+        check_index(index, len)?;
+        self.get_scene_mut(from).unwrap().remove_event(event);
+        self.get_scene_mut(scene_id)
+            .unwrap()
+            .insert_event(index, event)
+    }
+    ///Returns the id of the scene the event is told in
+    pub fn get_event_scene(&self, event: Id) -> Option<Id> {
+        self.get_ordered_scenes()
+            .into_iter()
+            .find(|s| s.events.contains(&event))
+            .map(|s| s.id)
+    }
+    ///Returns every scene of the story, in the order they are told
+    pub fn get_ordered_scenes(&self) -> Vec<&Scene> {
+        self.acts.iter().flat_map(|a| a.scenes.iter()).collect()
+    }
+    ///Returns every event of the story, in the order they are told
+    pub fn get_ordered_events(&self) -> Vec<Id> {
+        self.get_ordered_scenes()
+            .into_iter()
+            .flat_map(|s| s.events.iter().copied())
+            .collect()
+    }
+    ///Returns every scene the entity is present in or seen from
+    pub fn get_scenes_with_entity(&self, entity: Id) -> Vec<&Scene> {
+        self.get_ordered_scenes()
+            .into_iter()
+            .filter(|s| s.present_entities.contains(&entity) || s.pov == Some(entity))
+            .collect()
+    }
+    ///Returns every scene the progression realises
+    pub fn get_scenes_of_progression(&self, progression: Id) -> Vec<&Scene> {
+        self.get_ordered_scenes()
+            .into_iter()
+            .filter(|s| s.progressions.contains(&progression))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test_story {
+    use super::*;
+
+    fn scene_names(story: &Story) -> Vec<String> {
+        story
+            .get_ordered_scenes()
+            .iter()
+            .map(|s| s.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_story_structure() {
+        let mut story = Story::new();
+        let first = story.add_act(Act::new("Act I", "The call"));
+        let second = story.add_act(Act::new("Act II", "The ordeal"));
+        let hero = uuid::gen_128();
+        let mentor = uuid::gen_128();
+        let village = uuid::gen_128();
+        let events = (0..5).map(|_| uuid::gen_128()).collect::<Vec<_>>();
+
+        let mut opening = Scene::new("Opening", "");
+        opening.pov = Some(hero);
+        opening.location = Some(village);
+        opening.add_present_entity(mentor);
+        opening.add_progression(1);
+        let opening = story.add_scene(first, opening).unwrap();
+        let journey = story.add_scene(second, Scene::new("Journey", "")).unwrap();
+        let ordeal = story.add_scene(second, Scene::new("Ordeal", "")).unwrap();
+        for e in &events[..4] {
+            story.add_event(opening, *e).unwrap();
+        }
+        //an event is told in a single scene
+        assert!(story.add_event(opening, events[0]).is_err());
+        assert!(story.insert_event(ordeal, 0, events[1]).is_err());
+        assert!(story.insert_event(ordeal, 1, events[4]).is_err());
+        assert!(story.add_event(uuid::gen_128(), events[4]).is_err());
+        assert_eq!(scene_names(&story), ["Opening", "Journey", "Ordeal"]);
+
+        //reordering and moving scenes
+        story.move_scene(ordeal, second, 0).unwrap();
+        assert_eq!(scene_names(&story), ["Opening", "Ordeal", "Journey"]);
+        story.move_scene(journey, first, 0).unwrap();
+        assert_eq!(scene_names(&story), ["Journey", "Opening", "Ordeal"]);
+        assert_eq!(story.get_scene_act(journey), Some(first));
+        assert!(story.move_scene(ordeal, second, 1).is_err());
+        story.move_act(second, 0).unwrap();
+        assert_eq!(scene_names(&story), ["Ordeal", "Journey", "Opening"]);
+        story.move_act(second, 1).unwrap();
+
+        //splitting a scene keeps its setting, but not its progressions
+        let refusal = story.split_scene(opening, 2, "Refusal").unwrap();
+        assert_eq!(
+            scene_names(&story),
+            ["Journey", "Opening", "Refusal", "Ordeal"]
+        );
+        let split = story.get_scene(refusal).unwrap();
+        assert_eq!(split.get_events(), &events[2..4]);
+        assert_eq!(split.pov, Some(hero));
+        assert_eq!(split.get_present_entities(), &[mentor]);
+        assert!(split.get_progressions().is_empty());
+        assert_eq!(story.get_scene(opening).unwrap().get_events(), &events[..2]);
+
+        //moving events between scenes
+        story.move_event(events[3], ordeal, 0).unwrap();
+        story.add_event(ordeal, events[4]).unwrap();
+        story.move_event(events[4], ordeal, 0).unwrap();
+        assert_eq!(story.get_event_scene(events[3]), Some(ordeal));
+        assert_eq!(
+            story.get_ordered_events(),
+            [events[0], events[1], events[2], events[4], events[3]]
+        );
+        assert!(story.move_event(uuid::gen_128(), ordeal, 0).is_err());
+        assert_eq!(story.remove_event(events[1]), Some(opening));
+        assert_eq!(story.remove_event(events[1]), None);
+        story.insert_event(opening, 1, events[1]).unwrap();
+
+        //merging scenes across acts
+        story.get_scene_mut(ordeal).unwrap().add_progression(2);
+        story.merge_scenes(refusal, ordeal).unwrap();
+        assert_eq!(scene_names(&story), ["Journey", "Opening", "Refusal"]);
+        let merged = story.get_scene(refusal).unwrap();
+        assert_eq!(merged.get_events(), [events[2], events[4], events[3]]);
+        assert_eq!(merged.get_progressions(), &[2]);
+        assert_eq!(story.get_scenes_of_progression(1).len(), 1);
+        assert_eq!(story.get_scenes_with_entity(hero).len(), 2);
+        assert!(story.merge_scenes(refusal, refusal).is_err());
+
+        //splitting and merging acts
+        let third = story.split_act(first, 1, "Act III").unwrap();
+        assert_eq!(story.get_acts().len(), 3);
+        assert_eq!(story.get_scene_act(opening), Some(third));
+        story.merge_acts(first, third).unwrap();
+        assert_eq!(story.get_acts().len(), 2);
+        assert_eq!(scene_names(&story), ["Journey", "Opening", "Refusal"]);
+        assert!(story.remove_act(second).unwrap().get_scenes().is_empty());
+    }
+}
@@ -8,3 +8,108 @@ fn test_serialize() {
     let project = Project::new("test_description");
     let f = || "hello";
 }
+
+fn get_names(progressions: &[&Progression]) -> Vec<String> {
+    progressions.iter().map(|p| p.name.clone()).collect()
+}
+fn new_progression(name: &str) -> Progression {
+    Progression::new_draft(String::from(name), String::new(), String::new())
+}
+
+#[test]
+fn test_progression_ordering() {
+    let mut manuscript = Manuscript::new(String::from("Draft"), String::new());
+    let one = new_progression("One");
+    let one_id = one.get_id();
+    manuscript.add_progression(one).unwrap();
+    let four = new_progression("Four");
+    let four_id = four.get_id();
+    manuscript.add_progression(four).unwrap();
+    manuscript
+        .insert_after(new_progression("Two"), one_id)
+        .unwrap();
+    manuscript
+        .insert_before(new_progression("Three"), four_id)
+        .unwrap();
+    manuscript
+        .insert_before(new_progression("Zero"), one_id)
+        .unwrap();
+    assert_eq!(
+        get_names(&manuscript.get_ordered_progressions()),
+        ["Zero", "One", "Two", "Three", "Four"]
+    );
+    assert!(manuscript
+        .insert_after(new_progression("Lost"), uuid::gen_128())
+        .is_err());
+
+    manuscript.move_to(four_id, 0).unwrap();
+    assert_eq!(manuscript.get_position(four_id), Some(0));
+    manuscript.move_to(four_id, 4).unwrap();
+    assert_eq!(
+        get_names(&manuscript.get_ordered_progressions()),
+        ["Zero", "One", "Two", "Three", "Four"]
+    );
+    assert!(manuscript.move_to(four_id, 5).is_err());
+
+    //inserting again and again at the same place eventually spreads the ranks out
+    let mut last = one_id;
+    for i in 0..100 {
+        let p = new_progression(&format!("Interlude {}", i));
+        let id = p.get_id();
+        manuscript.insert_before(p, last).unwrap();
+        last = id;
+    }
+    let ordered = get_names(&manuscript.get_ordered_progressions());
+    assert_eq!(ordered.len(), 105);
+    assert_eq!(ordered[1], "Interlude 99");
+    assert_eq!(ordered[100], "Interlude 0");
+    assert_eq!(ordered[101], "One");
+
+    //the ordering given to the deprecated constructor is replaced by the manuscript
+    #[allow(deprecated)]
+    let legacy = Progression::new(String::from("Legacy"), String::new(), String::new(), 0);
+    manuscript.add_progression(legacy).unwrap();
+    let ordered = get_names(&manuscript.get_ordered_progressions());
+    assert_eq!(ordered.last().unwrap(), "Legacy");
+}
+#[test]
+fn test_chronological_progressions() {
+    use khronos::{Coef, Cycle, DateTime, System, UnitCycle};
+    let month = Cycle::new(1, "Month", "", Coef::Inverted(12), None, 0).unwrap();
+    let unit = UnitCycle {
+        name: String::from("Year"),
+        description: String::new(),
+    };
+    let calendar = System::from_cycles(unit, vec![month]).unwrap();
+    let date = |d: &str| DateTime::from_cycle_string(d, &calendar).unwrap();
+
+    let mut manuscript = Manuscript::new(String::from("Draft"), String::new());
+    for (name, story_date) in [
+        ("Present", Some("1020:5")),
+        ("Aside", None),
+        ("Flashback", Some("1000:1")),
+        ("Epilogue", Some("1040:1")),
+    ] {
+        let mut p = new_progression(name);
+        p.set_story_date(story_date.map(date));
+        manuscript.add_progression(p).unwrap();
+    }
+    assert_eq!(
+        get_names(
+            &manuscript
+                .get_chronological_progressions(&calendar)
+                .unwrap()
+        ),
+        ["Flashback", "Present", "Epilogue", "Aside"]
+    );
+    //the narrative order is untouched until the manuscript is reordered
+    assert_eq!(
+        get_names(&manuscript.get_ordered_progressions()),
+        ["Present", "Aside", "Flashback", "Epilogue"]
+    );
+    manuscript.reorder_chronologically(&calendar).unwrap();
+    assert_eq!(
+        get_names(&manuscript.get_ordered_progressions()),
+        ["Flashback", "Present", "Epilogue", "Aside"]
+    );
+}
@@ -0,0 +1,360 @@
+//! Timelines of the events of the world.
+//!
+//! A [Timeline] keeps event entities, those with an [EventComponent], in chronological order, measured in a
+//! calendar of its own. The events themselves live in the [Entman], so the same event may be placed on several
+//! timelines, and is read from the world whenever the timeline needs it. An event happens
+//! either at a date, or sometime between two dates when its time is only vaguely known, such as "sometime in
+//! the Third Age". Events whose times are not known at all cannot be placed on a timeline.
+use super::*;
+use crate::ecs::component::components::EventComponent;
+use crate::ecs::Entman;
+use khronos::DateTime;
+use std::cmp::Ordering;
+
+///When an event happened
+#[nvproc::bincode_derive]
+#[nvproc::serde_derive]
+#[derive(Debug, PartialEq)]
+pub enum EEventTime {
+    At(DateTime),
+    ///Sometime between the two dates, both included
+    Between(DateTime, DateTime),
+}
+impl EEventTime {
+    pub fn get_start(&self) -> &DateTime {
+        match self {
+            EEventTime::At(date) => date,
+            EEventTime::Between(start, _) => start,
+        }
+    }
+    pub fn get_end(&self) -> &DateTime {
+        match self {
+            EEventTime::At(date) => date,
+            EEventTime::Between(_, end) => end,
+        }
+    }
+}
+
+#[nvproc::bincode_derive]
+pub struct Timeline {
+    id: Id,
+    pub name: String,
+    pub description: String,
+    ///The calendar the dates of the events are measured in
+    calendar: khronos::System,
+    ///The event entities, ordered by the start of the events, then by their end
+    events: Vec<Id>,
+}
+impl Timeline {
+    pub fn new(name: &str, calendar: khronos::System) -> Self {
+        Timeline {
+            id: uuid::gen_128(),
+            name: String::from(name),
+            description: String::new(),
+            calendar,
+            events: Vec::new(),
+        }
+    }
+    pub fn get_id(&self) -> Id {
+        self.id
+    }
+    pub fn get_calendar(&self) -> &khronos::System {
+        &self.calendar
+    }
+    //Returns the first and last moment the time may refer to, in unit cycles from the universal 0 Epoch
+    fn get_span(&self, time: &EEventTime) -> Result<(f64, f64)> {
+        self.calendar.verify_date(time.get_start())?;
+        self.calendar.verify_date(time.get_end())?;
+        let start = self.calendar.to_units(time.get_start())?;
+        let end = self.calendar.to_units(time.get_end())?;
+        if self.calendar.compare(time.get_start(), time.get_end())? == Ordering::Greater {
+            return Err(anyhow!(
+                "The event cannot start at {} after it ends at {}",
+                time.get_start(),
+                time.get_end()
+            ));
+        }
+        Ok((start, end.max(start)))
+    }
+    fn get_event_span(&self, em: &Entman, event: Id) -> Result<(f64, f64)> {
+        let component = em.get_event(event)?;
+        match &component.time {
+            Some(time) => self.get_span(time),
+            None => Err(anyhow!("The event '{}' has no time", component.name)),
+        }
+    }
+    //Places the event after every event that does not come later
+    fn place(&mut self, em: &Entman, event: Id) -> Result<()> {
+        let span = self.get_event_span(em, event)?;
+        //events removed from the world since they were placed are kept where they are and skipped,
+        // so they cannot be bisected past
+        let index = self
+            .events
+            .iter()
+            .position(|e| {
+                self.get_event_span(em, *e)
+                    .map_or(false, |other| other > span)
+            })
+            .unwrap_or(self.events.len());
+        self.events.insert(index, event);
+        Ok(())
+    }
+    ///Inserts the event entity in its place on the timeline
+    pub fn insert_event(&mut self, em: &Entman, event: Id) -> Result<()> {
+        if self.contains_event(event) {
+            return Err(anyhow!(
+                "The event '{}' is already on the timeline",
+                em.get_event(event)?.name
+            ));
+        }
+        self.place(em, event)
+    }
+    pub fn contains_event(&self, event: Id) -> bool {
+        self.events.contains(&event)
+    }
+    ///Takes the event off the timeline, returning whether it was on it. The event entity is left untouched.
+    pub fn remove_event(&mut self, event: Id) -> bool {
+        let len = self.events.len();
+        self.events.retain(|e| *e != event);
+        self.events.len() != len
+    }
+    ///Moves the event to another time, both on the timeline and on its entity. The event is left untouched
+    /// if the time is not valid.
+    pub fn move_event(&mut self, em: &mut Entman, event: Id, time: EEventTime) -> Result<()> {
+        if !self.contains_event(event) {
+            return Err(anyhow!("Event with id {} is not on the timeline", event));
+        }
+        self.get_span(&time)?;
+        em.get_component_mut::<EventComponent>(event)?
+            .component
+            .time = Some(time);
+        self.remove_event(event);
+        self.place(em, event)
+    }
+    ///Puts the events back in order, after their times were changed without [Timeline::move_event]. Events
+    /// that no longer have a valid time are taken off the timeline, and returned.
+    pub fn reorder(&mut self, em: &Entman) -> Vec<Id> {
+        let mut spans = Vec::new();
+        let mut dropped = Vec::new();
+        for event in std::mem::take(&mut self.events) {
+            match self.get_event_span(em, event) {
+                Ok(span) => spans.push((span, event)),
+                Err(_) => dropped.push(event),
+            }
+        }
+        //the sort is stable, so simultaneous events keep their order
+        spans.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        self.events = spans.into_iter().map(|(_, e)| e).collect();
+        dropped
+    }
+    ///Returns every event entity in chronological order, including those removed from the world since
+    pub fn get_events(&self) -> &[Id] {
+        &self.events
+    }
+    ///Returns every living event that may have happened between the two dates, both included
+    pub fn get_events_between(
+        &self,
+        em: &Entman,
+        start: &DateTime,
+        end: &DateTime,
+    ) -> Result<Vec<Id>> {
+        let (start, end) = self.get_span(&EEventTime::Between(start.clone(), end.clone()))?;
+        let tolerance = khronos::get_tolerance(start.abs().max(end.abs()));
+        Ok(self
+            .events
+            .iter()
+            .filter(|e| {
+                matches!(self.get_event_span(em, **e), Ok((s, e)) if s <= end + tolerance && e >= start - tolerance)
+            })
+            .copied()
+            .collect())
+    }
+    ///Returns every living event that may have happened at the date
+    pub fn get_events_at(&self, em: &Entman, date: &DateTime) -> Result<Vec<Id>> {
+        self.get_events_between(em, date, date)
+    }
+    ///Returns every living event of the timeline the entity takes part in, in chronological order
+    pub fn get_events_involving(&self, em: &Entman, entity: Id) -> Result<Vec<Id>> {
+        let involving = em.get_events_involving(entity)?;
+        Ok(self
+            .events
+            .iter()
+            .filter(|e| involving.contains(e))
+            .copied()
+            .collect())
+    }
+    ///Returns every living event of the timeline the entity takes part in that may have happened between the
+    /// two dates
+    pub fn get_events_involving_between(
+        &self,
+        em: &Entman,
+        entity: Id,
+        start: &DateTime,
+        end: &DateTime,
+    ) -> Result<Vec<Id>> {
+        let involving = em.get_events_involving(entity)?;
+        Ok(self
+            .get_events_between(em, start, end)?
+            .into_iter()
+            .filter(|e| involving.contains(e))
+            .collect())
+    }
+}
+//impl PartialEq for Timeline based on id
+impl PartialEq for Timeline {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+#[cfg(test)]
+mod test_timeline {
+    use super::*;
+    use crate::ecs::component::components::EEventRole;
+    use khronos::{Coef, Cycle, System, UnitCycle};
+
+    fn get_calendar() -> System {
+        let month = Cycle::new(1, "Month", "", Coef::Inverted(12), None, 0).unwrap();
+        let day = Cycle::new(2, "Day", "", Coef::Inverted(30), None, 1).unwrap();
+        let unit = UnitCycle {
+            name: String::from("Year"),
+            description: String::new(),
+        };
+        System::from_cycles(unit, vec![month, day]).unwrap()
+    }
+    fn date(calendar: &System, date: &str) -> DateTime {
+        DateTime::from_cycle_string(date, calendar).unwrap()
+    }
+    fn names(em: &Entman, events: &[Id]) -> Vec<String> {
+        events
+            .iter()
+            .map(|e| em.get_event(*e).unwrap().name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_timeline() {
+        let mut project = Project::new_empty();
+        let mut em = Entman::new();
+        let calendar = project.add_calendar(get_calendar());
+        let timeline_id = project.new_timeline("History", calendar).unwrap();
+        let hero = em.add_entity();
+        let villain = em.add_entity();
+        let timeline = project.get_timeline_mut(timeline_id).unwrap();
+        let c = timeline.get_calendar().clone();
+        let mut add = |name: &str, time: Option<EEventTime>, involved: &[Id]| {
+            let mut event = EventComponent::new(name, "");
+            event.time = time;
+            let event = em.add_event(event).unwrap();
+            for entity in involved {
+                em.add_participant(event, *entity, EEventRole::Participant)
+                    .unwrap();
+            }
+            event
+        };
+
+        let battle = add(
+            "Battle",
+            Some(EEventTime::At(date(&c, "1020:6:3"))),
+            &[hero, villain],
+        );
+        let exile = add(
+            "Exile",
+            Some(EEventTime::Between(date(&c, "1021"), date(&c, "1030"))),
+            &[villain],
+        );
+        let founding = add("Founding", Some(EEventTime::At(date(&c, "1000:1:1"))), &[]);
+        let rumour = add("Rumour", None, &[]);
+        let paradox = add(
+            "Paradox",
+            Some(EEventTime::Between(date(&c, "1030"), date(&c, "1020"))),
+            &[],
+        );
+        for event in [battle, exile, founding] {
+            timeline.insert_event(&em, event).unwrap();
+        }
+        assert_eq!(
+            names(&em, timeline.get_events()),
+            ["Founding", "Battle", "Exile"]
+        );
+
+        //undated events and events that end before they start cannot be placed
+        assert!(timeline.insert_event(&em, rumour).is_err());
+        assert!(timeline.insert_event(&em, paradox).is_err());
+        assert!(timeline.insert_event(&em, founding).is_err());
+        assert!(timeline.insert_event(&em, hero).is_err());
+
+        timeline
+            .move_event(&mut em, battle, EEventTime::At(date(&c, "1025:2:1")))
+            .unwrap();
+        assert_eq!(
+            names(&em, timeline.get_events()),
+            ["Founding", "Exile", "Battle"]
+        );
+        assert_eq!(
+            em.get_event(battle).unwrap().time,
+            Some(EEventTime::At(date(&c, "1025:2:1")))
+        );
+        let mut invalid = date(&c, "1025:12:1");
+        invalid.measures[1].moment = 13.0;
+        assert!(timeline
+            .move_event(&mut em, battle, EEventTime::At(invalid))
+            .is_err());
+        assert!(timeline
+            .move_event(&mut em, rumour, EEventTime::At(date(&c, "1000")))
+            .is_err());
+        assert_eq!(timeline.get_events().len(), 3);
+
+        //the exile may have happened at any time in its range
+        let between = timeline
+            .get_events_between(&em, &date(&c, "1022:1:1"), &date(&c, "1026"))
+            .unwrap();
+        assert_eq!(names(&em, &between), ["Exile", "Battle"]);
+        let at = timeline.get_events_at(&em, &date(&c, "1000:1:1")).unwrap();
+        assert_eq!(names(&em, &at), ["Founding"]);
+        let involving = timeline
+            .get_events_involving_between(&em, hero, &date(&c, "1000"), &date(&c, "1024"))
+            .unwrap();
+        assert!(involving.is_empty());
+        let involving = timeline.get_events_involving(&em, villain).unwrap();
+        assert_eq!(names(&em, &involving), ["Exile", "Battle"]);
+
+        //times changed on the entities directly are picked up by reordering
+        em.add_participant(founding, hero, EEventRole::Protagonist)
+            .unwrap();
+        em.get_component_mut::<EventComponent>(founding)
+            .unwrap()
+            .component
+            .time = Some(EEventTime::At(date(&c, "1040")));
+        em.get_component_mut::<EventComponent>(exile)
+            .unwrap()
+            .component
+            .time = None;
+        assert_eq!(timeline.reorder(&em), vec![exile]);
+        assert_eq!(names(&em, timeline.get_events()), ["Battle", "Founding"]);
+        let involving = timeline.get_events_involving(&em, hero).unwrap();
+        assert_eq!(involving, [battle, founding]);
+
+        //removed events are skipped
+        em.remove_entity(battle).unwrap();
+        let involving = timeline.get_events_involving(&em, hero).unwrap();
+        assert_eq!(involving, [founding]);
+        assert!(timeline.remove_event(battle));
+        assert!(!timeline.remove_event(battle));
+
+        //and do not move earlier events past later ones
+        let mut dated = |name: &str, cycle_string: &str| {
+            let mut event = EventComponent::new(name, "");
+            event.time = Some(EEventTime::At(date(&c, cycle_string)));
+            em.add_event(event).unwrap()
+        };
+        let coronation = dated("Coronation", "1050");
+        let treaty = dated("Treaty", "1010");
+        timeline.insert_event(&em, coronation).unwrap();
+        em.remove_entity(coronation).unwrap();
+        timeline.insert_event(&em, treaty).unwrap();
+        assert_eq!(timeline.get_events(), [treaty, founding, coronation]);
+        assert!(project.new_timeline("Elsewhere", uuid::gen_128()).is_err());
+        assert!(project.remove_timeline(timeline_id).is_some());
+    }
+}
@@ -929,12 +929,25 @@ pub fn generate_query_ty_tuple_impls(item: TokenStream) -> TokenStream {
 
         impl_body.push(quote! {
             impl <#(#sub_list: QueryTy,)*> QueryTy for (#(#sub_list,)*)  {
+                type Item<'a> = (#(#sub_list ::Item<'a>,)*) where Self: 'a;
+                type ItemMut<'a> = (#(#sub_list ::ItemMut<'a>,)*) where Self: 'a;
+                fn fetch<'a>(comps: &[&'a dyn ComponentTy]) -> Option<Self::Item<'a>> {
+                    Some((#(#sub_list ::fetch(comps)?,)*))
+                }
+                fn fetch_mut<'a>(comps: &mut Vec<&'a mut dyn ComponentTy>) -> Option<Self::ItemMut<'a>> {
+                    Some((#(#sub_list ::fetch_mut(comps)?,)*))
+                }
                 fn generate_sig()->Signature{
                     vec![#(#sub_list ::generate_sig()),*].into()
                 }
                 fn contains<Q:ComponentTy>()->bool{
                     #(#sub_list ::contains::<Q>())||*
                 }
+                fn generate_filter()->QueryFilter{
+                    let mut filter = QueryFilter::new();
+                    #(filter.merge(&#sub_list ::generate_filter());)*
+                    filter
+                }
 
             }
         });